@@ -0,0 +1,36 @@
+extern crate rna_secondary_structure;
+
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use rna_secondary_structure::combinatorics::random_structure;
+use rna_secondary_structure::distance_metrics::{get_mountain_distance, pairwise_mountain_distances};
+use rna_secondary_structure::secondary_structure::PairedSites;
+
+/// Compares the time taken to compute an all-vs-all mountain distance matrix using
+/// pairwise_mountain_distances, which computes each mountain vector once, against naively calling
+/// get_mountain_distance for every pair of structures.
+fn main() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let paired = (0..500).map(|_| random_structure(300, 3, &mut rng)).collect::<Vec<Vec<i64>>>();
+    let structures = paired.iter().map(|p| p as &dyn PairedSites).collect::<Vec<&dyn PairedSites>>();
+
+    let start = Instant::now();
+    let cached = pairwise_mountain_distances(&structures, None).unwrap();
+    println!("pairwise_mountain_distances: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut naive = vec![vec![0.0; structures.len()]; structures.len()];
+    for i in 0..structures.len() {
+        for j in i + 1..structures.len() {
+            let d = get_mountain_distance(structures[i], structures[j], None).unwrap();
+            naive[i][j] = d;
+            naive[j][i] = d;
+        }
+    }
+    println!("naive nested loop: {:?}", start.elapsed());
+
+    assert_eq!(cached, naive);
+}
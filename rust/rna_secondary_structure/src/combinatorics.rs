@@ -1,11 +1,13 @@
-//! A tiny module for counting non-pseudoknotted secondary structures.
+//! A tiny module for counting non-pseudoknotted and pseudoknotted secondary structures.
 
 extern crate num_bigint;
 extern crate num_traits;
 
 use cached::proc_macro::cached;
 use num_bigint::BigUint;
-use num_traits::One;
+use num_traits::{One, Zero};
+use rand::Rng;
+use std::collections::HashMap;
 use std::ops::{Add, MulAssign};
 
 #[cached]
@@ -24,9 +26,216 @@ fn _count_structures(n: i64, mingap: i64) -> BigUint {
 
 /// Returns the count of possible non-pseudoknotted secondary structures of a specified length, n,
 /// with at least 'mingap' unpaired nucleotides between every base-pair.
+///
+/// The counts are memoized in a process-global cache keyed on `(n, mingap)`, which is never
+/// cleared. Use [count_structures_with_cache](fn.count_structures_with_cache.html) to keep the
+/// cache in memory owned by the caller instead. A negative `mingap` is treated as 0.
 pub fn count_structures(n: i64, mingap: i64) -> BigUint {
+    let mingap = mingap.max(0);
     for i in 1..n + 1 {
         _count_structures(i, mingap);
     }
     _count_structures(n, mingap)
-}
\ No newline at end of file
+}
+
+/// Returns the same count as [count_structures](fn.count_structures.html), memoizing the counts
+/// in a cache owned by the caller, keyed on `(n, mingap)`, rather than in a process-global cache.
+/// The cache may be reused between calls, and dropped to reclaim its memory. As with
+/// `count_structures`, a negative `mingap` is treated as 0.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::combinatorics::count_structures_with_cache;
+/// use num_bigint::BigUint;
+/// use std::collections::HashMap;
+/// let mut cache = HashMap::new();
+/// assert_eq!(count_structures_with_cache(4, 0, &mut cache), BigUint::from(9u32));
+/// ```
+pub fn count_structures_with_cache(n: i64, mingap: i64, cache: &mut HashMap<(i64, i64), BigUint>) -> BigUint {
+    let mingap = mingap.max(0);
+    for i in 0..n + 1 {
+        if cache.contains_key(&(i, mingap)) {
+            continue;
+        }
+        let mut v: BigUint = One::one();
+        if i > mingap {
+            v = cache[&(i - 1, mingap)].clone();
+            for k in 1..i - mingap {
+                v += &cache[&(k - 1, mingap)] * &cache[&(i - k - 1, mingap)];
+            }
+        }
+        cache.insert((i, mingap), v);
+    }
+    cache.get(&(n, mingap)).cloned().unwrap_or_else(One::one)
+}
+
+/// Returns every non-pseudoknotted secondary structure of a specified length, n, with at least
+/// 'mingap' unpaired nucleotides between every base-pair, as paired sites vectors.
+///
+/// The structures are generated using the same decomposition as
+/// [count_structures](fn.count_structures.html), so the number of structures returned is
+/// `count_structures(n, mingap)`. This grows exponentially with n: for mingap = 0 the counts are
+/// the Motzkin numbers, which grow roughly as 3^n, and for mingap = 1 they grow roughly as 2.618^n
+/// (the square of the golden ratio). This is therefore only practical for short lengths, e.g.
+/// validating algorithms. A negative `mingap` is treated as 0.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::combinatorics::enumerate_structures;
+/// let structures = enumerate_structures(4, 1);
+/// assert_eq!(structures, vec![vec![0, 0, 0, 0], vec![3, 0, 1, 0], vec![4, 0, 0, 1], vec![0, 4, 0, 2]]);
+/// ```
+pub fn enumerate_structures(n: i64, mingap: i64) -> Vec<Vec<i64>> {
+    let mingap = mingap.max(0);
+    let mut structures: Vec<Vec<Vec<i64>>> = vec![vec![vec![]]];
+    for len in 1..n.max(0) + 1 {
+        // the last nucleotide is unpaired
+        let mut current: Vec<Vec<i64>> = structures[(len - 1) as usize]
+            .iter()
+            .map(|s| {
+                let mut s = s.clone();
+                s.push(0);
+                s
+            })
+            .collect();
+        // the last nucleotide is paired to k
+        for k in 1..len - mingap {
+            for left in structures[(k - 1) as usize].iter() {
+                for inner in structures[(len - k - 1) as usize].iter() {
+                    let mut s = left.clone();
+                    s.push(len);
+                    s.extend(inner.iter().map(|j| if *j > 0 { j + k } else { 0 }));
+                    s.push(k);
+                    current.push(s);
+                }
+            }
+        }
+        structures.push(current);
+    }
+    structures.swap_remove(n.max(0) as usize)
+}
+
+/// Returns a uniformly random number in the range 0..bound, by rejection sampling.
+fn random_below(bound: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let bits = bound.bits();
+    let mut digits = vec![0u32; bits.div_ceil(32)];
+    loop {
+        for digit in digits.iter_mut() {
+            *digit = rng.gen();
+        }
+        if !bits.is_multiple_of(32) {
+            let last = digits.len() - 1;
+            digits[last] &= (1u32 << (bits % 32)) - 1;
+        }
+        let r = BigUint::from_slice(&digits);
+        if &r < bound {
+            return r;
+        }
+    }
+}
+
+/// Returns a secondary structure drawn uniformly at random from all the non-pseudoknotted
+/// secondary structures of a specified length, n, with at least 'mingap' unpaired nucleotides
+/// between every base-pair, as a paired sites vector.
+///
+/// The structure is unranked using the same decomposition as
+/// [count_structures](fn.count_structures.html): the last nucleotide of each subsequence is
+/// either unpaired, or paired to a position k, with probability proportional to the number of
+/// structures of the resulting subsequences. As with `count_structures`, a negative `mingap` is
+/// treated as 0.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::combinatorics::random_structure;
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let paired = random_structure(20, 3, &mut rng);
+/// assert_eq!(paired.len(), 20);
+/// ```
+pub fn random_structure(n: i64, mingap: i64, rng: &mut impl Rng) -> Vec<i64> {
+    let mingap = mingap.max(0);
+    let mut paired = vec![0; n.max(0) as usize];
+    count_structures(n, mingap);
+
+    // subsequences still to be sampled, as (number of preceding nucleotides, length)
+    let mut stack: Vec<(i64, i64)> = vec![(0, n)];
+    while let Some((start, len)) = stack.pop() {
+        if len <= mingap {
+            continue;
+        }
+        let mut r = random_below(&_count_structures(len, mingap), rng);
+        let unpaired = _count_structures(len - 1, mingap);
+        if r < unpaired {
+            stack.push((start, len - 1));
+            continue;
+        }
+        r -= unpaired;
+        for k in 1..len - mingap {
+            let w = _count_structures(k - 1, mingap) * _count_structures(len - k - 1, mingap);
+            if r < w {
+                paired[(start + k - 1) as usize] = start + len;
+                paired[(start + len - 1) as usize] = start + k;
+                stack.push((start, k - 1));
+                stack.push((start + k, len - k - 1));
+                break;
+            }
+            r -= w;
+        }
+    }
+    paired
+}
+
+/// Returns the count of all possible secondary structures of a specified length, n, including
+/// pseudoknotted structures, with at least 'mingap' unpaired nucleotides between every base-pair.
+/// That is, the number of matchings of n nucleotides in which every base-pair `(i, j)` has
+/// `j - i > mingap`. This is never less than [count_structures](fn.count_structures.html),
+/// which counts only the non-crossing matchings, and the difference between the two is the
+/// number of pseudoknotted structures. For mingap = 0 this gives the telephone numbers.
+///
+/// Nucleotides are processed 5' to 3', tracking how many unclosed base-pairs may be closed at
+/// the current position, and which of the preceding 'mingap' positions opened a base-pair. This
+/// takes O(n^2 2^mingap) time, and 'mingap' must be less than 64. A negative `mingap` is treated
+/// as 0.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::combinatorics::{count_all_pairings, count_structures};
+/// use num_bigint::BigUint;
+/// assert_eq!(count_all_pairings(4, 0), BigUint::from(10u32));
+/// // exactly one of the 10 structures, (1, 3) with (2, 4), is pseudoknotted
+/// assert_eq!(count_structures(4, 0), BigUint::from(9u32));
+/// ```
+pub fn count_all_pairings(n: i64, mingap: i64) -> BigUint {
+    let mingap = mingap.max(0);
+    assert!(mingap < 64, "mingap must be less than 64");
+    let mingap = mingap as u32;
+    let window: u64 = if mingap == 0 { 0 } else { (1u64 << mingap) - 1 };
+
+    // (number of open base-pairs that may be closed, recently opened positions) -> count
+    let mut states: HashMap<(usize, u64), BigUint> = HashMap::new();
+    states.insert((0, 0), One::one());
+    for _ in 0..n.max(0) {
+        let mut next: HashMap<(usize, u64), BigUint> = HashMap::new();
+        for ((open, recent), count) in states {
+            let mut advance = |open: usize, opened: u64, count: BigUint| {
+                let (open, recent) = if mingap == 0 {
+                    (open + opened as usize, 0)
+                } else {
+                    (open + ((recent >> (mingap - 1)) & 1) as usize, ((recent << 1) | opened) & window)
+                };
+                *next.entry((open, recent)).or_insert_with(Zero::zero) += count;
+            };
+            // close one of the eligible open base-pairs
+            if open > 0 {
+                advance(open - 1, 0, &count * BigUint::from(open));
+            }
+            // open a new base-pair
+            advance(open, 1, count.clone());
+            // leave unpaired
+            advance(open, 0, count);
+        }
+        states = next;
+    }
+    states.remove(&(0, 0)).unwrap_or_else(Zero::zero)
+}
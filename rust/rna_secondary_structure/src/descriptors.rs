@@ -0,0 +1,116 @@
+//! A module for computing summary descriptors of many secondary structures in bulk.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::secondary_structure::{pseudoknot_order, SecondaryStructureRecord};
+
+/// A table row of summary descriptors of a single secondary structure, as computed by
+/// [compute_descriptors](fn.compute_descriptors.html).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StructureDescriptors {
+    /// The name of the record.
+    pub name: String,
+
+    /// The length of the secondary structure.
+    pub length: usize,
+
+    /// The number of base-pairs.
+    pub num_pairs: usize,
+
+    /// The number of helices, i.e. maximal runs of directly stacked base-pairs.
+    pub num_helices: usize,
+
+    /// The maximum height of the mountain vector.
+    pub max_nesting_depth: i64,
+
+    /// Whether the secondary structure contains crossing base-pairs.
+    pub pseudoknotted: bool,
+
+    /// The number of non-crossing pages needed to draw every base-pair (at least 1), see
+    /// [pseudoknot_order](../secondary_structure/fn.pseudoknot_order.html).
+    pub page_number: usize,
+
+    /// The fraction of G and C nucleotides amongst the A, C, G, T, and U nucleotides of the
+    /// sequence (0.0 if there are none), see
+    /// [gc_content](../secondary_structure/struct.SecondaryStructureRecord.html#method.gc_content).
+    pub gc_content: f64,
+
+    /// The sum of the heights of the mountain vector.
+    pub mountain_area: f64,
+}
+
+impl StructureDescriptors {
+    /// Computes the descriptors of a single SecondaryStructureRecord in one pass over the
+    /// structure and one pass over the sequence, plus the page assignment of its base-pairs.
+    pub fn from_record(ss: &SecondaryStructureRecord) -> StructureDescriptors {
+        let paired = &ss.paired;
+        let len = paired.len();
+
+        let mut num_pairs = 0;
+        let mut num_helices = 0;
+        let mut height = 0;
+        let mut max_nesting_depth = 0;
+        let mut mountain_area = 0.0;
+        for (i, j) in paired.iter().enumerate() {
+            let j = *j;
+            if j > (i as i64) + 1 && j as usize <= len {
+                num_pairs += 1;
+                if i == 0 || paired[i - 1] != j + 1 {
+                    num_helices += 1;
+                }
+                height += 1;
+            } else if j > 0 && j < (i as i64) + 1 {
+                height -= 1;
+            }
+            max_nesting_depth = max_nesting_depth.max(height);
+            mountain_area += height as f64;
+        }
+
+        let page_number = pseudoknot_order(ss);
+
+        StructureDescriptors {
+            name: ss.name.clone(),
+            length: len,
+            num_pairs,
+            num_helices,
+            max_nesting_depth,
+            pseudoknotted: page_number > 1,
+            page_number,
+            gc_content: ss.gc_content(),
+            mountain_area,
+        }
+    }
+}
+
+/// Computes the [StructureDescriptors](struct.StructureDescriptors.html) of each record, in order.
+/// Each record is processed independently in a single pass; see
+/// [compute_descriptors_par](fn.compute_descriptors_par.html) for a parallel version.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::descriptors::compute_descriptors;
+/// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+/// let mut ss: SecondaryStructureRecord = "((.((...)).))..[[..]]".parse().unwrap();
+/// ss.set_sequence("GGAGCAAAGCAUCAAGCAAGC".to_string());
+///
+/// let descriptors = compute_descriptors(&[ss]);
+/// assert_eq!(descriptors[0].num_pairs, 6);
+/// assert_eq!(descriptors[0].num_helices, 3);
+/// assert_eq!(descriptors[0].max_nesting_depth, 4);
+/// assert_eq!(descriptors[0].pseudoknotted, false);
+/// assert_eq!(descriptors[0].page_number, 1);
+/// ```
+pub fn compute_descriptors(records: &[SecondaryStructureRecord]) -> Vec<StructureDescriptors> {
+    records.iter().map(StructureDescriptors::from_record).collect()
+}
+
+/// Computes the same descriptors as [compute_descriptors](fn.compute_descriptors.html), but
+/// processes the records in parallel using rayon. Requires the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub fn compute_descriptors_par(records: &[SecondaryStructureRecord]) -> Vec<StructureDescriptors> {
+    records.par_iter().map(StructureDescriptors::from_record).collect()
+}
@@ -3,15 +3,54 @@
 //! Implements the 'Mountain Metric' as defined in:
 //! `Moulton, Vincent, et al. "Metrics on RNA secondary structures." Journal of Computational Biology 7.1-2 (2000): 277-292.`
 
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use thiserror::Error;
 
-use crate::secondary_structure::PairedSites;
+use crate::secondary_structure::{base_pairs, decompose_loops, enclosed_elements, is_planar, Loop, PairedSites, SecondaryStructureRecord, StructureParseError};
 
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
 pub enum SecondaryStructureMetricError {
     #[error("Secondary structures must be the same length.")]
     UnequalLength,
+
+    #[error("Secondary structures {i} and {j} must be the same length.")]
+    UnequalLengthBetween {
+        i: usize,
+        j: usize,
+    },
+
+    #[error("Invalid mountain vector at position {pos}: {msg}")]
+    InvalidMountainVector {
+        pos: usize,
+        msg: String,
+    },
+
+    #[error(transparent)]
+    Structure(#[from] StructureParseError),
+}
+
+/// A distance metric between two secondary structures.
+///
+/// This is implemented for every function or closure with the same signature as
+/// [get_weighted_mountain_distance](fn.get_weighted_mountain_distance.html), so that metrics
+/// taking additional parameters can be supplied as closures, e.g.
+/// `|a: &dyn PairedSites, b: &dyn PairedSites| get_mountain_distance(a, b, Some(2.0))`.
+pub trait StructureMetric {
+    /// Returns the distance between two secondary structures.
+    fn distance(&self, paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError>;
+}
+
+impl<F> StructureMetric for F
+    where
+        F: Fn(&dyn PairedSites, &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError>
+{
+    fn distance(&self, paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError> {
+        self(paired1, paired2)
+    }
 }
 
 /// Returns a mountain vector from a list of paired sites
@@ -44,8 +83,14 @@ pub fn get_mountain_vector(paired: &dyn PairedSites) -> Vec<f64> {
     mountain
 }
 
-/// Returns a paired sites vector from a mountain vector. WARNING: this inversion is only valid
-/// for mountain vectors derived from non-pseudoknotted secondary structures.
+/// Returns a paired sites vector from a mountain vector, reversing
+/// [get_mountain_vector](fn.get_mountain_vector.html). Each position must step up by one (opening a
+/// base-pair), step down by one (closing the most recently opened base-pair) or stay level
+/// (unpaired) relative to the previous height. WARNING: this inversion is only valid for mountain
+/// vectors derived from non-pseudoknotted secondary structures.
+///
+/// Returns `InvalidMountainVector` if a height is not an integer, a step is larger than one, the
+/// height drops below zero, or the mountain vector ends at a nonzero height.
 ///
 /// # Examples
 /// ```rust
@@ -54,33 +99,78 @@ pub fn get_mountain_vector(paired: &dyn PairedSites) -> Vec<f64> {
 /// let paired_exp = from_dotbracketstring("<<<..<<<.<..>>.>..>..>...<<...>..>>.>").unwrap();
 ///
 /// let mountain = get_mountain_vector(&paired_exp);
-/// assert_eq!(invert_mountain_vector(&mountain), paired_exp);
+/// assert_eq!(invert_mountain_vector(&mountain).unwrap(), paired_exp);
+/// assert!(invert_mountain_vector(&[1.0, 1.5, 0.0]).is_err());
+/// assert!(invert_mountain_vector(&[1.0, 1.0, 1.0]).is_err());
+/// ```
+pub fn invert_mountain_vector(mountain: &[f64]) -> Result<Vec<i64>, SecondaryStructureMetricError> {
+    let invalid = |pos: usize, msg: &str| SecondaryStructureMetricError::InvalidMountainVector { pos, msg: msg.to_string() };
+
+    let mut stack: Vec<usize> = Vec::new();
+    let mut paired: Vec<i64> = vec![0; mountain.len()];
+    let mut last_height = 0.0;
+    for (i, height) in mountain.iter().enumerate() {
+        let height = *height;
+        if height.fract() != 0.0 {
+            return Err(invalid(i, "height is not an integer"));
+        }
+        let step = height - last_height;
+        if step == 1.0 {
+            stack.push(i);
+        } else if step == -1.0 {
+            let j = stack.pop().ok_or_else(|| invalid(i, "height drops below zero"))?;
+            paired[i] = (j + 1) as i64;
+            paired[j] = (i + 1) as i64;
+        } else if step != 0.0 {
+            return Err(invalid(i, "height changes by more than one"));
+        }
+        last_height = height;
+    }
+    if !stack.is_empty() {
+        return Err(invalid(mountain.len() - 1, "mountain vector ends at a nonzero height"));
+    }
+    Ok(paired)
+}
+
+/// Returns a paired sites vector from a weighted mountain vector, reversing
+/// [get_weighted_mountain_vector](fn.get_weighted_mountain_vector.html). Only the direction of each
+/// step is used: an increase in height opens a base-pair, a decrease closes the most recently opened
+/// base-pair. WARNING: this inversion is only valid for mountain vectors derived from
+/// non-pseudoknotted secondary structures.
+///
+/// Returns `InvalidMountainVector` if a base-pair is closed before any is opened, or a base-pair
+/// is left open at the end of the mountain vector.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::*;
+/// let paired_exp = from_dotbracketstring("<<<..<<<.<..>>.>..>..>...<<...>..>>.>").unwrap();
 ///
 /// let weighted_mountain = get_weighted_mountain_vector(&paired_exp);
-/// assert_eq!(invert_mountain_vector(&weighted_mountain), paired_exp);
+/// assert_eq!(invert_weighted_mountain_vector(&weighted_mountain).unwrap(), paired_exp);
 /// ```
-pub fn invert_mountain_vector<'a, T>(mountain: T) -> Vec<i64>
-    where
-    T: IntoIterator<Item=&'a  f64>,
-    T::IntoIter : ExactSizeIterator<Item=&'a  f64>
-{
-    let it = mountain.into_iter();
+pub fn invert_weighted_mountain_vector(mountain: &[f64]) -> Result<Vec<i64>, SecondaryStructureMetricError> {
+    let invalid = |pos: usize, msg: &str| SecondaryStructureMetricError::InvalidMountainVector { pos, msg: msg.to_string() };
 
-    let mut stack: Vec<i64> = Vec::new();
-    let mut paired: Vec<i64> = vec![0; it.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    let mut paired: Vec<i64> = vec![0; mountain.len()];
     let mut last_height = 0.0;
-    for (i, height) in it.enumerate() {
+    for (i, height) in mountain.iter().enumerate() {
         let height = *height;
         if height > last_height {
-            stack.push(i as i64)
+            stack.push(i);
         } else if height < last_height {
-            let j = stack.pop().unwrap();
-            paired[i] = j + 1;
-            paired[j as usize] = (i + 1) as i64;
+            let j = stack.pop().ok_or_else(|| invalid(i, "base-pair closed before it was opened"))?;
+            paired[i] = (j + 1) as i64;
+            paired[j] = (i + 1) as i64;
         }
         last_height = height;
     }
-    paired
+    if !stack.is_empty() {
+        return Err(invalid(mountain.len() - 1, "mountain vector ends with unclosed base-pairs"));
+    }
+    Ok(paired)
 }
 
 /// Returns the mountain distance between two secondary structures.
@@ -97,11 +187,47 @@ pub fn get_mountain_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSite
 
     let m1 = get_mountain_vector(paired1);
     let m2 = get_mountain_vector(paired2);
+    Ok(mountain_vector_distance(&m1, &m2, p))
+}
+
+/// Returns `sum |a - b|^p` over the positions of two mountain vectors of equal length.
+fn mountain_vector_distance(m1: &[f64], m2: &[f64], p: f64) -> f64 {
     let mut d = 0.0;
     for (a, b) in m1.iter().zip(m2) {
         d += (a - b).abs().powf(p);
     }
-    Ok(d)
+    d
+}
+
+/// Returns the root-mean-square deviation between the mountain vectors of two secondary structures,
+/// `sqrt(mean((m1[i] - m2[i])^2))`, or 0.0 for two empty structures.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::get_mountain_rmsd;
+/// // mountain vectors [1, 2, 1, 0] and [1, 1, 1, 0]
+/// let paired1 = from_dotbracketstring("(())").unwrap();
+/// let paired2 = from_dotbracketstring("(..)").unwrap();
+/// assert_eq!(get_mountain_rmsd(&paired1, &paired2).unwrap(), 0.5);
+/// ```
+pub fn get_mountain_rmsd(paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError> {
+    let paired1 = paired1.paired();
+    let paired2 = paired2.paired();
+
+    if paired1.len() != paired2.len() {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+    if paired1.is_empty() {
+        return Ok(0.0);
+    }
+
+    let m1 = get_mountain_vector(paired1);
+    let m2 = get_mountain_vector(paired2);
+    let sum_sq: f64 = m1.iter().zip(m2).map(|(a, b)| (a - b) * (a - b)).sum();
+    Ok((sum_sq / paired1.len() as f64).sqrt())
 }
 
 /// Returns the unique and valid secondary structure configuration of the specified length that has
@@ -179,6 +305,107 @@ pub fn get_normalised_mountain_distance(paired1: &dyn PairedSites, paired2: &dyn
     Ok(get_mountain_distance(paired1, paired2, p)? / get_mountain_diameter(paired1.len() as i64, p))
 }
 
+/// Returns the Lp mountain distance between two secondary structures, `(sum |a - b|^p)^(1/p)`
+/// over the positions of their mountain vectors. Unlike
+/// [get_mountain_distance](fn.get_mountain_distance.html), which omits the p-th root, this is a
+/// proper Lp distance for every `p >= 1`. The two coincide for `p = 1`, the default.
+pub fn get_lp_mountain_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSites, p: Option<f64>) -> Result<f64, SecondaryStructureMetricError> {
+    let p = p.unwrap_or(1.0);
+    Ok(get_mountain_distance(paired1, paired2, Some(p))?.powf(1.0 / p))
+}
+
+/// Returns the normalised Lp mountain distance, d, between two secondary structure configurations,
+/// such that 0.0 <= d <= 1.0. This is the [Lp mountain distance](fn.get_lp_mountain_distance.html)
+/// divided by the Lp mountain distance between [structure_star](fn.get_structure_star.html) and
+/// [structure_zero](fn.get_structure_zero.html).
+///
+/// [get_normalised_mountain_distance](fn.get_normalised_mountain_distance.html) is retained for
+/// backward compatibility, but for `p != 1` it divides distances without the p-th root taken.
+///
+/// # Examples
+/// ```rust
+/// use crate::rna_secondary_structure::distance_metrics;
+///
+/// let p1 = distance_metrics::get_structure_star(100);
+/// let p2 = distance_metrics::get_structure_zero(100);
+/// let max_distance = distance_metrics::get_normalised_lp_mountain_distance(&p1, &p2, Some(2.0)).unwrap();
+/// assert_eq!(max_distance, 1.0);
+/// ```
+pub fn get_normalised_lp_mountain_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSites, p: Option<f64>) -> Result<f64, SecondaryStructureMetricError> {
+    let len = paired1.paired().len() as i64;
+    let diameter = get_lp_mountain_distance(&get_structure_star(len), &get_structure_zero(len), p)?;
+    Ok(get_lp_mountain_distance(paired1, paired2, p)? / diameter)
+}
+
+/// Returns the symmetric all-vs-all matrix of [mountain distances](fn.get_mountain_distance.html)
+/// between the specified structures, with a zero diagonal. The mountain vector of each structure
+/// is computed once, and each distance is computed once and mirrored across the diagonal.
+///
+/// Returns `UnequalLengthBetween` naming the first structure whose length differs from that of the
+/// first structure.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, PairedSites};
+/// use rna_secondary_structure::distance_metrics::pairwise_mountain_distances;
+/// let p1 = from_dotbracketstring("(...)").unwrap();
+/// let p2 = from_dotbracketstring(".....").unwrap();
+/// let matrix = pairwise_mountain_distances(&[&p1, &p2], None).unwrap();
+/// assert_eq!(matrix, vec![vec![0.0, 4.0], vec![4.0, 0.0]]);
+/// ```
+pub fn pairwise_mountain_distances(structures: &[&dyn PairedSites], p: Option<f64>) -> Result<Vec<Vec<f64>>, SecondaryStructureMetricError> {
+    check_pairwise_lengths(structures)?;
+    let p = p.unwrap_or(1.0);
+
+    let mountains = structures.iter().map(|paired| get_mountain_vector(*paired)).collect::<Vec<Vec<f64>>>();
+    let mut matrix = vec![vec![0.0; structures.len()]; structures.len()];
+    for i in 0..structures.len() {
+        for j in i + 1..structures.len() {
+            let d = mountain_vector_distance(&mountains[i], &mountains[j], p);
+            matrix[i][j] = d;
+            matrix[j][i] = d;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Computes the same matrix as [pairwise_mountain_distances](fn.pairwise_mountain_distances.html),
+/// but computes the rows of the upper triangle in parallel using rayon. Requires the `rayon`
+/// feature.
+#[cfg(feature = "rayon")]
+pub fn pairwise_mountain_distances_par(structures: &[&dyn PairedSites], p: Option<f64>) -> Result<Vec<Vec<f64>>, SecondaryStructureMetricError> {
+    check_pairwise_lengths(structures)?;
+    let p = p.unwrap_or(1.0);
+
+    let paired = structures.iter().map(|ss| ss.paired()).collect::<Vec<&Vec<i64>>>();
+    let mountains = paired.par_iter().map(|paired| get_mountain_vector(*paired)).collect::<Vec<Vec<f64>>>();
+    let rows = (0..mountains.len()).into_par_iter()
+        .map(|i| (i + 1..mountains.len()).map(|j| mountain_vector_distance(&mountains[i], &mountains[j], p)).collect())
+        .collect::<Vec<Vec<f64>>>();
+
+    let mut matrix = vec![vec![0.0; mountains.len()]; mountains.len()];
+    for (i, row) in rows.into_iter().enumerate() {
+        for (offset, d) in row.into_iter().enumerate() {
+            let j = i + 1 + offset;
+            matrix[i][j] = d;
+            matrix[j][i] = d;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Returns `UnequalLengthBetween` naming the first structure whose length differs from that of
+/// the first structure.
+fn check_pairwise_lengths(structures: &[&dyn PairedSites]) -> Result<(), SecondaryStructureMetricError> {
+    if let Some(first) = structures.first() {
+        let len = first.paired().len();
+        if let Some(j) = structures.iter().position(|paired| paired.paired().len() != len) {
+            return Err(SecondaryStructureMetricError::UnequalLengthBetween { i: 0, j });
+        }
+    }
+    Ok(())
+}
+
 /// Returns a weighted mountain vector, where the step up or down in mountain height at base-paired
 /// positions is inversely proportional to the number of nucleotides separating the base-pairs.
 pub fn get_weighted_mountain_vector(paired: &dyn PairedSites) -> Vec<f64> {
@@ -225,4 +452,657 @@ pub fn get_normalised_weighted_mountain_distance(paired1: &dyn PairedSites, pair
     let paired2 = paired2.paired();
 
     Ok(get_weighted_mountain_distance(paired1, paired2)? / get_weighted_mountain_diameter(paired1.len() as i64))
-}
\ No newline at end of file
+}
+
+/// Returns the set of base-pairs of a structure as 0-based `(i, j)` with `i < j`.
+fn base_pair_set(paired: &dyn PairedSites) -> HashSet<(usize, usize)> {
+    base_pairs(paired).into_iter().collect()
+}
+
+/// Returns the base-pair distance between two secondary structures, i.e. the number of base-pairs
+/// present in exactly one of the two structures.
+///
+/// Unlike the mountain metric, the structures are not required to be the same length, as the
+/// distance is defined purely on the two sets of base-pairs. This never returns `UnequalLength`.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::get_base_pair_distance;
+/// let paired1 = from_dotbracketstring("((..))").unwrap();
+/// let paired2 = from_dotbracketstring("(....)").unwrap();
+/// assert_eq!(get_base_pair_distance(&paired1, &paired2).unwrap(), 1);
+/// ```
+pub fn get_base_pair_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<usize, SecondaryStructureMetricError> {
+    let pairs1 = base_pair_set(paired1.paired());
+    let pairs2 = base_pair_set(paired2.paired());
+    Ok(pairs1.symmetric_difference(&pairs2).count())
+}
+
+/// Returns the Hamming distance between two secondary structures, i.e. the number of positions
+/// whose paired state (opening a base-pair, closing a base-pair, or unpaired) differs.
+///
+/// This is coarser than the [base-pair distance](fn.get_base_pair_distance.html), as the identity
+/// of the partner is ignored: two positions that both open a base-pair agree even if they pair
+/// with different partners.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::get_hamming_distance;
+/// let paired1 = from_dotbracketstring("((...))").unwrap();
+/// let paired2 = from_dotbracketstring("(.(.).)").unwrap();
+/// assert_eq!(get_hamming_distance(&paired1, &paired2).unwrap(), 4);
+/// ```
+pub fn get_hamming_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<usize, SecondaryStructureMetricError> {
+    let paired1 = paired1.paired();
+    let paired2 = paired2.paired();
+
+    if paired1.len() != paired2.len() {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let state = |i: usize, j: i64| if j == 0 { 0 } else { (j - (i as i64) - 1).signum() };
+    Ok(paired1.iter().zip(paired2).enumerate()
+        .filter(|(i, (a, b))| state(*i, **a) != state(*i, **b))
+        .count())
+}
+
+/// Returns the Morgan-Higgs distance between two secondary structures. Each base-pair present in
+/// only one of the two structures contributes a cost equal to the number of base-pairs of the other
+/// structure that it crosses, i.e. `(k, l)` with `i < k < j < l` or `k < i < l < j`. Base-pairs
+/// common to both structures contribute nothing.
+///
+/// Each base-pair in the symmetric difference is compared against every base-pair of the other
+/// structure, taking O(n^2) time.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::get_morgan_higgs_distance;
+/// let paired1 = from_dotbracketstring("(....)....").unwrap();
+/// let paired2 = from_dotbracketstring("...(....).").unwrap();
+/// assert_eq!(get_morgan_higgs_distance(&paired1, &paired2).unwrap(), 2.0);
+/// ```
+pub fn get_morgan_higgs_distance(paired1: &dyn PairedSites, paired2: &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError> {
+    let paired1 = paired1.paired();
+    let paired2 = paired2.paired();
+
+    if paired1.len() != paired2.len() {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let pairs1 = base_pair_set(paired1);
+    let pairs2 = base_pair_set(paired2);
+    let crosses = |(i, j): (usize, usize), (k, l): (usize, usize)| (i < k && k < j && j < l) || (k < i && i < l && l < j);
+    let crossings = |pairs: &HashSet<(usize, usize)>, other: &HashSet<(usize, usize)>| {
+        pairs.difference(other)
+            .map(|pair| other.iter().filter(|o| crosses(*pair, **o)).count())
+            .sum::<usize>()
+    };
+    Ok((crossings(&pairs1, &pairs2) + crossings(&pairs2, &pairs1)) as f64)
+}
+
+/// Accuracy of a predicted secondary structure against a reference structure, treating each
+/// reference base-pair as a positive. See [score_prediction](fn.score_prediction.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PredictionScores {
+    /// TP/(TP+FN), the fraction of reference base-pairs that were predicted.
+    pub sensitivity: f64,
+    /// TP/(TP+FP), the fraction of predicted base-pairs that are in the reference.
+    pub ppv: f64,
+    /// The Matthews correlation coefficient over all n(n-1)/2 possible base-pairs.
+    pub mcc: f64,
+    /// The harmonic mean of the sensitivity and PPV.
+    pub f1: f64,
+}
+
+/// Returns `numerator / denominator`, or 0.0 if the denominator is zero.
+fn ratio_or_zero(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 { 0.0 } else { numerator / denominator }
+}
+
+impl PredictionScores {
+    /// Computes the scores from the true positive, false positive and false negative base-pair
+    /// counts for structures of length `len`.
+    fn from_counts(tp: usize, fp: usize, fn_: usize, len: usize) -> PredictionScores {
+        let (tp, fp, fn_) = (tp as f64, fp as f64, fn_ as f64);
+        let possible = (len * len.saturating_sub(1) / 2) as f64;
+        let tn = (possible - tp - fp - fn_).max(0.0);
+
+        let sensitivity = ratio_or_zero(tp, tp + fn_);
+        let ppv = ratio_or_zero(tp, tp + fp);
+        let mcc = ratio_or_zero(tp * tn - fp * fn_, ((tp + fp) * (tp + fn_) * (tn + fp) * (tn + fn_)).sqrt());
+        let f1 = ratio_or_zero(2.0 * sensitivity * ppv, sensitivity + ppv);
+        PredictionScores { sensitivity, ppv, mcc, f1 }
+    }
+}
+
+/// Scores a predicted secondary structure against a reference structure. True positives are
+/// base-pairs present in both structures, false negatives are base-pairs only in the reference, and
+/// false positives are base-pairs only in the prediction. Any ratio with a zero denominator, e.g.
+/// the sensitivity when the reference has no base-pairs, is reported as 0.0.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::score_prediction;
+/// let reference = from_dotbracketstring("((((...))))").unwrap();
+/// let predicted = from_dotbracketstring("((((...)))).").unwrap();
+/// assert!(score_prediction(&predicted, &reference).is_err());
+///
+/// let predicted = from_dotbracketstring(".(((...))).").unwrap();
+/// let scores = score_prediction(&predicted, &reference).unwrap();
+/// assert_eq!(scores.sensitivity, 0.75);
+/// assert_eq!(scores.ppv, 1.0);
+/// ```
+pub fn score_prediction(predicted: &dyn PairedSites, reference: &dyn PairedSites) -> Result<PredictionScores, SecondaryStructureMetricError> {
+    let len = reference.paired().len();
+    if predicted.paired().len() != len {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let predicted = base_pair_set(predicted.paired());
+    let reference = base_pair_set(reference.paired());
+    let tp = predicted.intersection(&reference).count();
+    Ok(PredictionScores::from_counts(tp, predicted.len() - tp, reference.len() - tp, len))
+}
+
+/// Scores a predicted secondary structure against a reference structure as in
+/// [score_prediction](fn.score_prediction.html), but allowing base-pairs to slip: a predicted
+/// base-pair `(i, j)` is a true positive if some reference base-pair `(i', j')` has
+/// `|i - i'| <= slip` and `|j - j'| <= slip`. Predicted base-pairs are matched in 5' order to the
+/// nearest (by `|i - i'| + |j - j'|) unmatched reference base-pair, so that each reference base-pair
+/// is matched at most once. `slip = 0` is identical to exact scoring.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// use rna_secondary_structure::distance_metrics::score_prediction_with_slippage;
+/// let reference = from_dotbracketstring("((((...))))..").unwrap();
+/// let predicted = from_dotbracketstring(".((((...)))).").unwrap();
+/// assert_eq!(score_prediction_with_slippage(&predicted, &reference, 0).unwrap().sensitivity, 0.0);
+/// assert_eq!(score_prediction_with_slippage(&predicted, &reference, 1).unwrap().sensitivity, 1.0);
+/// ```
+pub fn score_prediction_with_slippage(predicted: &dyn PairedSites, reference: &dyn PairedSites, slip: i64) -> Result<PredictionScores, SecondaryStructureMetricError> {
+    let len = reference.paired().len();
+    if predicted.paired().len() != len {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let mut predicted = base_pair_set(predicted.paired()).into_iter().collect::<Vec<(usize, usize)>>();
+    predicted.sort_unstable();
+    let mut reference = base_pair_set(reference.paired()).into_iter().collect::<Vec<(usize, usize)>>();
+    reference.sort_unstable();
+
+    let mut matched = vec![false; reference.len()];
+    let mut tp = 0;
+    for (i, j) in predicted.iter() {
+        let nearest = reference.iter().enumerate()
+            .filter(|(index, _)| !matched[*index])
+            .map(|(index, (k, l))| (index, (*i as i64 - *k as i64).abs(), (*j as i64 - *l as i64).abs()))
+            .filter(|(_, di, dj)| *di <= slip && *dj <= slip)
+            .min_by_key(|(_, di, dj)| di + dj);
+        if let Some((index, _, _)) = nearest {
+            matched[index] = true;
+            tp += 1;
+        }
+    }
+    Ok(PredictionScores::from_counts(tp, predicted.len() - tp, reference.len() - tp, len))
+}
+
+/// Returns the distance under the given metric from a reference secondary structure to each of
+/// the specified structures, in order. All structures must be the same length as the reference.
+pub fn distances_to_reference(reference: &dyn PairedSites, structures: &[&dyn PairedSites], metric: &dyn StructureMetric) -> Result<Vec<f64>, SecondaryStructureMetricError> {
+    let len = reference.paired().len();
+    if structures.iter().any(|paired| paired.paired().len() != len) {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+    structures.iter().map(|paired| metric.distance(reference, *paired)).collect()
+}
+
+/// Returns the distance under the given metric from a reference secondary structure to each member
+/// of an ensemble (e.g. a set of sampled structures), in ensemble order. The resulting
+/// distribution indicates whether the reference is typical of the ensemble or an outlier.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::{distance_distribution_to_ensemble, get_mountain_distance};
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, PairedSites, SecondaryStructureRecord};
+/// let reference = from_dotbracketstring("((...))").unwrap();
+/// let ensemble: Vec<SecondaryStructureRecord> = vec!["((...))".parse().unwrap(), "(.....)".parse().unwrap()];
+/// let metric = |a: &dyn PairedSites, b: &dyn PairedSites| get_mountain_distance(a, b, None);
+/// let distances = distance_distribution_to_ensemble(&reference, &ensemble, &metric).unwrap();
+/// assert_eq!(distances, vec![0.0, 4.0]);
+/// ```
+pub fn distance_distribution_to_ensemble(reference: &dyn PairedSites, ensemble: &[SecondaryStructureRecord], metric: &dyn StructureMetric) -> Result<Vec<f64>, SecondaryStructureMetricError> {
+    let ensemble = ensemble.iter().map(|ss| ss as &dyn PairedSites).collect::<Vec<&dyn PairedSites>>();
+    distances_to_reference(reference, &ensemble, metric)
+}
+
+/// A single operation of an edit script transforming one dot bracket string into another, see
+/// [dot_bracket_edit_script](fn.dot_bracket_edit_script.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOp {
+    /// Insert the character `c` found at 0-based position `pos` of the second string.
+    Insert {
+        /// The position of the inserted character in the second string.
+        pos: usize,
+        /// The inserted character.
+        c: char,
+    },
+    /// Delete the character `c` found at 0-based position `pos` of the first string.
+    Delete {
+        /// The position of the deleted character in the first string.
+        pos: usize,
+        /// The deleted character.
+        c: char,
+    },
+    /// Substitute the character `from` at 0-based position `pos` of the first string with `to`.
+    Substitute {
+        /// The position of the substituted character in the first string.
+        pos: usize,
+        /// The character in the first string.
+        from: char,
+        /// The character in the second string.
+        to: char,
+    },
+}
+
+/// Returns a minimal edit script of single character insertions, deletions, and substitutions that
+/// transforms the dot bracket string `a` into `b` (i.e. an alignment with unit-cost Levenshtein
+/// distance), in left-to-right order. Unlike the position-wise metrics, `a` and `b` may differ
+/// in length. Where several minimal scripts exist, substitutions are preferred over deletions,
+/// and deletions over insertions.
+///
+/// The dynamic programming matrix requires O(n*m) time and memory for strings of length n and m,
+/// which should be considered for long inputs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::{dot_bracket_edit_script, EditOp};
+/// let script = dot_bracket_edit_script("((..))", "((...))");
+/// assert_eq!(script, vec![EditOp::Insert { pos: 2, c: '.' }]);
+/// assert!(dot_bracket_edit_script("(...)", "(...)").is_empty());
+/// ```
+pub fn dot_bracket_edit_script(a: &str, b: &str) -> Vec<EditOp> {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    let (n, m) = (a.len(), b.len());
+
+    let mut cost = vec![vec![0; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, entry) in cost[0].iter_mut().enumerate() {
+        *entry = j;
+    }
+    for i in 1..n + 1 {
+        for j in 1..m + 1 {
+            let substitution = cost[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cost[i][j] = substitution.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut script = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && cost[i][j] == cost[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 } {
+            if a[i - 1] != b[j - 1] {
+                script.push(EditOp::Substitute { pos: i - 1, from: a[i - 1], to: b[j - 1] });
+            }
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            script.push(EditOp::Delete { pos: i - 1, c: a[i - 1] });
+            i -= 1;
+        } else {
+            script.push(EditOp::Insert { pos: j - 1, c: b[j - 1] });
+            j -= 1;
+        }
+    }
+    script.reverse();
+    script
+}
+
+/// Returns the coarse grained representation (Shapiro, 1988) of a non-pseudoknotted secondary
+/// structure, as written by ViennaRNA's RNAdistance. Each loop is written as `(`, the loops it
+/// encloses, a letter for its type, and `)`: `H` for a hairpin loop, `B` for a bulge, `I` for an
+/// internal loop, `M` for a multiloop, and `R` for the external loop (the root). Stacked
+/// base-pairs are not represented.
+///
+/// Returns [StructureParseError::Pseudoknotted](../secondary_structure/enum.StructureParseError.html)
+/// if the structure contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::coarse_grained_string;
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// let paired = from_dotbracketstring(".((..(((...)))..((..)))).").unwrap();
+/// assert_eq!(coarse_grained_string(&paired).unwrap(), "(((H)(H)M)R)");
+/// ```
+pub fn coarse_grained_string(paired: &dyn PairedSites) -> Result<String, StructureParseError> {
+    if !is_planar(paired, &[]) {
+        return Err(StructureParseError::Pseudoknotted);
+    }
+    let paired = paired.paired();
+
+    let mut coarse = "(".to_string();
+    for (i, j) in enclosed_elements(paired, 0, paired.len()).0 {
+        coarse_grained_loop(paired, i, j, &mut coarse);
+    }
+    coarse.push_str("R)");
+    Ok(coarse)
+}
+
+/// Appends the coarse grained representation of the loop closed by the base-pair `(i, j)`,
+/// skipping any stacked base-pairs.
+fn coarse_grained_loop(paired: &[i64], i: usize, j: usize, coarse: &mut String) {
+    let (branches, unpaired) = enclosed_elements(paired, i + 1, j);
+    if branches.len() == 1 && unpaired.is_empty() {
+        coarse_grained_loop(paired, branches[0].0, branches[0].1, coarse);
+        return;
+    }
+
+    coarse.push('(');
+    for (k, l) in branches.iter() {
+        coarse_grained_loop(paired, *k, *l, coarse);
+    }
+    coarse.push(match branches.as_slice() {
+        [] => 'H',
+        [(k, l)] => if *k == i + 1 || *l == j - 1 { 'B' } else { 'I' },
+        _ => 'M'
+    });
+    coarse.push(')');
+}
+
+/// Returns the unit-cost Levenshtein distance between the
+/// [coarse grained representations](fn.coarse_grained_string.html) of two non-pseudoknotted
+/// secondary structures, which may differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::coarse_grained_edit_distance;
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// let hairpin = from_dotbracketstring("((((...))))").unwrap();
+/// let bulged = from_dotbracketstring("((.((...))))").unwrap();
+/// assert_eq!(coarse_grained_edit_distance(&hairpin, &bulged).unwrap(), 3);
+/// ```
+pub fn coarse_grained_edit_distance(a: &dyn PairedSites, b: &dyn PairedSites) -> Result<usize, StructureParseError> {
+    Ok(dot_bracket_edit_script(&coarse_grained_string(a)?, &coarse_grained_string(b)?).len())
+}
+
+/// An ordered rooted tree in postorder, as used by [tree_edit_distance](fn.tree_edit_distance.html):
+/// the label of each node (true for a base-pair, false for an unpaired nucleotide or the root) and
+/// the postorder index of its leftmost leaf descendant.
+struct PostorderTree {
+    labels: Vec<bool>,
+    leftmost: Vec<usize>,
+}
+
+impl PostorderTree {
+    /// Builds the tree of a non-pseudoknotted secondary structure: a root whose children are the
+    /// elements of the external loop, in which each base-pair is a node whose children are the
+    /// base-pairs and unpaired nucleotides it directly encloses.
+    fn from_paired(paired: &[i64]) -> PostorderTree {
+        let mut tree = PostorderTree { labels: Vec::new(), leftmost: Vec::new() };
+        let first = tree.add_children(paired, 0, paired.len());
+        tree.labels.push(false);
+        tree.leftmost.push(first.unwrap_or(tree.labels.len() - 1));
+        tree
+    }
+
+    /// Adds the elements directly within `start..end` in postorder, returning the leftmost leaf of
+    /// the first element, if any.
+    fn add_children(&mut self, paired: &[i64], start: usize, end: usize) -> Option<usize> {
+        let mut first = None;
+        let mut k = start;
+        while k < end {
+            let j = paired[k];
+            let leftmost = if j > (k as i64) + 1 && (j as usize) <= end {
+                let leftmost = self.add_children(paired, k + 1, (j - 1) as usize);
+                self.labels.push(true);
+                self.leftmost.push(leftmost.unwrap_or(self.labels.len() - 1));
+                k = j as usize;
+                *self.leftmost.last().unwrap()
+            } else {
+                self.labels.push(false);
+                self.leftmost.push(self.labels.len() - 1);
+                k += 1;
+                self.labels.len() - 1
+            };
+            first.get_or_insert(leftmost);
+        }
+        first
+    }
+
+    /// Returns the keyroots: the highest node with each leftmost leaf, in increasing order.
+    fn keyroots(&self) -> Vec<usize> {
+        let mut highest: HashMap<usize, usize> = HashMap::new();
+        for (node, leftmost) in self.leftmost.iter().enumerate() {
+            highest.insert(*leftmost, node);
+        }
+        let mut keyroots = highest.into_values().collect::<Vec<usize>>();
+        keyroots.sort_unstable();
+        keyroots
+    }
+}
+
+/// Returns the tree edit distance between two non-pseudoknotted secondary structures, with unit
+/// costs for inserting, deleting, and relabelling a node, computed using the algorithm of Zhang
+/// and Shasha (1989). Each structure is represented as an ordered rooted tree, in which each
+/// base-pair and each unpaired nucleotide is a node, the children of a base-pair are the
+/// base-pairs and unpaired nucleotides it directly encloses, and the children of the root are
+/// those of the external loop. The structures may differ in length.
+///
+/// Returns [StructureParseError::Pseudoknotted](../secondary_structure/enum.StructureParseError.html)
+/// if either structure contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::tree_edit_distance;
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// let a = from_dotbracketstring("((...))").unwrap();
+/// let b = from_dotbracketstring("((....))").unwrap();
+/// assert_eq!(tree_edit_distance(&a, &a).unwrap(), 0);
+/// assert_eq!(tree_edit_distance(&a, &b).unwrap(), 1);
+/// ```
+pub fn tree_edit_distance(a: &dyn PairedSites, b: &dyn PairedSites) -> Result<usize, StructureParseError> {
+    if !is_planar(a, &[]) || !is_planar(b, &[]) {
+        return Err(StructureParseError::Pseudoknotted);
+    }
+    let a = PostorderTree::from_paired(a.paired());
+    let b = PostorderTree::from_paired(b.paired());
+    let (n, m) = (a.labels.len(), b.labels.len());
+
+    let mut tree_distance = vec![vec![0; m]; n];
+    for i in a.keyroots() {
+        for j in b.keyroots() {
+            // the forest distance between the postorder ranges a.leftmost[i]..=x and b.leftmost[j]..=y,
+            // offset so that index 0 is the empty forest
+            let (li, lj) = (a.leftmost[i], b.leftmost[j]);
+            let mut forest = vec![vec![0; j - lj + 2]; i - li + 2];
+            for x in 1..i - li + 2 {
+                forest[x][0] = forest[x - 1][0] + 1;
+            }
+            for y in 1..j - lj + 2 {
+                forest[0][y] = forest[0][y - 1] + 1;
+            }
+            for x in 1..i - li + 2 {
+                for y in 1..j - lj + 2 {
+                    let (node_a, node_b) = (li + x - 1, lj + y - 1);
+                    let delete = forest[x - 1][y] + 1;
+                    let insert = forest[x][y - 1] + 1;
+                    forest[x][y] = if a.leftmost[node_a] == li && b.leftmost[node_b] == lj {
+                        let relabel = forest[x - 1][y - 1] + if a.labels[node_a] == b.labels[node_b] { 0 } else { 1 };
+                        let distance = delete.min(insert).min(relabel);
+                        tree_distance[node_a][node_b] = distance;
+                        distance
+                    } else {
+                        let subtree = forest[a.leftmost[node_a] - li][b.leftmost[node_b] - lj] + tree_distance[node_a][node_b];
+                        delete.min(insert).min(subtree)
+                    };
+                }
+            }
+        }
+    }
+    Ok(tree_distance[n - 1][m - 1])
+}
+
+/// Returns the index of, and distance to, the structure in `set` closest to `query` under the given
+/// metric, or `None` if `set` is empty. Ties return the first structure encountered, and the
+/// search stops early at a structure identical (distance 0.0) to the query.
+///
+/// Returns `UnequalLength` if any structure in `set` differs in length from the query.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::{nearest_neighbor, get_weighted_mountain_distance};
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, PairedSites};
+/// let query = from_dotbracketstring("((...))..").unwrap();
+/// let p1 = from_dotbracketstring(".........").unwrap();
+/// let p2 = from_dotbracketstring("((....)).").unwrap();
+/// let (index, _distance) = nearest_neighbor(&query, &[&p1, &p2], &get_weighted_mountain_distance).unwrap().unwrap();
+/// assert_eq!(index, 1);
+/// assert!(nearest_neighbor(&query, &[], &get_weighted_mountain_distance).unwrap().is_none());
+/// ```
+pub fn nearest_neighbor(query: &dyn PairedSites, set: &[&dyn PairedSites], metric: &dyn StructureMetric) -> Result<Option<(usize, f64)>, SecondaryStructureMetricError> {
+    let len = query.paired().len();
+    if set.iter().any(|paired| paired.paired().len() != len) {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let mut nearest: Option<(usize, f64)> = None;
+    for (index, paired) in set.iter().enumerate() {
+        let d = metric.distance(query, *paired)?;
+        match nearest {
+            Some((_, best)) if best <= d => {}
+            _ => nearest = Some((index, d)),
+        }
+        if d == 0.0 {
+            break;
+        }
+    }
+    Ok(nearest)
+}
+
+/// The loops present in one secondary structure but not the other, as returned by
+/// [loop_difference](fn.loop_difference.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopDiff {
+    /// Loops of the first structure with no matching loop in the second.
+    pub only_in_a: Vec<Loop>,
+    /// Loops of the second structure with no matching loop in the first.
+    pub only_in_b: Vec<Loop>,
+}
+
+/// Returns the kind of a loop together with the base-pairs closing it, which identify the loop
+/// independently of its unpaired positions.
+fn loop_key(l: &Loop) -> (std::mem::Discriminant<Loop>, Vec<(usize, usize)>) {
+    let closing = match l {
+        Loop::Hairpin { closing, .. } => vec![*closing],
+        Loop::Stack { outer, inner } => vec![*outer, *inner],
+        Loop::Bulge { outer, inner, .. } => vec![*outer, *inner],
+        Loop::Internal { outer, inner, .. } => vec![*outer, *inner],
+        Loop::Multiloop { closing, branches, .. } => std::iter::once(*closing).chain(branches.iter().cloned()).collect(),
+        Loop::External { branches, .. } => branches.clone(),
+    };
+    (std::mem::discriminant(l), closing)
+}
+
+/// Returns the symmetric difference of the loop decompositions (see
+/// [decompose_loops](../secondary_structure/fn.decompose_loops.html)) of two structures. Two loops
+/// match if they are of the same kind and are closed by the same base-pairs, so that, for example,
+/// a hairpin that became an internal loop is reported as a hairpin only in `a` and an internal
+/// loop only in `b`. Loops are reported in decomposition order.
+///
+/// Returns `UnequalLength` if the structures differ in length, or a `Pseudoknotted` structure error
+/// if either structure contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::distance_metrics::loop_difference;
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, Loop};
+/// let a = from_dotbracketstring("((.......))").unwrap();
+/// let b = from_dotbracketstring("((.(...).))").unwrap();
+/// let diff = loop_difference(&a, &b).unwrap();
+/// assert_eq!(diff.only_in_a, vec![Loop::Hairpin { closing: (1, 9), unpaired: (2..9).collect() }]);
+/// assert_eq!(diff.only_in_b, vec![
+///     Loop::Internal { outer: (1, 9), inner: (3, 7), unpaired: vec![2, 8] },
+///     Loop::Hairpin { closing: (3, 7), unpaired: vec![4, 5, 6] },
+/// ]);
+/// ```
+pub fn loop_difference(a: &dyn PairedSites, b: &dyn PairedSites) -> Result<LoopDiff, SecondaryStructureMetricError> {
+    if a.paired().len() != b.paired().len() {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+    let loops_a = decompose_loops(a)?;
+    let loops_b = decompose_loops(b)?;
+    let keys_a = loops_a.iter().map(loop_key).collect::<Vec<_>>();
+    let keys_b = loops_b.iter().map(loop_key).collect::<Vec<_>>();
+
+    let only_in_a = loops_a.iter().zip(keys_a.iter())
+        .filter(|(_, key)| !keys_b.contains(key))
+        .map(|(l, _)| l.clone())
+        .collect();
+    let only_in_b = loops_b.iter().zip(keys_b.iter())
+        .filter(|(_, key)| !keys_a.contains(key))
+        .map(|(l, _)| l.clone())
+        .collect();
+    Ok(LoopDiff { only_in_a, only_in_b })
+}
+
+/// Returns the consensus of a set of secondary structures of equal length, containing each
+/// base-pair that occurs in more than `threshold` of the structures (as a fraction, e.g. 0.5 for a
+/// majority). If two such base-pairs share a position, the more frequent one is kept (or the one
+/// with the smaller 5' position if equally frequent), so that no site is paired twice.
+///
+/// Returns `UnequalLength` if the structures differ in length.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, PairedSites};
+/// use rna_secondary_structure::distance_metrics::consensus_structure;
+/// let p1 = from_dotbracketstring("((...))").unwrap();
+/// let p2 = from_dotbracketstring("(.....)").unwrap();
+/// let p3 = from_dotbracketstring(".(...).").unwrap();
+/// let consensus = consensus_structure(&[&p1, &p2, &p3], 0.5).unwrap();
+/// assert_eq!(get_dot_bracket_string(&consensus).unwrap(), "((...))");
+/// ```
+pub fn consensus_structure(structures: &[&dyn PairedSites], threshold: f64) -> Result<Vec<i64>, SecondaryStructureMetricError> {
+    let len = match structures.first() {
+        Some(first) => first.paired().len(),
+        None => return Ok(Vec::new())
+    };
+    if structures.iter().any(|paired| paired.paired().len() != len) {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+
+    let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+    for paired in structures {
+        for pair in base_pair_set(paired.paired()) {
+            *counts.entry(pair).or_insert(0) += 1;
+        }
+    }
+    let mut frequent = counts.into_iter()
+        .filter(|(_, count)| (*count as f64) / (structures.len() as f64) > threshold)
+        .collect::<Vec<((usize, usize), usize)>>();
+    frequent.sort_unstable_by(|(pair1, count1), (pair2, count2)| count2.cmp(count1).then(pair1.cmp(pair2)));
+
+    let mut consensus = vec![0; len];
+    for ((i, j), _) in frequent {
+        if consensus[i] == 0 && consensus[j] == 0 {
+            consensus[i] = (j + 1) as i64;
+            consensus[j] = (i + 1) as i64;
+        }
+    }
+    Ok(consensus)
+}
@@ -0,0 +1,384 @@
+//! A module for predicting secondary structures from nucleotide sequences.
+
+use rand::Rng;
+
+use crate::secondary_structure::{is_canonical_pair, normalise_nucleotide, SecondaryStructureRecord};
+
+/// The thermal energy RT, in kcal/mol, at 37 degrees Celsius.
+const RT: f64 = 0.61632;
+
+/// Folds a nucleotide sequence using the Nussinov algorithm, returning a non-pseudoknotted
+/// secondary structure with the maximum number of canonical base-pairs (A-U, G-C and G-U, see
+/// [is_canonical_pair](../secondary_structure/fn.is_canonical_pair.html)) such that every hairpin
+/// loop contains at least `min_loop` unpaired nucleotides.
+///
+/// The dynamic programming recursion takes O(n^3) time and O(n^2) memory. Where several structures
+/// have the maximum number of base-pairs, the traceback prefers leaving the 5'-most nucleotide of an
+/// interval unpaired, and otherwise pairs it with its nearest possible partner. The returned record
+/// has the input sequence and an empty name. See [nussinov_with](fn.nussinov_with.html) to choose
+/// which base-pairs are allowed.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::folding::nussinov;
+/// let ss = nussinov("GGGAAACCC", 3);
+/// assert_eq!(ss.get_dot_bracket_string().unwrap(), "(((...)))");
+/// ```
+pub fn nussinov(sequence: &str, min_loop: usize) -> SecondaryStructureRecord {
+    nussinov_with(sequence, min_loop, canonical_plus_wobble)
+}
+
+/// Returns true if the two nucleotides can form a Watson-Crick base-pair (A-U or G-C).
+/// Nucleotides are case-insensitive and T is treated as U.
+pub fn canonical_pairs(a: char, b: char) -> bool {
+    matches!((normalise_nucleotide(a), normalise_nucleotide(b)), ('A', 'U') | ('U', 'A') | ('G', 'C') | ('C', 'G'))
+}
+
+/// Returns true if the two nucleotides can form a Watson-Crick (A-U, G-C) or G-U wobble
+/// base-pair, see [is_canonical_pair](../secondary_structure/fn.is_canonical_pair.html).
+pub fn canonical_plus_wobble(a: char, b: char) -> bool {
+    is_canonical_pair(a, b)
+}
+
+/// Folds a nucleotide sequence using the Nussinov algorithm as [nussinov](fn.nussinov.html)
+/// does, but allowing only the base-pairs of nucleotides for which 'can_pair' returns true, such
+/// as [canonical_pairs](fn.canonical_pairs.html) or
+/// [canonical_plus_wobble](fn.canonical_plus_wobble.html).
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::folding::{canonical_pairs, nussinov_with};
+/// let ss = nussinov_with("GGGAAAUCC", 3, canonical_pairs);
+/// assert_eq!(ss.get_dot_bracket_string().unwrap(), ".((....))");
+/// ```
+pub fn nussinov_with(sequence: &str, min_loop: usize, can_pair: impl Fn(char, char) -> bool) -> SecondaryStructureRecord {
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    let len = nucleotides.len();
+    let can_pair = |i: usize, k: usize| k > i + min_loop && can_pair(nucleotides[i], nucleotides[k]);
+
+    // best[i][j] is the maximum number of base-pairs within the half-open interval i..j
+    let mut best = vec![vec![0usize; len + 1]; len + 1];
+    for i in (0..len).rev() {
+        for j in i + 1..=len {
+            let mut b = best[i + 1][j];
+            for k in i + min_loop + 1..j {
+                if can_pair(i, k) {
+                    b = b.max(1 + best[i + 1][k] + best[k + 1][j]);
+                }
+            }
+            best[i][j] = b;
+        }
+    }
+
+    let mut paired = vec![0; len];
+    let mut intervals = vec![(0, len)];
+    while let Some((i, j)) = intervals.pop() {
+        if i >= j {
+            continue;
+        }
+        if best[i][j] == best[i + 1][j] {
+            intervals.push((i + 1, j));
+            continue;
+        }
+        let k = (i + min_loop + 1..j)
+            .find(|k| can_pair(i, *k) && best[i][j] == 1 + best[i + 1][*k] + best[*k + 1][j])
+            .unwrap();
+        paired[i] = (k + 1) as i64;
+        paired[k] = (i + 1) as i64;
+        intervals.push((i + 1, k));
+        intervals.push((k + 1, j));
+    }
+
+    let mut ss = SecondaryStructureRecord::new(paired);
+    ss.set_sequence(sequence.to_string());
+    ss
+}
+
+/// Returns the maximum expected accuracy (MEA) structure of a sequence of length `len` given its
+/// base-pair probabilities, as 0-based `(i, j, p)` triples (e.g. from
+/// [parse_dotplot_ps](../io/fn.parse_dotplot_ps.html)). The MEA structure is the
+/// non-pseudoknotted secondary structure S maximising
+/// `sum_{(i,j) in S} 2 * gamma * p_ij + sum_{i unpaired in S} q_i`, where `q_i = 1 - sum_j p_ij` is
+/// the probability that i is unpaired. Larger values of `gamma` favour more base-pairs.
+///
+/// Base-pairs may be given in either orientation, base-pairs with a position beyond `len` or a
+/// position paired with itself are ignored, and a later triple for the same base-pair replaces an
+/// earlier one. The dynamic programming recursion takes O(n^3) time and O(n^2) memory, and ties are
+/// broken as in [nussinov](fn.nussinov.html). The returned record has a sequence of all N's.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::folding::mea_structure;
+/// let probabilities = vec![(0, 8, 0.9), (1, 7, 0.9), (2, 6, 0.8), (3, 5, 0.1)];
+/// let ss = mea_structure(9, &probabilities, 1.0);
+/// assert_eq!(ss.get_dot_bracket_string().unwrap(), "(((...)))");
+/// ```
+pub fn mea_structure(len: usize, probs: &[(usize, usize, f64)], gamma: f64) -> SecondaryStructureRecord {
+    let mut p = vec![vec![0.0; len]; len];
+    for (i, j, prob) in probs.iter() {
+        let (i, j) = if i < j { (*i, *j) } else { (*j, *i) };
+        if i != j && j < len {
+            p[i][j] = *prob;
+            p[j][i] = *prob;
+        }
+    }
+    let unpaired = p.iter().map(|row| 1.0 - row.iter().sum::<f64>()).collect::<Vec<f64>>();
+
+    // best[i][j] is the maximum expected accuracy of the half-open interval i..j
+    let mut best = vec![vec![0.0; len + 1]; len + 1];
+    for i in (0..len).rev() {
+        for j in i + 1..=len {
+            let mut b = best[i + 1][j] + unpaired[i];
+            for k in i + 1..j {
+                if p[i][k] > 0.0 {
+                    b = f64::max(b, 2.0 * gamma * p[i][k] + best[i + 1][k] + best[k + 1][j]);
+                }
+            }
+            best[i][j] = b;
+        }
+    }
+
+    let mut paired = vec![0; len];
+    let mut intervals = vec![(0, len)];
+    while let Some((i, j)) = intervals.pop() {
+        if i >= j {
+            continue;
+        }
+        if best[i][j] == best[i + 1][j] + unpaired[i] {
+            intervals.push((i + 1, j));
+            continue;
+        }
+        let k = (i + 1..j)
+            .find(|k| p[i][*k] > 0.0 && best[i][j] == 2.0 * gamma * p[i][*k] + best[i + 1][*k] + best[*k + 1][j])
+            .unwrap();
+        paired[i] = (k + 1) as i64;
+        paired[k] = (i + 1) as i64;
+        intervals.push((i + 1, k));
+        intervals.push((k + 1, j));
+    }
+
+    SecondaryStructureRecord::new(paired)
+}
+
+/// Returns the free energy, in kcal/mol, of a base-pair of the two nucleotides in the simple energy
+/// model used for the partition function and Boltzmann sampling, or None if they cannot form a
+/// [canonical pair](../secondary_structure/fn.is_canonical_pair.html): -3.0 for G-C, -2.0 for
+/// A-U, and -1.0 for G-U.
+fn pair_energy(a: char, b: char) -> Option<f64> {
+    if !is_canonical_pair(a, b) {
+        return None;
+    }
+    match (normalise_nucleotide(a), normalise_nucleotide(b)) {
+        ('G', 'C') | ('C', 'G') => Some(-3.0),
+        ('G', _) | (_, 'G') => Some(-1.0),
+        _ => Some(-2.0)
+    }
+}
+
+/// Returns the Boltzmann weight `exp(-E / RT)` of every possible base-pair `(i, k)` with more than
+/// `min_loop` positions between them, or 0.0 where no base-pair is possible.
+fn pair_weights(nucleotides: &[char], min_loop: usize) -> Vec<Vec<f64>> {
+    let len = nucleotides.len();
+    let mut weights = vec![vec![0.0; len]; len];
+    for i in 0..len {
+        for k in i + min_loop + 1..len {
+            if let Some(energy) = pair_energy(nucleotides[i], nucleotides[k]) {
+                weights[i][k] = (-energy / RT).exp();
+            }
+        }
+    }
+    weights
+}
+
+/// The inside partition functions of the half-open intervals i..j of a sequence, rescaled by
+/// span so that they remain within the range of an f64 for long sequences: `q[i][j]` times
+/// `exp(log_scale[j - i])` is the sum of the Boltzmann weights of the non-pseudoknotted
+/// structures on the interval. Each span is scaled so that its largest `q[i][j]` is 1.
+struct InsidePartition {
+    q: Vec<Vec<f64>>,
+    log_scale: Vec<f64>,
+}
+
+impl InsidePartition {
+    /// Returns the factor by which the product of the rescaled partition functions of two
+    /// intervals of spans `a` and `b` is multiplied to be rescaled as an interval of span `d`.
+    fn factor(&self, a: usize, b: usize, d: usize) -> f64 {
+        (self.log_scale[a] + self.log_scale[b] - self.log_scale[d]).exp()
+    }
+}
+
+/// Returns the rescaled inside partition function of every half-open interval i..j, computed in
+/// order of increasing span.
+fn inside_partition(weights: &[Vec<f64>]) -> InsidePartition {
+    let len = weights.len();
+    let mut inside = InsidePartition { q: vec![vec![1.0; len + 1]; len + 1], log_scale: vec![0.0; len + 1] };
+    for span in 1..=len {
+        // the base-pair (i, k) encloses an interval of span a and is followed by one of span
+        // span - 2 - a, which are computed relative to the scale of span - 1 and then rescaled
+        let factors = (0..span - 1).map(|a| inside.factor(a, span - 2 - a, span - 1)).collect::<Vec<f64>>();
+        let q = &mut inside.q;
+        let mut largest: f64 = 0.0;
+        for i in 0..=len - span {
+            let j = i + span;
+            let mut z = q[i + 1][j];
+            for k in i + 1..j {
+                if weights[i][k] > 0.0 {
+                    z += weights[i][k] * q[i + 1][k] * q[k + 1][j] * factors[k - i - 1];
+                }
+            }
+            q[i][j] = z;
+            largest = largest.max(z);
+        }
+        if largest <= 0.0 {
+            largest = 1.0;
+        }
+        for i in 0..=len - span {
+            q[i][i + span] /= largest;
+        }
+        inside.log_scale[span] = inside.log_scale[span - 1] + largest.ln();
+    }
+    inside
+}
+
+/// Returns the partition function of a nucleotide sequence, the sum of the Boltzmann weights
+/// `exp(-E / RT)` of its non-pseudoknotted secondary structures with RT = 0.61632 kcal/mol
+/// (37 degrees Celsius), together with its base-pair probabilities as 0-based `(i, j, p)` triples
+/// with i < j, in order of i and then j. Only base-pairs with a non-zero probability are returned,
+/// so the probabilities can be passed directly to [mea_structure](fn.mea_structure.html).
+///
+/// The energy model is that of [boltzmann_sample](fn.boltzmann_sample.html): the base-pairs
+/// allowed by [nussinov](fn.nussinov.html), with every hairpin loop containing at least
+/// `min_loop` unpaired nucleotides, each contributing a free energy of -3.0 kcal/mol for G-C, -2.0
+/// for A-U, and -1.0 for G-U, and the unstructured sequence having a weight of 1. The inside and
+/// outside recursions of McCaskill's algorithm take O(n^3) time and O(n^2) memory.
+///
+/// The recursions rescale the partition function of each interval by its length, so the
+/// probabilities are accurate for sequences of any length. The partition function itself exceeds
+/// the range of an f64 for sequences longer than roughly 400 nucleotides, and is then returned as
+/// infinity.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::folding::{mea_structure, partition_function};
+/// let (z, probabilities) = partition_function("GGGGAAAACCCC", 3);
+/// assert!(z > 1.0);
+/// let ss = mea_structure(12, &probabilities, 1.0);
+/// assert_eq!(ss.get_dot_bracket_string().unwrap(), "((((....))))");
+/// ```
+pub fn partition_function(sequence: &str, min_loop: usize) -> (f64, Vec<(usize, usize, f64)>) {
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    let len = nucleotides.len();
+    let weights = pair_weights(&nucleotides, min_loop);
+    let inside = inside_partition(&weights);
+    let q = &inside.q;
+    let z = (q[0][len].ln() + inside.log_scale[len]).exp();
+
+    // outside[i][j] * q[i][j] is the probability that the half-open interval i..j is decomposed,
+    // filled from the longest interval down so that each is complete before it is used
+    let mut outside = vec![vec![0.0; len + 1]; len + 1];
+    outside[0][len] = 1.0 / q[0][len];
+    let mut probability = vec![vec![0.0; len]; len];
+    for span in (1..=len).rev() {
+        let unpaired = inside.factor(span - 1, 0, span);
+        let factors = (0..span - 1).map(|a| inside.factor(a, span - 2 - a, span)).collect::<Vec<f64>>();
+        for i in 0..=len - span {
+            let j = i + span;
+            let o = outside[i][j];
+            if o == 0.0 {
+                continue;
+            }
+            outside[i + 1][j] += o * unpaired;
+            for k in i + 1..j {
+                if weights[i][k] > 0.0 {
+                    let w = o * weights[i][k] * factors[k - i - 1];
+                    outside[i + 1][k] += w * q[k + 1][j];
+                    outside[k + 1][j] += w * q[i + 1][k];
+                    probability[i][k] += w * q[i + 1][k] * q[k + 1][j];
+                }
+            }
+        }
+    }
+
+    let mut probs = Vec::new();
+    for (i, row) in probability.iter().enumerate() {
+        for (k, p) in row.iter().enumerate() {
+            if *p > 0.0 {
+                probs.push((i, k, *p));
+            }
+        }
+    }
+    (z, probs)
+}
+
+/// Draws `n` non-pseudoknotted secondary structures of a nucleotide sequence from its Boltzmann
+/// ensemble, so that each structure is drawn with probability proportional to
+/// `exp(-E / RT)`, with RT = 0.61632 kcal/mol (37 degrees Celsius).
+///
+/// The pairing model is that of [nussinov](fn.nussinov.html), with the same base-pairs allowed
+/// and every hairpin loop containing at least `min_loop` unpaired nucleotides, but each base-pair
+/// contributes a free energy to E: -3.0 kcal/mol for G-C, -2.0 for A-U, and -1.0 for G-U. The
+/// structures are drawn by stochastic traceback through the McCaskill partition function, which
+/// takes O(n^3) time and O(n^2) memory, after which each structure takes O(n^2) time. As in
+/// [partition_function](fn.partition_function.html), the partition functions are rescaled so that
+/// they do not overflow for long sequences. The returned records have the input sequence and an
+/// empty name.
+///
+/// # Examples
+/// ```rust
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rna_secondary_structure::folding::boltzmann_sample;
+/// let mut rng = StdRng::seed_from_u64(7);
+/// let samples = boltzmann_sample("GGGGAAAACCCC", 3, 10, &mut rng);
+/// assert_eq!(samples.len(), 10);
+/// assert!(samples.iter().all(|ss| ss.sequence == "GGGGAAAACCCC"));
+/// ```
+pub fn boltzmann_sample(sequence: &str, min_loop: usize, n: usize, rng: &mut impl Rng) -> Vec<SecondaryStructureRecord> {
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    let len = nucleotides.len();
+    let weights = pair_weights(&nucleotides, min_loop);
+    let inside = inside_partition(&weights);
+    let q = &inside.q;
+
+    let mut samples = Vec::with_capacity(n);
+    for _ in 0..n {
+        let mut paired = vec![0; len];
+        let mut intervals = vec![(0, len)];
+        while let Some((i, j)) = intervals.pop() {
+            if i >= j {
+                continue;
+            }
+            let span = j - i;
+            let mut r = rng.gen::<f64>() * q[i][j] - q[i + 1][j] * inside.factor(span - 1, 0, span);
+            if r < 0.0 {
+                intervals.push((i + 1, j));
+                continue;
+            }
+            // falls back to the last possible partner should rounding leave r slightly positive
+            let mut partner = None;
+            for k in i + 1..j {
+                if weights[i][k] > 0.0 {
+                    partner = Some(k);
+                    r -= weights[i][k] * q[i + 1][k] * q[k + 1][j] * inside.factor(k - i - 1, j - k - 1, span);
+                    if r < 0.0 {
+                        break;
+                    }
+                }
+            }
+            match partner {
+                Some(k) => {
+                    paired[i] = (k + 1) as i64;
+                    paired[k] = (i + 1) as i64;
+                    intervals.push((i + 1, k));
+                    intervals.push((k + 1, j));
+                }
+                None => intervals.push((i + 1, j))
+            }
+        }
+
+        let mut ss = SecondaryStructureRecord::new(paired);
+        ss.set_sequence(sequence.to_string());
+        samples.push(ss);
+    }
+    samples
+}
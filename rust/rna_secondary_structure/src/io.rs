@@ -1,52 +1,322 @@
 //! A module for parsing, reading, and writing various secondary structure formats.
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Lines, Read};
 use std::iter::*;
 use std::path::Path;
 
+use thiserror::Error;
+
+use crate::read_rfam::parse_rfam_stockholm;
 use crate::secondary_structure;
-use crate::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, SecondaryStructureRecord, StructureParseError};
+use crate::secondary_structure::{from_dotbracketstring, from_wuss_string, get_dot_bracket_string, is_canonical_pair, is_pseudoknot_safe, SecondaryStructureRecord, StructureParseError};
 
-fn parse_ct(reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
-    let mut ls: Vec<SecondaryStructureRecord> = Vec::new();
-    let mut sequence = "".to_string();
-    let mut paired = Vec::new();
-    let mut name = "".to_string();
-    for line in reader.lines() {
-        let line = line?;
-        let spl = line.trim().split_whitespace().collect::<Vec<&str>>();
-        if !spl.is_empty() && spl[0].starts_with('>') {
-            if !paired.is_empty() {
-                ls.push(SecondaryStructureRecord {
-                    name: name.clone(),
-                    sequence: sequence.to_string(),
-                    paired: paired.clone(),
-                });
-                sequence = "".to_string();
-                paired.clear();
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum CtParseError {
+    #[error("Line {line}: expected a CT header line of the form '<length> <name>'.")]
+    ExpectedHeader {
+        line: usize,
+    },
+
+    #[error("Line {line}: expected 6 columns, found {found}.")]
+    WrongColumnCount {
+        line: usize,
+        found: usize,
+    },
+
+    #[error("Line {line}: expected an integer in columns 1, 3, 4, 5 and 6.")]
+    BadInteger {
+        line: usize,
+    },
+
+    #[error("Line {line}: expected index {expected}, found {found}.")]
+    NonSequentialIndex {
+        line: usize,
+        expected: i64,
+        found: i64,
+    },
+
+    #[error("Line {line}: partner {partner} is outside the range 0..={len}.")]
+    OutOfRangePartner {
+        line: usize,
+        partner: i64,
+        len: usize,
+    },
+
+    #[error("Line {line}: position is paired with itself.")]
+    SelfPairing {
+        line: usize,
+    },
+
+    #[error("Line {line}: header declares {expected} positions, but {found} were found.")]
+    TruncatedRecord {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Invalid secondary structure: {source}")]
+    InvalidStructure {
+        #[from]
+        source: StructureParseError,
+    },
+
+    #[error("I/O error: {source}")]
+    Io {
+        #[from]
+        source: io::Error,
+    },
+}
+
+/// Parses a CT header line of the form `<length> ENERGY = <energy> <name>` (or `dG = <energy>`, as
+/// written by mfold) or `<length> <name>`, returning the name and the energy if present.
+fn parse_ct_header(line: &str) -> (String, Option<f64>) {
+    let line = line.trim();
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start();
+    for tag in ["ENERGY", "Energy", "dG"].iter() {
+        if let Some(value) = rest.strip_prefix(tag) {
+            let value = value.trim_start().trim_start_matches('=').trim_start();
+            let end = value.find(char::is_whitespace).unwrap_or(value.len());
+            if let Ok(energy) = value[..end].parse::<f64>() {
+                return (value[end..].trim().to_string(), Some(energy));
             }
-            name = line[1..].to_string();
-        } else if spl.len() >= 6 && spl[0].parse::<i64>().is_ok() && spl[5].parse::<i64>().is_ok() {
-            sequence.push_str(spl[1]);
-            paired.push(spl[4].parse::<i64>().unwrap());
         }
     }
-    if !paired.is_empty() {
-        ls.push(SecondaryStructureRecord {
-            name,
-            sequence,
-            paired: paired.clone(),
-        });
-        paired.clear();
+    (rest.to_string(), None)
+}
+
+/// Builds a record from the residue numbers (column 0) and partner numbers (column 4) of a CT
+/// record. Residue numbers are mapped to dense 0-based positions in order of appearance, so that
+/// CT files with non-contiguous numbering (e.g. from structures with missing residues) produce a
+/// dense paired vector. Partners referring to residues not present are treated as unpaired. The
+/// original numbering is retained only if it differs from `1..=n`.
+fn ct_record(name: String, sequence: String, numbers: &[i64], partners: &[i64], energy: Option<f64>) -> SecondaryStructureRecord {
+    let contiguous = numbers.iter().enumerate().all(|(i, number)| *number == (i as i64) + 1);
+    let paired = if contiguous {
+        partners.to_vec()
+    } else {
+        let index: HashMap<i64, i64> = numbers.iter().enumerate().map(|(i, number)| (*number, (i as i64) + 1)).collect();
+        partners.iter().map(|partner| *index.get(partner).unwrap_or(&0)).collect()
+    };
+
+    SecondaryStructureRecord {
+        name,
+        sequence,
+        paired,
+        energy,
+        annotation: None,
+        numbering: if contiguous { None } else { Some(numbers.to_vec()) },
     }
-    Ok(ls)
+}
+
+/// How strictly the CT grammar is applied by [CtRecords](struct.CtRecords.html).
+#[derive(Clone, Copy, PartialEq)]
+enum CtStrictness {
+    /// Records are delimited by header lines, which are `>name` lines or other lines starting with
+    /// an integer that are not data lines, and data lines are those accepted by
+    /// [is_ct_data_line](fn.is_ct_data_line.html). Non-contiguous numbering is retained.
+    Lenient,
+    /// As `Lenient`, but each record is also validated.
+    Validated,
+    /// Each record starts with a `>name` line or a numeric header declaring the number of
+    /// positions, which must then be matched exactly by the data lines that follow. Every data
+    /// line must have 6 integer columns (bar the nucleotide), indices must run `1, 2, ..., n` and
+    /// partners must lie in `0..=n`, may not refer to the position itself, and must be symmetric.
+    Strict,
+}
+
+/// A CT record being read: the name, energy, length declared by a numeric header (if any), the
+/// header line number, the sequence and the number and partner of each position with the line
+/// number it was read from.
+#[derive(Default)]
+struct CtRecordLines {
+    name: String,
+    energy: Option<f64>,
+    declared_len: Option<usize>,
+    header_line: usize,
+    sequence: String,
+    numbers: Vec<i64>,
+    partners: Vec<(i64, usize)>,
+}
+
+impl CtRecordLines {
+    /// Starts a record from a `>name` or numeric header line. Strictly, a numeric header must
+    /// begin with the number of positions.
+    fn from_header(line: &str, line_number: usize, strictness: CtStrictness) -> Result<CtRecordLines, CtParseError> {
+        let first = line.split_whitespace().next().unwrap_or("");
+        let (name, energy, declared_len) = if first.starts_with('>') {
+            (line.trim()[1..].to_string(), None, None)
+        } else {
+            let declared_len = first.parse::<usize>().ok();
+            if strictness == CtStrictness::Strict && declared_len.is_none() {
+                return Err(CtParseError::ExpectedHeader { line: line_number });
+            }
+            let (name, energy) = parse_ct_header(line);
+            (name, energy, declared_len)
+        };
+        Ok(CtRecordLines { name, energy, declared_len, header_line: line_number, ..Default::default() })
+    }
+
+    fn is_complete(&self) -> bool {
+        self.declared_len == Some(self.partners.len())
+    }
+
+    /// Adds the position on a data line, split into columns.
+    fn push(&mut self, spl: &[&str], line_number: usize, strictness: CtStrictness) -> Result<(), CtParseError> {
+        let (number, partner) = if strictness == CtStrictness::Strict {
+            if spl.len() != 6 {
+                return Err(CtParseError::WrongColumnCount { line: line_number, found: spl.len() });
+            }
+            let columns = [spl[0], spl[2], spl[3], spl[4], spl[5]].iter()
+                .map(|column| column.parse::<i64>())
+                .collect::<Result<Vec<i64>, _>>()
+                .map_err(|_| CtParseError::BadInteger { line: line_number })?;
+            let expected = (self.partners.len() as i64) + 1;
+            if columns[0] != expected {
+                return Err(CtParseError::NonSequentialIndex { line: line_number, expected, found: columns[0] });
+            }
+            (columns[0], columns[3])
+        } else {
+            if !is_ct_data_columns(spl) {
+                return Err(CtParseError::WrongColumnCount { line: line_number, found: spl.len() });
+            }
+            let partner = spl[4].parse::<i64>().map_err(|_| CtParseError::BadInteger { line: line_number })?;
+            (spl[0].parse::<i64>().unwrap(), partner)
+        };
+        self.sequence.push_str(spl[1]);
+        self.numbers.push(number);
+        self.partners.push((partner, line_number));
+        Ok(())
+    }
+
+    fn finish(self, strictness: CtStrictness) -> Result<SecondaryStructureRecord, CtParseError> {
+        let len = self.partners.len();
+        if strictness == CtStrictness::Strict {
+            if let Some(expected) = self.declared_len {
+                if expected != len {
+                    return Err(CtParseError::TruncatedRecord { line: self.header_line, expected, found: len });
+                }
+            }
+            for (i, (partner, line)) in self.partners.iter().enumerate() {
+                if *partner < 0 || *partner > len as i64 {
+                    return Err(CtParseError::OutOfRangePartner { line: *line, partner: *partner, len });
+                }
+                if *partner == (i as i64) + 1 {
+                    return Err(CtParseError::SelfPairing { line: *line });
+                }
+            }
+        }
+
+        let partners = self.partners.iter().map(|(partner, _)| *partner).collect::<Vec<i64>>();
+        let ss = ct_record(self.name, self.sequence, &self.numbers, &partners, self.energy);
+        if strictness != CtStrictness::Lenient {
+            ss.validate()?;
+        }
+        Ok(ss)
+    }
+}
+
+/// An iterator over the records of a CT buffer, holding the header line that ended the previous
+/// record, if any, with its line number. This implements the CT grammar for every CT parser, at
+/// the given [CtStrictness](enum.CtStrictness.html). A record containing an error is returned as
+/// an `Err` once it has been read, and iteration continues with the next record.
+struct CtRecords<R: BufRead> {
+    lines: Enumerate<Lines<R>>,
+    header: Option<(usize, String)>,
+    strictness: CtStrictness,
+}
+
+impl<R: BufRead> CtRecords<R> {
+    fn new(reader: R, strictness: CtStrictness) -> CtRecords<R> {
+        CtRecords { lines: reader.lines().enumerate(), header: None, strictness }
+    }
+}
+
+impl<R: BufRead> Iterator for CtRecords<R> {
+    type Item = Result<SecondaryStructureRecord, CtParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let strict = self.strictness == CtStrictness::Strict;
+        loop {
+            let mut record: Option<CtRecordLines> = None;
+            let mut error: Option<CtParseError> = None;
+            if let Some((line_number, header)) = self.header.take() {
+                match CtRecordLines::from_header(&header, line_number, self.strictness) {
+                    Ok(header) => record = Some(header),
+                    Err(err) => return Some(Err(err))
+                }
+            }
+
+            for (line_number, line) in &mut self.lines {
+                let line_number = line_number + 1;
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => return Some(Err(err.into()))
+                };
+                let spl = line.split_whitespace().collect::<Vec<&str>>();
+                if spl.is_empty() {
+                    continue;
+                }
+                let is_header = spl[0].starts_with('>') || if strict {
+                    record.as_ref().is_none_or(|record| record.is_complete())
+                } else {
+                    !is_ct_data_columns(&spl) && spl[0].parse::<i64>().is_ok()
+                };
+
+                if is_header && record.is_some() {
+                    self.header = Some((line_number, line));
+                    break;
+                }
+                let result = if is_header {
+                    CtRecordLines::from_header(&line, line_number, self.strictness).map(|header| record = Some(header))
+                } else {
+                    record.get_or_insert_with(CtRecordLines::default).push(&spl, line_number, self.strictness)
+                };
+                if let Err(err) = result {
+                    error.get_or_insert(err);
+                    record.get_or_insert_with(CtRecordLines::default);
+                }
+            }
+
+            let record = record?;
+            if let Some(err) = error {
+                return Some(Err(err));
+            }
+            if record.partners.is_empty() && !strict {
+                continue; // a header without any data lines is skipped
+            }
+            return Some(record.finish(self.strictness));
+        }
+    }
+}
+
+/// Converts an error from a lenient CT parser into the error previously returned by it, that is
+/// an `InvalidStructure` or `Io` error is unwrapped.
+fn box_lenient_ct_error(err: CtParseError) -> Box<dyn Error> {
+    match err {
+        CtParseError::InvalidStructure { source } => Box::new(source),
+        CtParseError::Io { source } => Box::new(source),
+        err => Box::new(err)
+    }
+}
+
+/// Parses CT records leniently, returning the first error encountered. Records are only validated
+/// if `validate` is true.
+fn parse_ct(reader: impl BufRead, validate: bool) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+    let strictness = if validate { CtStrictness::Validated } else { CtStrictness::Lenient };
+    CtRecords::new(reader, strictness).collect::<Result<_, _>>().map_err(box_lenient_ct_error)
 }
 
 /// Reads a connect (CT) format string and returns a vector of SecondaryStructureRecords.
 ///
+/// Returns a `CtParseError` for a line that is neither a header line, a data line, nor blank,
+/// and for a data line whose partner column is not an integer. See
+/// [parse_ct_string_strict](fn.parse_ct_string_strict.html) for stricter checks of each record.
+///
 /// # Examples
 ///
 /// ```rust
@@ -70,28 +340,120 @@ fn parse_ct(reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, Box<d
 /// assert_eq!(ss_obs.paired, vec![8, 5, 0, 0, 2, 0, 0, 1]);
 /// ```
 pub fn parse_ct_string(ct_string: &String) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
-    parse_ct(ct_string.as_bytes())
+    parse_ct(ct_string.as_bytes(), false)
 }
 
 /// Reads a connect (CT) format file and returns a vector of SecondaryStructureRecords.
 pub fn read_ct_file(f: File) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
-    parse_ct(BufReader::new(f))
+    parse_ct(BufReader::new(f), false)
 }
 
-fn write_ct(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
-    let it = ss.sequence.chars().zip(ss.paired.iter());
+/// Returns an iterator over the records of a connect (CT) format buffer, reading one record at a
+/// time so that only the current record is held in memory. Records are delimited by header lines
+/// as in [parse_ct_string](fn.parse_ct_string.html), and the final record is returned at the end
+/// of the buffer.
+///
+/// Each record is [validated](../secondary_structure/struct.SecondaryStructureRecord.html#method.validate),
+/// and a record containing a line that is neither a header nor a data line (bar blank lines) is
+/// returned as a `CtParseError`. An invalid record is returned as an `Err` and iteration continues
+/// with the next record.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let ct_string = ">first\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t1\t2\n>second\n1\tA\t0\t2\t0\t1\n";
+/// let names: Vec<String> = io::iter_ct_file(ct_string.as_bytes()).map(|ss| ss.unwrap().name).collect();
+/// assert_eq!(names, vec!["first", "second"]);
+/// ```
+pub fn iter_ct_file(reader: impl BufRead) -> impl Iterator<Item=Result<SecondaryStructureRecord, Box<dyn Error>>> {
+    CtRecords::new(reader, CtStrictness::Validated).map(|record| record.map_err(box_lenient_ct_error))
+}
 
-    buffer.write_all(format!(">{}\n", ss.name).as_bytes())?;
-    for (i, (c, j)) in it.enumerate() {
-        buffer.write_all(format!("{}\t{}\t{}\t{}\t{}\t{}\n", i + 1, c, i, i + 2, j, i + 1).as_bytes())?;
+/// Reads a connect (CT) format string as [parse_ct_string](fn.parse_ct_string.html) does, but
+/// [validates](../secondary_structure/struct.SecondaryStructureRecord.html#method.validate) each
+/// record, returning an `InconsistentPairing` error for asymmetric or out-of-range pairings.
+pub fn parse_ct_string_validated(ct_string: &String) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+    parse_ct(ct_string.as_bytes(), true)
+}
+
+/// Reads a connect (CT) format file as [read_ct_file](fn.read_ct_file.html) does, but validates
+/// each record, see [parse_ct_string_validated](fn.parse_ct_string_validated.html).
+pub fn read_ct_file_validated(f: File) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+    parse_ct(BufReader::new(f), true)
+}
+
+/// Reads a buffer of connect (CT) format records strictly, see
+/// [parse_ct_string_strict](fn.parse_ct_string_strict.html). A failure to read from the buffer is
+/// returned as `CtParseError::Io`.
+pub fn parse_ct_strict(reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, CtParseError> {
+    CtRecords::new(reader, CtStrictness::Strict).collect()
+}
+
+/// Reads a connect (CT) format string strictly, returning a `CtParseError` for malformed files
+/// that [parse_ct_string](fn.parse_ct_string.html) accepts or reports less precisely:
+///
+/// * a data line without exactly 6 columns, or with a non-integer column,
+/// * an index column that does not increase by one from 1,
+/// * a partner outside `0..=n` or referring to the position itself,
+/// * a numeric header declaring a different number of positions than follow it.
+///
+/// Records are also [validated](../secondary_structure/struct.SecondaryStructureRecord.html#method.validate),
+/// so that asymmetric pairings are rejected as `InvalidStructure`. Non-contiguous numbering, which
+/// the lenient parser accepts, is rejected as non-sequential. A `CtParseError` converts into a
+/// `Box<dyn Error>`, so the `?` operator can be used as with the other parsers.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let skipped = ">skipped\n1\tG\t0\t2\t4\t1\n2\tA\t1\t3\t0\t2\n4\tC\t3\t5\t1\t4\n";
+/// assert_eq!(io::parse_ct_string(&skipped.to_string()).unwrap()[0].sequence, "GAC");
+/// assert!(io::parse_ct_string_strict(&skipped.to_string()).is_err());
+/// ```
+pub fn parse_ct_string_strict(ct_string: &String) -> Result<Vec<SecondaryStructureRecord>, CtParseError> {
+    parse_ct_strict(ct_string.as_bytes())
+}
+
+/// Reads a connect (CT) format file strictly, see [parse_ct_string_strict](fn.parse_ct_string_strict.html).
+pub fn read_ct_file_strict(f: File) -> Result<Vec<SecondaryStructureRecord>, CtParseError> {
+    parse_ct_strict(BufReader::new(f))
+}
+
+fn write_ct(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    match ss.energy {
+        Some(energy) => buffer.write_all(format!("{}\tENERGY = {}\t{}\n", ss.paired.len(), energy, ss.name).as_bytes())?,
+        None => buffer.write_all(format!(">{}\n", ss.name).as_bytes())?
+    }
+    match &ss.numbering {
+        Some(numbering) if numbering.len() == ss.paired.len() => {
+            for (i, c, j) in ss.iter_positions() {
+                let n = numbering[i];
+                let j = j.and_then(|j| numbering.get(j).copied()).unwrap_or(0);
+                buffer.write_all(format!("{}\t{}\t{}\t{}\t{}\t{}\n", n, c, n - 1, n + 1, j, n).as_bytes())?;
+            }
+        }
+        _ => {
+            for (i, c, j) in ss.iter_positions() {
+                let j = j.map_or(0, |j| j + 1);
+                buffer.write_all(format!("{}\t{}\t{}\t{}\t{}\t{}\n", i + 1, c, i, i + 2, j, i + 1).as_bytes())?;
+            }
+        }
     }
     Ok(())
 }
 
 /// Writes a single SecondaryStructureRecord to the specified path in connect (CT) format.
 pub fn write_ct_file(path: &Path, ss: &secondary_structure::SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
-    let append = false;
+    write_ct_file_append(path, ss, false)
+}
 
+/// Writes a single SecondaryStructureRecord to the specified path in connect (CT) format. If
+/// `append` is true the record is appended to the end of an existing file, otherwise the file is
+/// truncated as in [write_ct_file](fn.write_ct_file.html).
+pub fn write_ct_file_append(path: &Path, ss: &SecondaryStructureRecord, append: bool) -> Result<(), Box<dyn Error>> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
@@ -136,6 +498,11 @@ pub fn write_records_to_ct_file<'a, I>(path: &Path, records: I) -> Result<(), Bo
 
 /// Get a connect (CT) format string representation of a secondary structure and sequence.
 ///
+/// The CT string has one row per position of the secondary structure. If the sequence is shorter
+/// than the secondary structure, the missing nucleotides are written as 'N', see
+/// [iter_positions](../secondary_structure/struct.SecondaryStructureRecord.html#method.iter_positions).
+/// When the record has a `numbering`, a partner beyond the end of the numbering is written as 0.
+///
 /// # Examples
 /// 
 /// ```rust
@@ -166,14 +533,152 @@ pub fn get_ct_string(ss: &SecondaryStructureRecord) -> String {
     String::from_utf8(bytes).unwrap()
 }
 
+fn parse_bpseq(reader: impl BufRead) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    let mut filename = None;
+    let mut organism = None;
+    let mut sequence = "".to_string();
+    let mut paired = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("Filename:") {
+            filename = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Organism:") {
+            organism = Some(value.trim().to_string());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let spl = line.split_whitespace().collect::<Vec<&str>>();
+            let index = spl.first().and_then(|index| index.parse::<i64>().ok());
+            let partner = spl.get(2).and_then(|partner| partner.parse::<i64>().ok());
+            match (index, partner) {
+                (Some(index), Some(partner)) if spl.len() == 3 && index == (paired.len() as i64) + 1 => {
+                    sequence.push_str(spl[1]);
+                    paired.push(partner);
+                }
+                (Some(_), _) => return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: format!("Expected a BPSEQ line '{} <base> <partner>', found '{}'.", paired.len() + 1, line)
+                })),
+                // other header lines, e.g. 'Accession Number:' or 'Citation and related information:'
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(j) = paired.iter().find(|j| **j < 0 || **j > paired.len() as i64) {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: format!("BPSEQ partner {} is outside the range 0..={}.", j, paired.len())
+        }));
+    }
+
+    Ok(SecondaryStructureRecord {
+        name: filename.or(organism).unwrap_or_default(),
+        sequence,
+        paired,
+        energy: None,
+        annotation: None,
+        numbering: None,
+    })
+}
+
+/// Reads a BPSEQ format string and returns the SecondaryStructureRecord. Each data line has the
+/// form `<index> <base> <partner>` with 1-based indices and a partner of 0 for unpaired sites.
+/// The name is taken from the `Filename:` header line, or otherwise the `Organism:` line. Other
+/// header lines and blank lines are ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let bpseq_string =
+/// "Filename: example.bpseq
+/// Organism: Example organism
+/// 1 G 6
+/// 2 C 5
+/// 3 A 0
+/// 4 A 0
+/// 5 G 2
+/// 6 C 1
+/// ";
+///
+/// let ss = io::parse_bpseq_string(bpseq_string).unwrap();
+/// assert_eq!(ss.name, "example.bpseq");
+/// assert_eq!(ss.sequence, "GCAAGC");
+/// assert_eq!(ss.paired, vec![6, 5, 0, 0, 2, 1]);
+/// ```
+pub fn parse_bpseq_string(s: &str) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    parse_bpseq(s.as_bytes())
+}
+
+/// Reads a BPSEQ format file and returns the SecondaryStructureRecord.
+pub fn read_bpseq_file(f: File) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    parse_bpseq(BufReader::new(f))
+}
+
+fn write_bpseq(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    if !ss.name.is_empty() {
+        buffer.write_all(format!("Filename: {}\n", ss.name).as_bytes())?;
+    }
+    for (i, c, j) in ss.iter_positions() {
+        buffer.write_all(format!("{} {} {}\n", i + 1, c, j.map_or(0, |j| j + 1)).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Get a BPSEQ format string representation of a secondary structure and sequence. As for
+/// [get_ct_string](fn.get_ct_string.html), there is one row per position of the secondary
+/// structure.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::secondary_structure;
+/// use crate::rna_secondary_structure::io;
+/// let mut ss : secondary_structure::SecondaryStructureRecord = "(..)".parse().unwrap();
+/// ss.set_sequence("GAAC".to_string());
+/// ss.name = "example".to_string();
+/// assert_eq!(io::get_bpseq_string(&ss), "Filename: example\n1 G 4\n2 A 0\n3 A 0\n4 C 1\n");
+/// ```
+pub fn get_bpseq_string(ss: &SecondaryStructureRecord) -> String {
+    let mut bytes = Vec::new();
+    write_bpseq(&mut bytes, ss).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Writes a single SecondaryStructureRecord to the specified path in BPSEQ format.
+pub fn write_bpseq_file(path: &Path, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    let append = false;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    write_bpseq(&mut file, ss)?;
+
+    Ok(())
+}
+
 /// Write the name, sequence, and secondary structure conformation (in dot bracket notation) of a
-/// SecondaryStructureRecord to a buffer.
+/// SecondaryStructureRecord to a buffer. If the record has an energy it is written after the dot
+/// bracket string in the ViennaRNA style, e.g. `((...)) (-1.20)`.
+///
+/// Returns `InsufficientBracketTypes`, without writing anything, if the secondary structure is too
+/// pseudoknotted to be represented in dot bracket notation, see
+/// [is_pseudoknot_safe](../secondary_structure/fn.is_pseudoknot_safe.html). Such structures can be
+/// written losslessly in CT format.
 pub fn write_dbn(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    if !is_pseudoknot_safe(&ss.paired) {
+        return Err(Box::new(StructureParseError::InsufficientBracketTypes));
+    }
     buffer.write_all(format!(">{}", &ss.name).as_bytes())?;
     buffer.write_all(b"\n")?;
     buffer.write_all(&ss.sequence.as_bytes())?;
     buffer.write_all(b"\n")?;
     buffer.write_all(get_dot_bracket_string(&ss.paired)?.as_bytes())?;
+    if let Some(energy) = ss.energy {
+        buffer.write_all(format!(" ({:.2})", energy).as_bytes())?;
+    }
     buffer.write_all(b"\n")?;
     Ok(())
 }
@@ -181,8 +686,13 @@ pub fn write_dbn(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> R
 /// Write the name, sequence, and secondary structure conformation (in dot bracket notation) of a
 /// SecondaryStructureRecord to the specified file path.
 pub fn write_dbn_file(path: &Path, ss: &secondary_structure::SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
-    let append = false;
+    write_dbn_file_append(path, ss, false)
+}
 
+/// Write a SecondaryStructureRecord to the specified file path in dot bracket notation. If
+/// `append` is true the record is appended to the end of an existing file, otherwise the file is
+/// truncated as in [write_dbn_file](fn.write_dbn_file.html).
+pub fn write_dbn_file_append(path: &Path, ss: &SecondaryStructureRecord, append: bool) -> Result<(), Box<dyn Error>> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
@@ -252,10 +762,14 @@ fn parse_dbn(reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, Box<
             sequence = line.to_string();
             m = 2;
         } else if m == 2 {
+            let (structure, energy) = split_vienna_structure_line(line)?;
             ls.push(SecondaryStructureRecord {
                 name: name.clone(),
                 sequence: sequence.clone(),
-                paired: from_dotbracketstring(line)?,
+                paired: from_dotbracketstring(structure)?,
+                energy,
+                annotation: None,
+                numbering: None,
             });
             m = 3;
         }
@@ -270,6 +784,8 @@ pub fn read_dbn_file(f: File) -> Result<Vec<SecondaryStructureRecord>, Box<dyn E
 }
 
 /// Reads a dot bracket notation (DBN) string and returns a vector of SecondaryStructureRecords.
+/// A dot bracket string may be followed by a free energy in parentheses, e.g. `((...)) (-1.20)`,
+/// which is stored in the record's `energy` field, as written by [write_dbn](fn.write_dbn.html).
 ///
 /// # Examples
 ///
@@ -286,19 +802,691 @@ pub fn read_dbn_file(f: File) -> Result<Vec<SecondaryStructureRecord>, Box<dyn E
 /// ((..<<.)).((>>..))
 /// ";
 ///
-/// let non_pseudoknotted = &io::parse_dbn_string(&dbn_string.to_string()).unwrap()[0];
+/// let non_pseudoknotted = &io::parse_dbn_string(dbn_string).unwrap()[0];
 /// assert_eq!(non_pseudoknotted.name, "non-pseudoknotted");
 /// assert_eq!(non_pseudoknotted.sequence, "AUUCCCGAAGGU");
 /// assert_eq!(non_pseudoknotted.paired, vec![12, 11, 8, 7, 0, 0, 4, 3, 0, 0, 2, 1]);
 ///
-/// let pseudoknotted = &io::parse_dbn_string(&dbn_string.to_string()).unwrap()[1];
+/// let pseudoknotted = &io::parse_dbn_string(dbn_string).unwrap()[1];
 /// assert_eq!(pseudoknotted.name, "pseudoknotted");
 /// assert_eq!(pseudoknotted.sequence, "GACCGCAUCUUAGCGGUA");
 /// assert_eq!(pseudoknotted.paired, vec![9, 8, 0, 0, 14, 13, 0, 2, 1, 0, 18, 17, 6, 5, 0, 0, 12, 11]);
 /// ```
-pub fn parse_dbn_string(dbn_string: &String) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+pub fn parse_dbn_string(dbn_string: &str) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
     parse_dbn(dbn_string.as_bytes())
 }
 
+/// Reads a dot bracket notation (DBN) buffer in which each sequence may be followed by several
+/// structures, e.g. an MFE structure and its suboptimals: a `>name` line, the sequence, and one or
+/// more dot bracket strings, each the same length as the sequence, up to a blank line or the next
+/// `>name` line. Returns, for each sequence, a SecondaryStructureRecord for the first structure
+/// together with the paired sites vectors of the alternative structures that follow it.
+///
+/// As in [parse_dbn_string](fn.parse_dbn_string.html), a structure may be followed by a free
+/// energy in parentheses; the energy of the first structure is stored in the record's `energy`
+/// field, and those of the alternatives are discarded.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let dbn = ">hairpin
+/// GGGAAACCC
+/// (((...))) (-6.00)
+/// .((...)).
+/// ";
+///
+/// let records = io::parse_dbn_multi(dbn.as_bytes()).unwrap();
+/// let (ss, alternatives) = &records[0];
+/// assert_eq!(ss.paired, vec![9, 8, 7, 0, 0, 0, 3, 2, 1]);
+/// assert_eq!(ss.energy, Some(-6.0));
+/// assert_eq!(alternatives, &vec![vec![0, 8, 7, 0, 0, 0, 3, 2, 0]]);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_dbn_multi(reader: impl BufRead) -> Result<Vec<(SecondaryStructureRecord, Vec<Vec<i64>>)>, Box<dyn Error>> {
+    let mut ls: Vec<(SecondaryStructureRecord, Vec<Vec<i64>>)> = Vec::new();
+    let mut sequence = "".to_string();
+    let mut name = "".to_string();
+    let mut m = 0;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            if m == 0 || m == 3 {
+                m = 0;
+            } else if m == 1 {
+                return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: "Expected a line containing a sequence. Found a blank line.".to_string()
+                }));
+            } else if m == 2 {
+                return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: "Expected a line containing a dot bracket string. Found a blank line.".to_string()
+                }));
+            }
+        } else if m == 0 || (m == 3 && line.starts_with('>')) {
+            name = line.trim_start_matches('>').to_string();
+            m = 1;
+        } else if m == 1 {
+            sequence = line.to_string();
+            m = 2;
+        } else {
+            let (structure, energy) = split_vienna_structure_line(line)?;
+            if structure.chars().count() != sequence.chars().count() {
+                return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: format!("Expected a dot bracket string of length {}. Found '{}'.", sequence.chars().count(), structure)
+                }));
+            }
+            let paired = from_dotbracketstring(structure)?;
+            if m == 2 {
+                let ss = SecondaryStructureRecord {
+                    name: name.clone(),
+                    sequence: sequence.clone(),
+                    paired,
+                    energy,
+                    annotation: None,
+                    numbering: None,
+                };
+                ls.push((ss, Vec::new()));
+                m = 3;
+            } else {
+                ls.last_mut().unwrap().1.push(paired);
+            }
+        }
+    }
+
+    if m == 1 || m == 2 {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: "Expected a line containing a dot bracket string. Found the end of the input.".to_string()
+        }));
+    }
+    Ok(ls)
+}
+
+/// The line width at which [write_fasta_structure](fn.write_fasta_structure.html) wraps sequences.
+pub const FASTA_LINE_WIDTH: usize = 80;
+
+/// Returns true if the line could be a dot bracket string for a sequence of length `len`: it must
+/// have length `len`, consist only of `.` and bracket characters, and not consist entirely of
+/// letters (which would be indistinguishable from a sequence line).
+fn is_structure_line(line: &str, len: usize) -> bool {
+    line.chars().count() == len
+        && line.chars().all(|c| c == '.' || secondary_structure::LEFT_BRACKETS.contains(c) || secondary_structure::RIGHT_BRACKETS.contains(c))
+        && !line.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Reads a buffer of FASTA-like records (a `>name` line, one or more sequence lines, and a dot
+/// bracket string on a single line) and returns a vector of SecondaryStructureRecords. Unlike
+/// [parse_dbn_string](fn.parse_dbn_string.html), sequences may be wrapped over several lines:
+/// sequence lines are accumulated until a line that is a dot bracket string of the same length as
+/// the sequence is found. Blank lines are ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let fasta = ">wrapped
+/// GGGAAA
+/// CCC
+/// (((...)))
+/// ";
+///
+/// let records = io::parse_fasta_structure(fasta.as_bytes()).unwrap();
+/// assert_eq!(records[0].name, "wrapped");
+/// assert_eq!(records[0].sequence, "GGGAAACCC");
+/// assert_eq!(records[0].paired, vec![9, 8, 7, 0, 0, 0, 3, 2, 1]);
+/// ```
+pub fn parse_fasta_structure(reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+    let mut ls: Vec<SecondaryStructureRecord> = Vec::new();
+    let mut name: Option<String> = None;
+    let mut sequence = "".to_string();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix('>') {
+            if name.is_some() {
+                return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: format!("Expected a line containing a dot bracket string of length {}. Found '{}'.", sequence.chars().count(), line)
+                }));
+            }
+            name = Some(value.to_string());
+        } else if name.is_none() {
+            return Err(Box::new(StructureParseError::ExpectedLine {
+                msg: format!("Expected a line starting with '>'. Found '{}'.", line)
+            }));
+        } else if !sequence.is_empty() && is_structure_line(line, sequence.chars().count()) {
+            ls.push(SecondaryStructureRecord {
+                name: name.take().unwrap(),
+                sequence: std::mem::take(&mut sequence),
+                paired: from_dotbracketstring(line)?,
+                energy: None,
+                annotation: None,
+                numbering: None,
+            });
+        } else {
+            sequence.push_str(line);
+        }
+    }
+
+    if name.is_some() {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: format!("Expected a line containing a dot bracket string of length {}. Found the end of the input.", sequence.chars().count())
+        }));
+    }
+    Ok(ls)
+}
+
+/// Writes a SecondaryStructureRecord to a buffer as a FASTA-like record readable by
+/// [parse_fasta_structure](fn.parse_fasta_structure.html): a `>name` line, the sequence wrapped at
+/// [FASTA_LINE_WIDTH](constant.FASTA_LINE_WIDTH.html) columns, and the dot bracket string. The dot
+/// bracket string is not wrapped, as the reader expects it on a single line.
+pub fn write_fasta_structure(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    buffer.write_all(format!(">{}\n", ss.name).as_bytes())?;
+    let sequence = ss.sequence.chars().collect::<Vec<char>>();
+    for chunk in sequence.chunks(FASTA_LINE_WIDTH) {
+        buffer.write_all(chunk.iter().collect::<String>().as_bytes())?;
+        buffer.write_all(b"\n")?;
+    }
+    buffer.write_all(get_dot_bracket_string(&ss.paired)?.as_bytes())?;
+    buffer.write_all(b"\n")?;
+    Ok(())
+}
 
+/// Splits an RNAfold structure line of the form `<dot bracket string> (<energy>)`, where the
+/// parenthesised energy may be space-padded and is optional, into its structure and energy.
+fn split_vienna_structure_line(line: &str) -> Result<(&str, Option<f64>), Box<dyn Error>> {
+    let (structure, rest) = match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], line[pos..].trim()),
+        None => (line, "")
+    };
+    if rest.is_empty() {
+        return Ok((structure, None));
+    }
+    match rest.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+        Some(energy) => Ok((structure, Some(energy.trim().parse::<f64>()?))),
+        None => Err(Box::new(StructureParseError::ExpectedLine {
+            msg: format!("Expected a free energy of the form '(-1.23)' after the dot bracket string. Found '{}'.", rest)
+        }))
+    }
+}
 
+/// Reads the output of ViennaRNA's `RNAfold` (an optional `>name` line, the sequence, and the
+/// dot bracket string followed by the free energy in parentheses, e.g. `((...)) (-1.20)`), and
+/// returns each SecondaryStructureRecord together with its free energy, which is also stored in
+/// the record's `energy` field. Structure lines without an energy give `None`.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let rnafold_output = ">example
+/// GGGGAAACCCC
+/// ((((...)))) ( -5.40)
+/// ";
+///
+/// let records = io::parse_vienna_string(rnafold_output).unwrap();
+/// assert_eq!(records[0].0.name, "example");
+/// assert_eq!(records[0].0.paired, vec![11, 10, 9, 8, 0, 0, 0, 4, 3, 2, 1]);
+/// assert_eq!(records[0].1, Some(-5.4));
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_vienna_string(s: &str) -> Result<Vec<(SecondaryStructureRecord, Option<f64>)>, Box<dyn Error>> {
+    let mut ls = Vec::new();
+    let mut name = "".to_string();
+    let mut sequence: Option<String> = None;
+    for line in s.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix('>') {
+            name = value.trim().to_string();
+        } else if let Some(sequence) = sequence.take() {
+            let (structure, energy) = split_vienna_structure_line(line)?;
+            let ss = SecondaryStructureRecord {
+                name: std::mem::take(&mut name),
+                sequence,
+                paired: from_dotbracketstring(structure)?,
+                energy,
+                annotation: None,
+                numbering: None,
+            };
+            ls.push((ss, energy));
+        } else {
+            sequence = Some(line.to_string());
+        }
+    }
+
+    if sequence.is_some() {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: "Expected a line containing a dot bracket string. Found the end of the input.".to_string()
+        }));
+    }
+    Ok(ls)
+}
+
+/// Reads a ViennaRNA dot plot PostScript file (`dot.ps`, as written by `RNAfold -p`) and returns
+/// the sequence, from the `/sequence` block, and the base-pair probabilities, from the `i j sqrt(p)
+/// ubox` lines, as 0-based `(i, j, p)` triples in order of appearance. The square roots stored in
+/// the file are squared back to probabilities. The `lbox` lines, which give the MFE structure, are
+/// ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let dotplot = "%!PS-Adobe-3.0 EPSF-3.0
+/// /sequence { (\\
+/// GGGAAACCC\\
+/// ) } def
+/// %start of base pair probability data
+/// 1 9 0.9 ubox
+/// 1 9 0.95 lbox
+/// showpage
+/// ";
+///
+/// let (sequence, probabilities) = io::parse_dotplot_ps(dotplot.as_bytes()).unwrap();
+/// assert_eq!(sequence, "GGGAAACCC");
+/// assert_eq!(probabilities.len(), 1);
+/// assert_eq!((probabilities[0].0, probabilities[0].1), (0, 8));
+/// assert!((probabilities[0].2 - 0.81).abs() < 1e-12);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_dotplot_ps(reader: impl BufRead) -> Result<(String, Vec<(usize, usize, f64)>), Box<dyn Error>> {
+    let mut sequence: Option<String> = None;
+    let mut in_sequence = false;
+    let mut probabilities = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("/sequence") {
+            in_sequence = true;
+            sequence = Some("".to_string());
+            let rest = rest.trim_start().trim_start_matches('{').trim_start();
+            if let Some(rest) = rest.strip_prefix('(') {
+                sequence.as_mut().unwrap().push_str(rest.split(')').next().unwrap().trim_end_matches('\\'));
+                in_sequence = !rest.contains(')');
+            }
+        } else if in_sequence {
+            let part = line.split(')').next().unwrap();
+            sequence.as_mut().unwrap().push_str(part.trim_end_matches('\\').trim());
+            in_sequence = !line.contains(')');
+        } else {
+            let spl = line.split_whitespace().collect::<Vec<&str>>();
+            if spl.len() == 4 && spl[3] == "ubox" && spl[0].parse::<usize>().is_ok() {
+                let i = spl[0].parse::<usize>()?;
+                let j = spl[1].parse::<usize>()?;
+                let sqrt_p = spl[2].parse::<f64>()?;
+                if i == 0 || j == 0 {
+                    return Err(Box::new(StructureParseError::ExpectedLine {
+                        msg: format!("Expected 1-based positions in the line '{}'.", line)
+                    }));
+                }
+                probabilities.push((i - 1, j - 1, sqrt_p * sqrt_p));
+            }
+        }
+    }
+
+    match sequence {
+        Some(sequence) => Ok((sequence, probabilities)),
+        None => Err(Box::new(StructureParseError::ExpectedLine {
+            msg: "Expected a '/sequence' block. Found the end of the input.".to_string()
+        }))
+    }
+}
+
+/// Serialises a SecondaryStructureRecord to a JSON string, with the fields of the record as
+/// object members. Requires the `serde` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+/// use crate::rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+/// let ss: SecondaryStructureRecord = "(..)".parse().unwrap();
+/// let json = io::to_json(&ss);
+/// assert!(json.contains("\"paired\":[4,0,0,1]"));
+/// assert_eq!(io::from_json(&json).unwrap().paired, ss.paired);
+/// ```
+#[cfg(feature = "serde")]
+pub fn to_json(ss: &SecondaryStructureRecord) -> String {
+    serde_json::to_string(ss).expect("a SecondaryStructureRecord is always serialisable")
+}
+
+/// Deserialises a SecondaryStructureRecord from a JSON string written by
+/// [to_json](fn.to_json.html). Requires the `serde` feature.
+#[cfg(feature = "serde")]
+pub fn from_json(json: &str) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    Ok(serde_json::from_str(json)?)
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Reads a buffer in bpRNA structure type (`.st`) format and returns the SecondaryStructureRecord.
+///
+/// The name is taken from the `#Name:` header and the remaining `#` comment lines are ignored.
+/// The first three lines that follow are the sequence, the dot bracket string (parsed with
+/// [from_wuss_string](../secondary_structure/fn.from_wuss_string.html), so that `[]`, `{}`, `<>`
+/// and letter pseudoknot brackets are accepted), and the per-position structure type annotation,
+/// which, if present, is stored in the record's `annotation` field. Any further lines (e.g. the
+/// NCBP annotation and the list of structural features) are ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let st_string =
+/// "#Name: bpRNA_example
+/// #Length: 12
+/// #PageNumber: 1
+/// GGGAAACCCAAA
+/// (((...)))...
+/// SSSHHHSSSEEE
+/// NNNNNNNNNNNN
+/// S1 1..3 \"GGG\" 7..9 \"CCC\"
+/// ";
+///
+/// let ss = io::parse_bprna_st(st_string.as_bytes()).unwrap();
+/// assert_eq!(ss.name, "bpRNA_example");
+/// assert_eq!(ss.sequence, "GGGAAACCCAAA");
+/// assert_eq!(ss.paired, vec![9, 8, 7, 0, 0, 0, 3, 2, 1, 0, 0, 0]);
+/// assert_eq!(ss.annotation, Some("SSSHHHSSSEEE".to_string()));
+/// ```
+pub fn parse_bprna_st(reader: impl BufRead) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    let mut name = "".to_string();
+    let mut lines = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("#Name:") {
+            name = value.trim().to_string();
+        } else if !line.starts_with('#') && !line.is_empty() {
+            lines.push(line.to_string());
+            if lines.len() == 3 {
+                break;
+            }
+        }
+    }
+
+    if lines.len() < 2 {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: "Expected a line containing a sequence followed by a line containing a dot bracket string.".to_string()
+        }));
+    }
+    let mut lines = lines.into_iter();
+    let sequence = lines.next().unwrap();
+    let paired = from_wuss_string(&lines.next().unwrap())?;
+    if sequence.chars().count() != paired.len() {
+        return Err(Box::new(StructureParseError::ExpectedLine {
+            msg: format!("Expected a dot bracket string of length {}, found length {}.", sequence.chars().count(), paired.len())
+        }));
+    }
+
+    Ok(SecondaryStructureRecord {
+        name,
+        sequence,
+        paired,
+        energy: None,
+        annotation: lines.next(),
+        numbering: None,
+    })
+}
+
+/// Reads a bpRNA structure type (`.st`) format string and returns the SecondaryStructureRecord,
+/// see [parse_bprna_st](fn.parse_bprna_st.html).
+pub fn parse_st_string(s: &str) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    parse_bprna_st(s.as_bytes())
+}
+
+/// Reads a bpRNA structure type (`.st`) format file and returns the SecondaryStructureRecord,
+/// see [parse_bprna_st](fn.parse_bprna_st.html).
+pub fn read_st_file(f: File) -> Result<SecondaryStructureRecord, Box<dyn Error>> {
+    parse_bprna_st(BufReader::new(f))
+}
+
+/// Returns the residue number at the end of a DSSR nucleotide identifier, e.g. 72 for `A.C72` or
+/// 55 for `A.PSU55^A` (ignoring the insertion code).
+fn parse_dssr_residue_number(nt: &str) -> Option<i64> {
+    let nt = nt.split('^').next().unwrap_or(nt);
+    let start = nt.rfind(|c: char| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+    let start = if start > 0 && nt[..start].ends_with('-') { start - 1 } else { start };
+    nt[start..].parse::<i64>().ok()
+}
+
+/// A base-pair parsed from DSSR output as `(i, j, lw)`, see [parse_dssr_pairs](fn.parse_dssr_pairs.html).
+pub type DssrPair = (usize, usize, String);
+
+/// Reads the base pair table (the `List of N base pairs` section) from DSSR output and returns
+/// each base-pair as `(i, j, lw)`, where `i` and `j` are 0-based positions derived from the
+/// residue numbers of the two nucleotides (residue number minus one) and `lw` is the
+/// Leontis-Westhof classification, e.g. `cWW` for a cis Watson-Crick/Watson-Crick pair.
+/// Non-canonical pairs are included, use
+/// [get_canonical_paired_from_dssr](fn.get_canonical_paired_from_dssr.html) to keep only the
+/// canonical subset.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let dssr_output =
+/// "List of 3 base pairs
+///      nt1            nt2            bp  name        Saenger   LW   DSSR
+///    1 A.G1           A.C12          G-C WC          19-XIX    cWW  cW-W
+///    2 A.G2           A.U11          G-U Wobble      28-XXVIII cWW  cW-W
+///    3 A.U4           A.A9           U-A rHoogsteen  24-XXIV   tWH  tW-M
+/// ****************************************************************************
+/// ";
+///
+/// let pairs = io::parse_dssr_pairs(dssr_output.as_bytes()).unwrap();
+/// assert_eq!(pairs, vec![(0, 11, "cWW".to_string()), (1, 10, "cWW".to_string()), (3, 8, "tWH".to_string())]);
+/// ```
+pub fn parse_dssr_pairs(reader: impl BufRead) -> Result<Vec<DssrPair>, Box<dyn Error>> {
+    let mut pairs = Vec::new();
+    let mut in_table = false;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.starts_with("List of") && line.contains("base pair") {
+            in_table = true;
+        } else if in_table {
+            let spl = line.split_whitespace().collect::<Vec<&str>>();
+            if spl.first() == Some(&"nt1") {
+                continue;
+            }
+            if spl.len() < 6 || spl[0].parse::<usize>().is_err() {
+                break;
+            }
+            let (i, j) = match (parse_dssr_residue_number(spl[1]), parse_dssr_residue_number(spl[2])) {
+                (Some(i), Some(j)) if i >= 1 && j >= 1 => ((i - 1) as usize, (j - 1) as usize),
+                _ => return Err(Box::new(StructureParseError::ExpectedLine {
+                    msg: format!("Expected positive residue numbers in DSSR base pair line: '{}'", line)
+                }))
+            };
+            pairs.push((i, j, spl[spl.len() - 2].to_string()));
+        }
+    }
+    Ok(pairs)
+}
+
+/// Returns a paired sites vector of the specified sequence's length containing only the DSSR
+/// base-pairs (see [parse_dssr_pairs](fn.parse_dssr_pairs.html)) that are cis Watson-Crick/Watson-Crick
+/// (`cWW`) and whose nucleotides form a canonical pair (A-U, G-C, or G-U). Pairs that are out of
+/// range or involve an already paired position are skipped, so the first listed pair is kept.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+/// let pairs = vec![(0, 11, "cWW".to_string()), (1, 10, "cWW".to_string()), (3, 8, "tWH".to_string()), (4, 7, "cWW".to_string())];
+/// let paired = io::get_canonical_paired_from_dssr("GGAUGAAAAAUC", &pairs);
+/// assert_eq!(paired, vec![12, 11, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1]);
+/// ```
+pub fn get_canonical_paired_from_dssr(sequence: &str, pairs: &[DssrPair]) -> Vec<i64> {
+    let sequence = sequence.chars().collect::<Vec<char>>();
+    let mut paired = vec![0; sequence.len()];
+    for (i, j, lw) in pairs.iter() {
+        let (i, j) = (*i, *j);
+        if lw == "cWW" && i != j && i < paired.len() && j < paired.len() && paired[i] == 0 && paired[j] == 0 && is_canonical_pair(sequence[i], sequence[j]) {
+            paired[i] = (j as i64) + 1;
+            paired[j] = (i as i64) + 1;
+        }
+    }
+    paired
+}
+
+/// Write a SecondaryStructureRecord to a buffer as a forna/fornac-compatible node-link JSON graph.
+///
+/// Each nucleotide becomes a node with its `name` (the base), 0-based `index`, and 1-based `num`.
+/// Links reference nodes by index and carry a `link_type` of either `"backbone"` (between
+/// consecutive nucleotides) or `"basepair"` (listed once per base-pair, 5' partner as `source`).
+/// Positions beyond the end of the sequence are written as `N`.
+pub fn write_forna_json(buffer: &mut dyn io::Write, ss: &SecondaryStructureRecord) -> Result<(), Box<dyn Error>> {
+    let mut bases = ss.sequence.chars();
+    let nodes = (0..ss.paired.len()).map(|i| {
+        let base = bases.next().unwrap_or('N').to_string();
+        format!("{{\"name\":\"{}\",\"num\":{},\"index\":{},\"node_type\":\"nucleotide\"}}", json_escape(&base), i + 1, i)
+    }).collect::<Vec<String>>();
+
+    let mut links = Vec::new();
+    for i in 1..ss.paired.len() {
+        links.push(format!("{{\"source\":{},\"target\":{},\"link_type\":\"backbone\"}}", i - 1, i));
+    }
+    for (i, j) in ss.paired.iter().enumerate() {
+        if *j > (i as i64) + 1 {
+            links.push(format!("{{\"source\":{},\"target\":{},\"link_type\":\"basepair\"}}", i, j - 1));
+        }
+    }
+
+    buffer.write_all(format!("{{\"name\":\"{}\",\"nodes\":[{}],\"links\":[{}]}}\n", json_escape(&ss.name), nodes.join(","), links.join(",")).as_bytes())?;
+    Ok(())
+}
+
+/// Get a forna/fornac-compatible node-link JSON string representation of a secondary structure
+/// and sequence, see [write_forna_json](fn.write_forna_json.html).
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::secondary_structure;
+/// use crate::rna_secondary_structure::io;
+/// let mut ss : secondary_structure::SecondaryStructureRecord = "(.)".parse().unwrap();
+/// ss.set_sequence("GAC".to_string());
+/// ss.name = "example".to_string();
+///
+/// let json_expected = concat!(
+///     "{\"name\":\"example\",\"nodes\":[",
+///     "{\"name\":\"G\",\"num\":1,\"index\":0,\"node_type\":\"nucleotide\"},",
+///     "{\"name\":\"A\",\"num\":2,\"index\":1,\"node_type\":\"nucleotide\"},",
+///     "{\"name\":\"C\",\"num\":3,\"index\":2,\"node_type\":\"nucleotide\"}],\"links\":[",
+///     "{\"source\":0,\"target\":1,\"link_type\":\"backbone\"},",
+///     "{\"source\":1,\"target\":2,\"link_type\":\"backbone\"},",
+///     "{\"source\":0,\"target\":2,\"link_type\":\"basepair\"}]}\n");
+/// assert_eq!(io::get_forna_json_string(&ss), json_expected);
+/// ```
+pub fn get_forna_json_string(ss: &SecondaryStructureRecord) -> String {
+    let mut bytes = Vec::new();
+    write_forna_json(&mut bytes, ss).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Returns true if the line is a CT data line: at least 6 columns, the first and the sixth of
+/// which are integers.
+fn is_ct_data_line(line: &str) -> bool {
+    is_ct_data_columns(&line.split_whitespace().collect::<Vec<&str>>())
+}
+
+/// Returns true if the columns of a line are those of a CT data line, see
+/// [is_ct_data_line](fn.is_ct_data_line.html).
+fn is_ct_data_columns(spl: &[&str]) -> bool {
+    spl.len() >= 6 && spl[0].parse::<i64>().is_ok() && spl[5].parse::<i64>().is_ok()
+}
+
+/// Reads a buffer of secondary structures in any of the following formats, detected from the
+/// first non-blank line (and the line after a `>name` line), and returns a vector of
+/// SecondaryStructureRecords:
+///
+/// * Rfam Stockholm, starting with `# STOCKHOLM`, see
+///   [parse_rfam_stockholm](../read_rfam/fn.parse_rfam_stockholm.html).
+/// * bpRNA `.st`, starting with a `#` comment such as `#Name:`, see
+///   [parse_bprna_st](fn.parse_bprna_st.html).
+/// * Connect (CT), starting with a numeric header line or a CT data line, or with a `>name` line
+///   followed by a CT data line, see [parse_ct_string](fn.parse_ct_string.html).
+/// * Otherwise, dot bracket notation (DBN) or FASTA-like records with a dot bracket string, see
+///   [parse_fasta_structure](fn.parse_fasta_structure.html).
+///
+/// An empty buffer returns an empty vector.
+///
+/// # Examples
+///
+/// ```rust
+/// use crate::rna_secondary_structure::io;
+///
+/// let dbn = ">hairpin\nGGGAAACCC\n(((...)))\n";
+/// assert_eq!(io::read_any(dbn.as_bytes()).unwrap()[0].paired, vec![9, 8, 7, 0, 0, 0, 3, 2, 1]);
+///
+/// let ct = ">hairpin\n1\tG\t0\t2\t3\t1\n2\tA\t1\t3\t0\t2\n3\tC\t2\t4\t1\t3\n";
+/// assert_eq!(io::read_any(ct.as_bytes()).unwrap()[0].paired, vec![3, 0, 1]);
+/// ```
+pub fn read_any(mut reader: impl BufRead) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
+    let mut peeked = String::new();
+    let mut first: Option<String> = None;
+    let mut second: Option<String> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        peeked.push_str(&line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if first.is_none() {
+            first = Some(line.to_string());
+            if !line.starts_with('>') {
+                break;
+            }
+        } else {
+            second = Some(line.to_string());
+            break;
+        }
+    }
+
+    let first = match first {
+        Some(first) => first,
+        None => return Ok(Vec::new())
+    };
+    let reader = peeked.as_bytes().chain(reader);
+    if first.starts_with("# STOCKHOLM") {
+        parse_rfam_stockholm(reader)
+    } else if first.starts_with('#') {
+        Ok(vec![parse_bprna_st(reader)?])
+    } else if first.split_whitespace().next().is_some_and(|token| token.parse::<i64>().is_ok())
+        || (first.starts_with('>') && second.is_some_and(|second| is_ct_data_line(&second))) {
+        parse_ct(reader, false)
+    } else {
+        parse_fasta_structure(reader)
+    }
+}
@@ -0,0 +1,63 @@
+//! A module for computing the geometry of secondary structure drawings.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::secondary_structure::{assign_pages, PairedSites};
+
+/// A base-pair drawn as a semicircular arc in an arc diagram, as computed by
+/// [arc_diagram](fn.arc_diagram.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arc {
+    /// The 0-based 5' position of the base-pair.
+    pub i: usize,
+
+    /// The 0-based 3' position of the base-pair.
+    pub j: usize,
+
+    /// The radius of the arc, half the span `(j - i) / 2`.
+    pub radius: f64,
+
+    /// The x-coordinate of the centre of the arc, midway between `i` and `j`.
+    pub center_x: f64,
+
+    /// The 0-based page of the base-pair, see
+    /// [pseudoknot_order](../secondary_structure/fn.pseudoknot_order.html). Arcs on the same page
+    /// never cross.
+    pub page: usize,
+}
+
+/// Returns the arcs of an arc diagram of the given secondary structure, ordered by 5' position.
+///
+/// The backbone is drawn along the x-axis, with the nucleotide at 0-based position k at (k, 0).
+/// Each base-pair `(i, j)` is a semicircle above the backbone through (i, 0) and (j, 0), centred
+/// at (center_x, 0). Crossing base-pairs are on different pages, so that they can be coloured or
+/// offset differently, e.g. by drawing the arcs on odd pages below the backbone.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::layout::arc_diagram;
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// let paired = from_dotbracketstring("((..[[..))..]]").unwrap();
+/// let arcs = arc_diagram(&paired);
+/// assert_eq!(arcs.len(), 4);
+/// assert_eq!((arcs[0].i, arcs[0].j, arcs[0].radius, arcs[0].center_x, arcs[0].page), (0, 9, 4.5, 4.5, 0));
+/// assert_eq!((arcs[2].i, arcs[2].j, arcs[2].radius, arcs[2].center_x, arcs[2].page), (4, 13, 4.5, 8.5, 1));
+/// ```
+pub fn arc_diagram(paired: &dyn PairedSites) -> Vec<Arc> {
+    let mut arcs: Vec<Arc> = Vec::new();
+    for (page, pairs) in assign_pages(paired).iter().enumerate() {
+        for (i, j) in pairs.iter() {
+            arcs.push(Arc {
+                i: *i,
+                j: *j,
+                radius: (j - i) as f64 / 2.0,
+                center_x: (i + j) as f64 / 2.0,
+                page,
+            });
+        }
+    }
+    arcs.sort_by_key(|arc| arc.i);
+    arcs
+}
@@ -6,4 +6,7 @@ pub mod secondary_structure;
 pub mod io;
 pub mod distance_metrics;
 pub mod read_rfam;
-pub mod combinatorics;
\ No newline at end of file
+pub mod combinatorics;
+pub mod descriptors;
+pub mod folding;
+pub mod layout;
\ No newline at end of file
@@ -2,13 +2,18 @@
 //!
 //! Example file: [ftp://ftp.ebi.ac.uk/pub/databases/Rfam/14.2/Rfam.seed.gz](ftp://ftp.ebi.ac.uk/pub/databases/Rfam/14.2/Rfam.seed.gz).
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io;
+use std::io::{BufRead, BufReader, Lines};
 
 use flate2::read::GzDecoder;
+use thiserror::Error;
 
-use crate::secondary_structure::SecondaryStructureRecord;
+use crate::distance_metrics::SecondaryStructureMetricError;
+use crate::io::write_dbn;
+use crate::secondary_structure::{base_pairs, from_wuss_string, get_dot_bracket_string, is_canonical_pair, normalise_nucleotide, PairedSites, SecondaryStructureRecord, StructureParseError};
 
 const START_RECORD_TAG: &str = "# STOCKHOLM";
 const RFAM_ACCESSION_TAG: &str = "#=GF AC";
@@ -37,7 +42,7 @@ pub fn parse_rfam_stockholm(reader: impl BufRead) -> Result<Vec<SecondaryStructu
             dotbracketstring = Some(value.trim().to_string());
         } else if line.starts_with(END_RECORD_TAG) {
             if accession.is_some() && dotbracketstring.is_some() && consensus_sequence.is_some() {
-                let mut ss: SecondaryStructureRecord = dotbracketstring.unwrap().parse()?;
+                let mut ss = SecondaryStructureRecord::new(from_wuss_string(&dotbracketstring.unwrap())?);
                 ss.name = accession.unwrap();
                 ss.set_sequence(consensus_sequence.unwrap());
                 ls.push(ss);
@@ -57,4 +62,419 @@ pub fn parse_rfam_stockholm(reader: impl BufRead) -> Result<Vec<SecondaryStructu
 pub fn parse_rfam_stockholm_gz_file(gz_file: File) -> Result<Vec<SecondaryStructureRecord>, Box<dyn Error>> {
     let reader = BufReader::new(GzDecoder::new(gz_file));
     parse_rfam_stockholm(reader)
-}
\ No newline at end of file
+}
+
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum RfamParseError {
+    #[error("Error in family '{accession}': {msg}")]
+    FamilyError {
+        accession: String,
+        msg: String,
+    },
+}
+
+/// The default gap characters of aligned sequences, for use with
+/// [project_consensus](fn.project_consensus.html).
+pub const SEQUENCE_GAP_CHARS: &str = ".-";
+
+/// A Stockholm alignment of a single family.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentRecord {
+    /// The accession (AC) of the family.
+    pub accession: String,
+
+    /// The consensus secondary structure (SS_cons) as a paired sites vector over the alignment
+    /// columns.
+    pub consensus_structure: Vec<i64>,
+
+    /// The aligned sequences as `(seq_name, aligned_sequence)` rows, in order of first appearance.
+    pub rows: Vec<(String, String)>,
+}
+
+struct StockholmRecords<R: BufRead> {
+    lines: Lines<R>,
+}
+
+impl<R: BufRead> StockholmRecords<R> {
+    fn family_error(accession: &Option<String>, msg: String) -> Box<dyn Error> {
+        Box::new(RfamParseError::FamilyError {
+            accession: accession.clone().unwrap_or_default(),
+            msg,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for StockholmRecords<R> {
+    type Item = Result<AlignmentRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut accession: Option<String> = None;
+        let mut dotbracketstring = String::new();
+        let mut rows: Vec<(String, String)> = Vec::new();
+        let mut row_index: HashMap<String, usize> = HashMap::new();
+        let mut started = false;
+        for line in &mut self.lines {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(Self::family_error(&accession, err.to_string())))
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            started = true;
+            if line.starts_with(RFAM_ACCESSION_TAG) {
+                let (_tag, value) = line.split_at(RFAM_ACCESSION_TAG.len());
+                accession = Some(value.trim().to_string());
+            } else if line.starts_with(RFAM_STRUCTURE_TAG) {
+                let (_tag, value) = line.split_at(RFAM_STRUCTURE_TAG.len());
+                dotbracketstring.push_str(value.trim());
+            } else if line.starts_with(END_RECORD_TAG) {
+                let consensus_structure = match from_wuss_string(&dotbracketstring) {
+                    Ok(paired) => paired,
+                    Err(err) => return Some(Err(Self::family_error(&accession, err.to_string())))
+                };
+                return Some(Ok(AlignmentRecord {
+                    accession: accession.unwrap_or_default(),
+                    consensus_structure,
+                    rows,
+                }));
+            } else if !line.starts_with('#') {
+                // interleaved alignments split each row over several blocks
+                let mut spl = trimmed.split_whitespace();
+                let name = spl.next().unwrap().to_string();
+                let aligned = spl.collect::<String>();
+                match row_index.get(&name) {
+                    Some(index) => rows[*index].1.push_str(&aligned),
+                    None => {
+                        row_index.insert(name.clone(), rows.len());
+                        rows.push((name, aligned));
+                    }
+                }
+            }
+        }
+
+        if started {
+            Some(Err(Self::family_error(&accession, format!("Expected a '{}' line at the end of the alignment.", END_RECORD_TAG))))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns an iterator over the families of a Stockholm file, parsing the accession (AC), the
+/// consensus secondary structure (SS_cons), and every aligned sequence of one family at a time.
+/// Rows and SS_cons lines of interleaved alignments are concatenated by sequence name. Errors are
+/// reported with the accession of the family in which they occurred.
+pub fn iter_rfam_stockholm(reader: impl BufRead) -> impl Iterator<Item=Result<AlignmentRecord, Box<dyn Error>>> {
+    StockholmRecords { lines: reader.lines() }
+}
+
+/// Reads a buffer representing a Stockholm file and returns an AlignmentRecord for each family,
+/// holding the accession (AC), the consensus secondary structure (SS_cons), and every aligned
+/// sequence. Rows and SS_cons lines of interleaved alignments are concatenated by sequence name.
+/// See [iter_rfam_stockholm](fn.iter_rfam_stockholm.html) to read one family at a time.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::parse_rfam_stockholm_full;
+///
+/// let stockholm = "# STOCKHOLM 1.0
+/// #=GF AC   RF99999
+/// seq1         GG-A
+/// seq2         GGAA
+/// #=GC SS_cons <<.-
+///
+/// seq1         AACC
+/// seq2         A-CU
+/// #=GC SS_cons __>>
+/// //
+/// ";
+///
+/// let alignments = parse_rfam_stockholm_full(stockholm.as_bytes()).unwrap();
+/// assert_eq!(alignments[0].accession, "RF99999");
+/// assert_eq!(alignments[0].consensus_structure, vec![8, 7, 0, 0, 0, 0, 2, 1]);
+/// assert_eq!(alignments[0].rows, vec![("seq1".to_string(), "GG-AAACC".to_string()), ("seq2".to_string(), "GGAAA-CU".to_string())]);
+/// ```
+pub fn parse_rfam_stockholm_full(reader: impl BufRead) -> Result<Vec<AlignmentRecord>, Box<dyn Error>> {
+    iter_rfam_stockholm(reader).collect()
+}
+
+/// Projects the consensus structure onto an aligned sequence by removing the columns that are gaps
+/// in that sequence, i.e. any character in 'gap_chars' (usually
+/// [SEQUENCE_GAP_CHARS](constant.SEQUENCE_GAP_CHARS.html)), discarding base-pairs with a gapped
+/// partner, and renumbering the remaining base-pairs to the ungapped sequence. Columns beyond the
+/// end of the consensus structure are ignored, and a partner beyond its end is treated as a gap.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::{project_consensus, SEQUENCE_GAP_CHARS};
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string};
+/// let consensus = from_dotbracketstring("((.(...)))").unwrap();
+/// let ss = project_consensus(&consensus, "GA-GAAACU-", SEQUENCE_GAP_CHARS);
+/// assert_eq!(ss.sequence, "GAGAAACU");
+/// assert_eq!(get_dot_bracket_string(&ss).unwrap(), ".((...))");
+/// ```
+pub fn project_consensus(consensus_paired: &[i64], aligned_seq: &str, gap_chars: &str) -> SecondaryStructureRecord {
+    let mut sequence = String::new();
+    let mut index = vec![0; consensus_paired.len()];
+    let mut len = 0;
+    for (column, c) in aligned_seq.chars().enumerate().take(consensus_paired.len()) {
+        if !gap_chars.contains(c) {
+            sequence.push(c);
+            len += 1;
+            index[column] = len;
+        }
+    }
+
+    let mut paired = Vec::with_capacity(len as usize);
+    for (column, j) in consensus_paired.iter().enumerate() {
+        if index[column] != 0 {
+            paired.push(if *j > 0 { index.get((*j - 1) as usize).copied().unwrap_or(0) } else { 0 });
+        }
+    }
+    // partners in gap columns, or beyond the consensus structure, have index 0, i.e. they become
+    // unpaired
+    let mut ss = SecondaryStructureRecord::new(paired);
+    ss.set_sequence(sequence);
+    ss
+}
+
+/// Reads a Stockholm file one family at a time and writes the consensus structure projected onto
+/// each ungapped aligned sequence to a buffer in dot bracket notation (DBN) format, named by
+/// sequence name. Only a single family is held in memory at a time, making this suitable for
+/// converting large files such as the full Rfam seed. Returns the number of records written.
+///
+/// Errors, including aligned sequences whose length differs from SS_cons, are reported with the
+/// accession of the family in which they occurred. Records written before the error remain in the
+/// buffer.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::write_stockholm_as_dbn;
+///
+/// let stockholm = "# STOCKHOLM 1.0
+/// #=GF AC   RF99999
+/// seq1         GG-AAACC
+/// seq2         GGAAA-CU
+/// #=GC SS_cons <<.___>>
+/// //
+/// ";
+///
+/// let mut buffer = Vec::new();
+/// assert_eq!(write_stockholm_as_dbn(stockholm.as_bytes(), &mut buffer).unwrap(), 2);
+/// assert_eq!(String::from_utf8(buffer).unwrap(), ">seq1\nGGAAACC\n((...))\n>seq2\nGGAAACU\n((...))\n");
+/// ```
+pub fn write_stockholm_as_dbn(reader: impl BufRead, buffer: &mut dyn io::Write) -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+    for alignment in iter_rfam_stockholm(reader) {
+        let alignment = alignment?;
+        let family_error = |msg: String| RfamParseError::FamilyError { accession: alignment.accession.clone(), msg };
+        for (name, aligned_seq) in alignment.rows.iter() {
+            if aligned_seq.chars().count() != alignment.consensus_structure.len() {
+                return Err(Box::new(family_error(format!("Aligned sequence '{}' has length {}, but SS_cons has length {}.", name, aligned_seq.chars().count(), alignment.consensus_structure.len()))));
+            }
+            let mut ss = project_consensus(&alignment.consensus_structure, aligned_seq, SEQUENCE_GAP_CHARS);
+            ss.name = name.clone();
+            write_dbn(buffer, &ss).map_err(|err| family_error(err.to_string()))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Writes a single family to a buffer in Stockholm format: the `# STOCKHOLM 1.0` header, the
+/// accession (AC), one row per record of its name and (aligned) sequence, the consensus secondary
+/// structure (SS_cons) in dot bracket notation, and the `//` terminator. The result may be read
+/// back using [parse_rfam_stockholm_full](fn.parse_rfam_stockholm_full.html).
+///
+/// Returns an error if the records and the consensus structure do not all share a length.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::write_stockholm;
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, SecondaryStructureRecord};
+///
+/// let consensus = from_dotbracketstring("((...))").unwrap();
+/// let mut ss = SecondaryStructureRecord::new(vec![0; 7]);
+/// ss.name = "seq1".to_string();
+/// ss.set_sequence("GG-AACC".to_string());
+///
+/// let mut buffer = Vec::new();
+/// write_stockholm(&mut buffer, "RF99999", &[&ss], &consensus).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "# STOCKHOLM 1.0
+/// #=GF AC   RF99999
+/// seq1         GG-AACC
+/// #=GC SS_cons ((...))
+/// //
+/// ");
+/// ```
+pub fn write_stockholm(buffer: &mut dyn io::Write, accession: &str, records: &[&SecondaryStructureRecord], consensus_paired: &[i64]) -> Result<(), Box<dyn Error>> {
+    let family_error = |msg: String| RfamParseError::FamilyError { accession: accession.to_string(), msg };
+    for ss in records.iter() {
+        if ss.sequence.chars().count() != consensus_paired.len() {
+            return Err(Box::new(family_error(format!("Sequence '{}' has length {}, but the consensus structure has length {}.", ss.name, ss.sequence.chars().count(), consensus_paired.len()))));
+        }
+    }
+    let consensus_paired = consensus_paired.to_vec();
+    let dotbracketstring = get_dot_bracket_string(&consensus_paired)?;
+
+    let width = records.iter().map(|ss| ss.name.chars().count()).chain(std::iter::once(RFAM_STRUCTURE_TAG.len())).max().unwrap_or_default();
+    writeln!(buffer, "{} 1.0", START_RECORD_TAG)?;
+    writeln!(buffer, "{}   {}", RFAM_ACCESSION_TAG, accession)?;
+    for ss in records.iter() {
+        writeln!(buffer, "{:width$} {}", ss.name, ss.sequence, width = width)?;
+    }
+    writeln!(buffer, "{:width$} {}", RFAM_STRUCTURE_TAG, dotbracketstring, width = width)?;
+    writeln!(buffer, "{}", END_RECORD_TAG)?;
+    Ok(())
+}
+
+/// Returns the base-pairs of a consensus structure, such as an `SS_cons` line in WUSS notation
+/// (see [from_wuss_string](../secondary_structure/fn.from_wuss_string.html)), as 0-based
+/// alignment column pairs `(i, j)` with `i < j`, ordered by `i`.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::alignment_base_pairs;
+/// assert_eq!(alignment_base_pairs("::<<-__>>,AA...aa").unwrap(), vec![(2, 8), (3, 7), (10, 16), (11, 15)]);
+/// ```
+pub fn alignment_base_pairs(ss_cons: &str) -> Result<Vec<(usize, usize)>, StructureParseError> {
+    Ok(base_pairs(&from_wuss_string(ss_cons)?))
+}
+
+/// Returns the mutual information, in bits (log base 2), between the two alignment columns of each
+/// consensus base-pair across the aligned sequences of an alignment, as `(name, aligned sequence)`
+/// rows such as [AlignmentRecord::rows](struct.AlignmentRecord.html#structfield.rows). Pairs at
+/// 0-based alignment columns are given as by [alignment_base_pairs](fn.alignment_base_pairs.html).
+///
+/// Nucleotides are compared ignoring case, with T treated as U. A row with a gap (see
+/// [SEQUENCE_GAP_CHARS](constant.SEQUENCE_GAP_CHARS.html)) in both columns of a base-pair is
+/// ignored for that base-pair, whereas a gap in one column is counted as a symbol of its own.
+/// Columns beyond the end of a row are treated as gaps. The score of a base-pair is 0.0 if every
+/// row is ignored, and is at most 2 bits for the four nucleotides, when the columns covary
+/// perfectly, e.g. through compensatory mutations.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::covariation_scores;
+/// let rows = vec![("a".to_string(), "GAAC".to_string()), ("b".to_string(), "CAAG".to_string())];
+/// // the outer pair covaries (G-C and C-G), the inner columns are constant
+/// assert_eq!(covariation_scores(&rows, &[(0, 3), (1, 2)]), vec![1.0, 0.0]);
+/// ```
+pub fn covariation_scores(records: &[(String, String)], ss_cons_pairs: &[(usize, usize)]) -> Vec<f64> {
+    let normalise = |c: char| if SEQUENCE_GAP_CHARS.contains(c) { '-' } else { normalise_nucleotide(c) };
+    let rows = records.iter()
+        .map(|(_, aligned)| aligned.chars().map(normalise).collect::<Vec<char>>())
+        .collect::<Vec<Vec<char>>>();
+
+    ss_cons_pairs.iter().map(|(i, j)| {
+        let mut joint: HashMap<(char, char), f64> = HashMap::new();
+        let mut total = 0.0;
+        for row in rows.iter() {
+            let x = row.get(*i).copied().unwrap_or('-');
+            let y = row.get(*j).copied().unwrap_or('-');
+            if x == '-' && y == '-' {
+                continue;
+            }
+            *joint.entry((x, y)).or_insert(0.0) += 1.0;
+            total += 1.0;
+        }
+
+        let mut left: HashMap<char, f64> = HashMap::new();
+        let mut right: HashMap<char, f64> = HashMap::new();
+        for ((x, y), count) in joint.iter() {
+            *left.entry(*x).or_insert(0.0) += count / total;
+            *right.entry(*y).or_insert(0.0) += count / total;
+        }
+        joint.iter().map(|((x, y), count)| {
+            let f = count / total;
+            f * (f / (left[x] * right[y])).log2()
+        }).sum::<f64>().max(0.0)
+    }).collect()
+}
+
+/// Returns, for each aligned member sequence, the fraction of the consensus structure's base-pairs
+/// whose two nucleotides (in alignment coordinates) can form a canonical base-pair in that member
+/// (see [is_canonical_pair](../secondary_structure/fn.is_canonical_pair.html)). Base-pairs with
+/// a gap at either position count as incompatible. If the consensus structure has no base-pairs
+/// every member is trivially compatible and 1.0 is returned.
+///
+/// Returns `UnequalLength` if any member sequence differs in length from the consensus structure.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::consensus_compatibility;
+/// use rna_secondary_structure::secondary_structure::from_dotbracketstring;
+/// let consensus = from_dotbracketstring("((...))").unwrap();
+/// let compatibility = consensus_compatibility(&consensus, &["GCAAAGC", "GCAAAAC", "G-AAAAA"]).unwrap();
+/// assert_eq!(compatibility, vec![1.0, 0.5, 0.0]);
+/// ```
+pub fn consensus_compatibility(consensus: &dyn PairedSites, member_sequences: &[&str]) -> Result<Vec<f64>, SecondaryStructureMetricError> {
+    let pairs = base_pairs(consensus);
+    let consensus = consensus.paired();
+
+    let mut compatibility = Vec::with_capacity(member_sequences.len());
+    for member in member_sequences {
+        let member = member.chars().collect::<Vec<char>>();
+        if member.len() != consensus.len() {
+            return Err(SecondaryStructureMetricError::UnequalLength);
+        }
+        if pairs.is_empty() {
+            compatibility.push(1.0);
+        } else {
+            let compatible = pairs.iter().filter(|(i, j)| is_canonical_pair(member[*i], member[*j])).count();
+            compatibility.push(compatible as f64 / pairs.len() as f64);
+        }
+    }
+    Ok(compatibility)
+}
+
+/// Returns a structural analogue of the RNAz structure conservation index (SCI): the mean, over
+/// member structures, of the fraction of consensus base-pairs recovered by each member. All
+/// structures are in alignment coordinates. The result lies in 0..1, with 1.0 meaning every
+/// member contains every consensus base-pair. If the consensus structure has no base-pairs 1.0 is
+/// returned, and if there are no members 0.0 is returned.
+///
+/// Returns `UnequalLength` if any member structure differs in length from the consensus structure.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::read_rfam::structure_conservation_index;
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, PairedSites};
+/// let consensus = from_dotbracketstring("((...))").unwrap();
+/// let member1 = from_dotbracketstring("((...))").unwrap();
+/// let member2 = from_dotbracketstring(".(...).").unwrap();
+/// let sci = structure_conservation_index(&[&member1, &member2], &consensus).unwrap();
+/// assert_eq!(sci, 0.75);
+/// ```
+pub fn structure_conservation_index(member_structures: &[&dyn PairedSites], consensus: &dyn PairedSites) -> Result<f64, SecondaryStructureMetricError> {
+    let consensus = consensus.paired();
+    if member_structures.iter().any(|member| member.paired().len() != consensus.len()) {
+        return Err(SecondaryStructureMetricError::UnequalLength);
+    }
+    if member_structures.is_empty() {
+        return Ok(0.0);
+    }
+    let pairs = base_pairs(consensus);
+    if pairs.is_empty() {
+        return Ok(1.0);
+    }
+
+    let mut total = 0.0;
+    for member in member_structures {
+        let member = member.paired();
+        let recovered = pairs.iter().filter(|(i, j)| member[*i] == (*j + 1) as i64).count();
+        total += recovered as f64 / pairs.len() as f64;
+    }
+    Ok(total / member_structures.len() as f64)
+}
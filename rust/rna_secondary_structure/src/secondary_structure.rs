@@ -1,11 +1,18 @@
 //! A module for representing secondary structures.
 
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::str;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::distance_metrics::get_mountain_vector;
+
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
 pub enum StructureParseError {
@@ -23,9 +30,10 @@ pub enum StructureParseError {
         pos: usize,
     },
 
-    #[error("Bracket type not recognised: '{c}'")]
+    #[error("Bracket type not recognised: '{c}'{}", .pos.map_or(String::new(), |pos| format!(" at position {}", pos)))]
     BracketTypeNotRecognised {
-        c: char
+        c: char,
+        pos: Option<usize>,
     },
 
     #[error("Insufficient bracket types are available for parsing structure unambigously.")]
@@ -41,6 +49,38 @@ pub enum StructureParseError {
     ExpectedLine {
         msg: String
     },
+
+    #[error("Structure is pseudoknotted, but a non-pseudoknotted structure is required.")]
+    Pseudoknotted,
+
+    #[error("Cannot add base-pair ({i}, {j}): {msg}")]
+    InvalidBasePair {
+        i: usize,
+        j: usize,
+        msg: String,
+    },
+
+    #[error("Abstract shape level {level} not recognised, expected a level from 1 to 5.")]
+    ShapeLevelNotRecognised {
+        level: u8
+    },
+
+    #[error("Inconsistent pairing: position {i} is paired with position {j}")]
+    InconsistentPairing {
+        i: usize,
+        j: usize,
+    },
+
+    #[error("Sequence of length {sequence_len} does not match secondary structure of length {structure_len}")]
+    LengthMismatch {
+        sequence_len: usize,
+        structure_len: usize,
+    },
+
+    #[error("Unpaired character '{c}' is a bracket character")]
+    UnpairedCharacterIsBracket {
+        c: char
+    },
 }
 
 /// A string of characters representing possible left bracket types
@@ -73,11 +113,18 @@ pub fn get_matching_bracket(brace: char) -> Result<char, StructureParseError> {
         return Ok(LEFT_BRACKETS.chars().nth(right_pos).unwrap());
     }
 
-    Err(StructureParseError::BracketTypeNotRecognised { c: brace })
+    Err(StructureParseError::BracketTypeNotRecognised { c: brace, pos: None })
 }
 
 /// A struct containing the name, nucleotide sequence, and secondary structure conformation of
 /// a secondary structure.
+///
+/// Two records are equal only if all of their fields are equal, not just their secondary
+/// structures (compare `paired` directly for that). Energies are compared bitwise, so that every
+/// record, including one with a NaN energy, is equal to itself. Records may therefore be used as
+/// `HashSet` and `HashMap` keys.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SecondaryStructureRecord {
     /// A name for this record.
     pub name: String,
@@ -87,6 +134,17 @@ pub struct SecondaryStructureRecord {
 
     /// A vector of paired sites.
     pub paired: Vec<i64>,
+
+    /// The free energy (in kcal/mol) of the secondary structure, if known.
+    pub energy: Option<f64>,
+
+    /// A per-position structure type annotation (e.g. the `E`, `S`, `H`, `B`, `I`, `M`, `X` string
+    /// in bpRNA `.st` files), if known.
+    pub annotation: Option<String>,
+
+    /// The original residue numbering of each position (e.g. from a CT file with non-contiguous
+    /// numbering), if it differs from `1..=n`.
+    pub numbering: Option<Vec<i64>>,
 }
 
 impl SecondaryStructureRecord {
@@ -97,23 +155,458 @@ impl SecondaryStructureRecord {
             name: "".to_string(),
             sequence: "N".repeat(paired.len()),
             paired,
+            energy: None,
+            annotation: None,
+            numbering: None,
         }
     }
 
+    /// Returns a [SecondaryStructureRecordBuilder](struct.SecondaryStructureRecordBuilder.html)
+    /// for constructing a record from a name, sequence, and secondary structure.
+    pub fn builder() -> SecondaryStructureRecordBuilder {
+        SecondaryStructureRecordBuilder::default()
+    }
+
     /// Set the nucleotide sequence.
     pub fn set_sequence(&mut self, sequence: String) {
         self.sequence = sequence;
     }
 
+    /// Converts the nucleotide sequence to RNA by replacing T with U, preserving case. The
+    /// secondary structure and all other characters are left unchanged.
+    pub fn to_rna(&mut self) {
+        self.sequence = self.sequence.chars().map(|c| match c {
+            'T' => 'U',
+            't' => 'u',
+            c => c
+        }).collect();
+    }
+
+    /// Converts the nucleotide sequence to DNA by replacing U with T, preserving case. The
+    /// secondary structure and all other characters are left unchanged.
+    pub fn to_dna(&mut self) {
+        self.sequence = self.sequence.chars().map(|c| match c {
+            'U' => 'T',
+            'u' => 't',
+            c => c
+        }).collect();
+    }
+
+    /// Returns the reverse complement of this record: the sequence reversed with complemented bases
+    /// (A-U/T, G-C, N-N, preserving case) and the structure reindexed so that position `i` maps to
+    /// `len - 1 - i`, preserving every base-pair. The sequence is treated as RNA, complementing A
+    /// to U, if it contains a U, and as DNA otherwise. Other characters are left unchanged.
+    ///
+    /// The name and annotation are retained (the annotation reversed), the energy is cleared as it
+    /// does not carry over to the complemented sequence, and any original numbering is dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "((...))..".parse().unwrap();
+    /// ss.sequence = "GCAAAGCAU".to_string();
+    /// let rc = ss.reverse_complement();
+    /// assert_eq!(rc.sequence, "AUGCUUUGC");
+    /// assert_eq!(rc.get_dot_bracket_string().unwrap(), "..((...))");
+    /// ```
+    pub fn reverse_complement(&self) -> SecondaryStructureRecord {
+        let rna = self.sequence.contains(['U', 'u']);
+        let sequence = self.sequence.chars().rev().map(|c| match c {
+            'A' => if rna { 'U' } else { 'T' },
+            'a' => if rna { 'u' } else { 't' },
+            'U' | 'T' => 'A',
+            'u' | 't' => 'a',
+            'G' => 'C',
+            'g' => 'c',
+            'C' => 'G',
+            'c' => 'g',
+            c => c
+        }).collect();
+
+        let len = self.paired.len() as i64;
+        let paired = self.paired.iter().rev().map(|j| if *j == 0 { 0 } else { len - *j + 1 }).collect();
+
+        SecondaryStructureRecord {
+            name: self.name.clone(),
+            sequence,
+            paired,
+            energy: None,
+            annotation: self.annotation.as_ref().map(|annotation| annotation.chars().rev().collect()),
+            numbering: None,
+        }
+    }
+
+    /// Returns the concatenation of this record followed by 'other', with the positions of the
+    /// base-pairs of 'other' shifted by the length of this record. No base-pairs are formed across
+    /// the junction.
+    ///
+    /// The name of this record is retained, the annotations are concatenated if both records have
+    /// one, and the energy and any original numbering are dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "(.)".parse().unwrap();
+    /// ss.set_sequence("GAC".to_string());
+    /// let mut other: SecondaryStructureRecord = "(..)".parse().unwrap();
+    /// other.set_sequence("GAAC".to_string());
+    /// let joined = ss.concat(&other);
+    /// assert_eq!(joined.sequence, "GACGAAC");
+    /// assert_eq!(joined.get_dot_bracket_string().unwrap(), "(.)(..)");
+    /// ```
+    pub fn concat(&self, other: &SecondaryStructureRecord) -> SecondaryStructureRecord {
+        let offset = self.paired.len() as i64;
+        let mut paired = self.paired.clone();
+        paired.extend(other.paired.iter().map(|j| if *j > 0 { j + offset } else { *j }));
+
+        SecondaryStructureRecord {
+            name: self.name.clone(),
+            sequence: format!("{}{}", self.sequence, other.sequence),
+            paired,
+            energy: None,
+            annotation: match (&self.annotation, &other.annotation) {
+                (Some(a), Some(b)) => Some(format!("{}{}", a, b)),
+                _ => None
+            },
+            numbering: None,
+        }
+    }
+
+    /// Returns the record restricted to the 0-based half-open range of positions 'range', i.e.
+    /// including `range.start` but not `range.end`. Base-pairs with a position outside the range
+    /// are removed, and the remaining base-pairs are renumbered relative to `range.start`.
+    ///
+    /// The name is retained, the annotation and any original numbering are restricted to the
+    /// range, and the energy is dropped.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or extends beyond the end of the secondary structure.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "((.))..(.)".parse().unwrap();
+    /// ss.set_sequence("GGACCAAGAC".to_string());
+    /// let sliced = ss.slice(1..9);
+    /// assert_eq!(sliced.sequence, "GACCAAGA");
+    /// assert_eq!(sliced.get_dot_bracket_string().unwrap(), "(.).....");
+    /// ```
+    pub fn slice(&self, range: Range<usize>) -> SecondaryStructureRecord {
+        let paired = self.paired[range.clone()].iter().map(|j| {
+            if *j > range.start as i64 && *j <= range.end as i64 { j - range.start as i64 } else { 0 }
+        }).collect();
+        let substring = |s: &String| s.chars().skip(range.start).take(range.len()).collect::<String>();
+
+        SecondaryStructureRecord {
+            name: self.name.clone(),
+            sequence: substring(&self.sequence),
+            paired,
+            energy: None,
+            annotation: self.annotation.as_ref().map(substring),
+            numbering: self.numbering.as_ref().map(|numbering| numbering.iter().skip(range.start).take(range.len()).cloned().collect()),
+        }
+    }
+
     /// Set the secondary structure conformation from a paired sites representation.
     pub fn set_paired(&mut self, paired: Vec<i64>) {
         self.paired = paired;
     }
 
+    /// Returns the length of the record, which is the length of its secondary structure. The
+    /// sequence may differ in length, see [sequence_len](#method.sequence_len).
+    pub fn len(&self) -> usize {
+        self.structure_len()
+    }
+
+    /// Returns true if the secondary structure has no positions.
+    pub fn is_empty(&self) -> bool {
+        self.paired.is_empty()
+    }
+
+    /// Returns the number of positions in the secondary structure, `paired.len()`.
+    pub fn structure_len(&self) -> usize {
+        self.paired.len()
+    }
+
+    /// Returns the number of characters in the nucleotide sequence, which need not equal the
+    /// [structure_len](#method.structure_len), for instance if the sequence was set separately.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    /// ss.set_sequence("GCAA".to_string());
+    /// assert_eq!((ss.len(), ss.structure_len(), ss.sequence_len()), (6, 6, 4));
+    /// ```
+    pub fn sequence_len(&self) -> usize {
+        self.sequence.chars().count()
+    }
+
     /// Get a dot bracket string representation of the secondary structure conformation.
     pub fn get_dot_bracket_string(&self) -> Result<String, StructureParseError> {
         get_dot_bracket_string(self)
     }
+
+    /// Checks that the paired sites vector is consistent: every nonzero partner lies within
+    /// `1..=len`, no site is paired with itself, and every pairing is symmetric, i.e.
+    /// `paired[i] == j` implies `paired[j - 1] == i + 1`.
+    ///
+    /// Returns `InconsistentPairing` for the first offending site, with positions given 1-based
+    /// as in the paired sites representation (a negative partner is reported as 0).
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// assert!(SecondaryStructureRecord::new(vec![4, 0, 0, 1]).validate().is_ok());
+    /// assert!(SecondaryStructureRecord::new(vec![4, 0, 0, 0]).validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), StructureParseError> {
+        validate_paired(&self.paired)
+    }
+
+    /// Returns a multi-line representation of the record for debugging: a `>name` header, the
+    /// sequence, the dot bracket string, and a ruler with a `|` marker every 10 positions beneath
+    /// a line of 1-based position labels ending above each marker. Unlike the terse `Display`
+    /// this never panics: a structure that cannot be written in dot bracket notation is shown as
+    /// `?` at each position.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "((((....))))".parse().unwrap();
+    /// ss.name = "hairpin".to_string();
+    /// ss.set_sequence("GGGGAAAACCCC".to_string());
+    /// assert_eq!(ss.pretty_print(), ">hairpin
+    /// GGGGAAAACCCC
+    /// ((((....))))
+    ///         10
+    /// .........|..
+    /// ");
+    /// ```
+    pub fn pretty_print(&self) -> String {
+        let len = self.paired.len();
+        let dbs = get_dot_bracket_string(self).unwrap_or_else(|_| "?".repeat(len));
+
+        let mut labels = String::new();
+        let mut markers = String::new();
+        for position in 1..=len {
+            if position % 10 == 0 {
+                let label = position.to_string();
+                labels.truncate(labels.len().saturating_sub(label.len() - 1));
+                labels.push_str(&label);
+                markers.push('|');
+            } else {
+                labels.push(' ');
+                markers.push('.');
+            }
+        }
+        format!(">{}\n{}\n{}\n{}\n{}\n", self.name, self.sequence, dbs, labels.trim_end(), markers)
+    }
+
+    /// Returns the base-pairs of the secondary structure, see [base_pairs](fn.base_pairs.html).
+    pub fn base_pairs(&self) -> Vec<(usize, usize)> {
+        base_pairs(self)
+    }
+
+    /// Adds a base-pair between the 0-based positions i and j, updating both positions.
+    ///
+    /// Returns `InvalidBasePair` if i equals j, either position is beyond the end of the secondary
+    /// structure, or either position is already paired, in which case the record is unchanged.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "......".parse().unwrap();
+    /// ss.add_base_pair(0, 5).unwrap();
+    /// assert_eq!(ss.get_dot_bracket_string().unwrap(), "(....)");
+    /// assert!(ss.add_base_pair(5, 3).is_err());
+    /// ```
+    pub fn add_base_pair(&mut self, i: usize, j: usize) -> Result<(), StructureParseError> {
+        let invalid = |msg: &str| Err(StructureParseError::InvalidBasePair { i, j, msg: msg.to_string() });
+        if i == j {
+            return invalid("a position cannot pair with itself");
+        }
+        if i >= self.paired.len() || j >= self.paired.len() {
+            return invalid(&format!("the secondary structure has length {}", self.paired.len()));
+        }
+        if self.paired[i] != 0 || self.paired[j] != 0 {
+            return invalid("a position is already paired");
+        }
+        self.paired[i] = (j + 1) as i64;
+        self.paired[j] = (i + 1) as i64;
+        Ok(())
+    }
+
+    /// Removes the base-pair involving the 0-based position i, if any, setting both of its
+    /// positions to unpaired.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    /// ss.remove_base_pair(4);
+    /// assert_eq!(ss.get_dot_bracket_string().unwrap(), "(....)");
+    /// ```
+    pub fn remove_base_pair(&mut self, i: usize) {
+        if let Some(j) = self.base_pair_at(i) {
+            if let Some(partner) = self.paired.get_mut(j) {
+                if *partner == (i + 1) as i64 {
+                    *partner = 0;
+                }
+            }
+            self.paired[i] = 0;
+        }
+    }
+
+    /// Returns an iterator over the positions of the record, yielding the 0-based position, the
+    /// nucleotide at that position, and the 0-based position of its partner (see
+    /// [base_pair_at](trait.PairedSites.html#method.base_pair_at)). Positions beyond the end of a
+    /// sequence shorter than the secondary structure are given the nucleotide 'N', and nucleotides
+    /// beyond the end of the secondary structure are ignored.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss: SecondaryStructureRecord = "(.)".parse().unwrap();
+    /// ss.set_sequence("AC".to_string());
+    /// let positions: Vec<(usize, char, Option<usize>)> = ss.iter_positions().collect();
+    /// assert_eq!(positions, vec![(0, 'A', Some(2)), (1, 'C', None), (2, 'N', Some(0))]);
+    /// ```
+    pub fn iter_positions(&self) -> impl Iterator<Item=(usize, char, Option<usize>)> + '_ {
+        let mut sequence = self.sequence.chars();
+        (0..self.paired.len()).map(move |i| (i, sequence.next().unwrap_or('N'), self.base_pair_at(i)))
+    }
+
+    /// Returns the fraction of G and C nucleotides amongst the A, C, G, T, and U nucleotides of the
+    /// sequence, ignoring case. Gaps, N, and all other characters are ignored. Returns 0.0 if there
+    /// are no A, C, G, T, or U nucleotides.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss = SecondaryStructureRecord::new(vec![0; 8]);
+    /// ss.set_sequence("GgA-NcUu".to_string());
+    /// assert_eq!(ss.gc_content(), 0.5);
+    /// ```
+    pub fn gc_content(&self) -> f64 {
+        let mut gc = 0;
+        let mut total = 0;
+        for c in self.sequence.chars() {
+            match c.to_ascii_uppercase() {
+                'G' | 'C' => {
+                    gc += 1;
+                    total += 1;
+                }
+                'A' | 'T' | 'U' => total += 1,
+                _ => {}
+            }
+        }
+        if total > 0 { gc as f64 / total as f64 } else { 0.0 }
+    }
+
+    /// Returns the number of occurrences of each character of the sequence, ignoring case, keyed
+    /// by the uppercase character. If 'merge_t_and_u' is true, T and U are counted together under
+    /// 'U'.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+    /// let mut ss = SecondaryStructureRecord::new(vec![0; 5]);
+    /// ss.set_sequence("GgTu-".to_string());
+    /// let counts = ss.base_counts(false);
+    /// assert_eq!((counts[&'G'], counts[&'T'], counts[&'U'], counts[&'-']), (2, 1, 1, 1));
+    /// assert_eq!(ss.base_counts(true)[&'U'], 2);
+    /// ```
+    pub fn base_counts(&self, merge_t_and_u: bool) -> HashMap<char, usize> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in self.sequence.chars() {
+            let c = match c.to_ascii_uppercase() {
+                'T' if merge_t_and_u => 'U',
+                c => c
+            };
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// A builder for a [SecondaryStructureRecord](struct.SecondaryStructureRecord.html), see
+/// [builder](struct.SecondaryStructureRecord.html#method.builder). The secondary structure is
+/// given either as a dot bracket string or as paired sites, whichever was set last. Without a
+/// sequence the record has a default sequence of all N's, and without a secondary structure every
+/// position of the sequence is unpaired.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+/// let ss = SecondaryStructureRecord::builder()
+///     .name("hairpin")
+///     .sequence("GGGAAACCC")
+///     .dot_bracket("(((...)))")
+///     .build()
+///     .unwrap();
+/// assert_eq!(ss.name, "hairpin");
+/// assert_eq!(ss.paired, vec![9, 8, 7, 0, 0, 0, 3, 2, 1]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SecondaryStructureRecordBuilder {
+    name: String,
+    sequence: Option<String>,
+    dot_bracket: Option<String>,
+    paired: Option<Vec<i64>>,
+}
+
+impl SecondaryStructureRecordBuilder {
+    /// Sets the name of the record.
+    pub fn name(mut self, name: &str) -> SecondaryStructureRecordBuilder {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets the nucleotide sequence of the record.
+    pub fn sequence(mut self, sequence: &str) -> SecondaryStructureRecordBuilder {
+        self.sequence = Some(sequence.to_string());
+        self
+    }
+
+    /// Sets the secondary structure from a dot bracket string, which is parsed by
+    /// [build](#method.build).
+    pub fn dot_bracket(mut self, dbs: &str) -> SecondaryStructureRecordBuilder {
+        self.dot_bracket = Some(dbs.to_string());
+        self.paired = None;
+        self
+    }
+
+    /// Sets the secondary structure from a list of paired sites.
+    pub fn paired(mut self, paired: Vec<i64>) -> SecondaryStructureRecordBuilder {
+        self.paired = Some(paired);
+        self.dot_bracket = None;
+        self
+    }
+
+    /// Builds the record, returning an error if the dot bracket string cannot be parsed, if the
+    /// paired sites are inconsistent (see
+    /// [validate](struct.SecondaryStructureRecord.html#method.validate)), or `LengthMismatch` if
+    /// the sequence and secondary structure differ in length.
+    pub fn build(self) -> Result<SecondaryStructureRecord, StructureParseError> {
+        let paired = match (self.paired, self.dot_bracket, &self.sequence) {
+            (Some(paired), _, _) => paired,
+            (None, Some(dbs), _) => from_dotbracketstring(&dbs)?,
+            (None, None, Some(sequence)) => vec![0; sequence.chars().count()],
+            (None, None, None) => Vec::new()
+        };
+
+        let mut ss = SecondaryStructureRecord::new(paired);
+        ss.name = self.name;
+        if let Some(sequence) = self.sequence {
+            ss.set_sequence(sequence);
+        }
+        if ss.sequence_len() != ss.structure_len() {
+            return Err(StructureParseError::LengthMismatch { sequence_len: ss.sequence_len(), structure_len: ss.structure_len() });
+        }
+        ss.validate()?;
+        Ok(ss)
+    }
 }
 
 /// A trait indicating that a struct can be converted to a vector representing a
@@ -122,6 +615,25 @@ pub trait PairedSites {
     /// Returns a reference to a list of base-paired and unpaired sites representing the
     /// conformation of an arbitrarily pseudoknotted secondary structure.
     fn paired(&self) -> &Vec<i64>;
+
+    /// Returns the 0-based position of the partner of the 0-based position i, or None if i is
+    /// unpaired or beyond the end of the secondary structure. This converts from the 1-based
+    /// paired sites representation, in which 0 denotes an unpaired site.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, PairedSites};
+    /// let paired = from_dotbracketstring("(.)").unwrap();
+    /// assert_eq!(paired.base_pair_at(0), Some(2));
+    /// assert_eq!(paired.base_pair_at(1), None);
+    /// assert_eq!(paired.base_pair_at(2), Some(0));
+    /// ```
+    fn base_pair_at(&self, i: usize) -> Option<usize> {
+        match self.paired().get(i) {
+            Some(j) if *j > 0 => Some((*j - 1) as usize),
+            _ => None
+        }
+    }
 }
 
 impl PartialEq<dyn PairedSites> for dyn PairedSites {
@@ -136,6 +648,29 @@ impl PairedSites for SecondaryStructureRecord {
     }
 }
 
+impl PartialEq for SecondaryStructureRecord {
+    fn eq(&self, other: &SecondaryStructureRecord) -> bool {
+        self.name == other.name
+            && self.sequence == other.sequence
+            && self.paired == other.paired
+            && self.energy.map(f64::to_bits) == other.energy.map(f64::to_bits)
+            && self.annotation == other.annotation
+            && self.numbering == other.numbering
+    }
+}
+
+impl Eq for SecondaryStructureRecord {}
+
+/// Hashes the name, sequence, and secondary structure, which is consistent with `PartialEq` as
+/// equal records also have equal names, sequences, and secondary structures.
+impl Hash for SecondaryStructureRecord {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.sequence.hash(state);
+        self.paired.hash(state);
+    }
+}
+
 impl Debug for SecondaryStructureRecord {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.paired.fmt(f)
@@ -148,6 +683,122 @@ impl PairedSites for Vec<i64> {
     }
 }
 
+/// Returns each base-pair once as `(i, j)` with `i < j`, ordered by `i`.
+///
+/// Note that the returned positions are 0-based, whereas the paired sites representation is
+/// 1-based: a base-pair `(i, j)` corresponds to `paired[i] == j + 1` and `paired[j] == i + 1`.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{base_pairs, from_dotbracketstring};
+/// let paired = from_dotbracketstring("((..))").unwrap();
+/// assert_eq!(base_pairs(&paired), vec![(0, 5), (1, 4)]);
+/// ```
+pub fn base_pairs(paired: &dyn PairedSites) -> Vec<(usize, usize)> {
+    paired.paired().iter().enumerate()
+        .filter(|(i, j)| **j > (*i as i64) + 1)
+        .map(|(i, j)| (i, (*j - 1) as usize))
+        .collect()
+}
+
+/// Returns the number of base-pairs in the given secondary structure.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, num_base_pairs};
+/// assert_eq!(num_base_pairs(&from_dotbracketstring("((..))[.]").unwrap()), 3);
+/// ```
+pub fn num_base_pairs(paired: &dyn PairedSites) -> usize {
+    base_pairs(paired).len()
+}
+
+/// Returns the proportion of nucleotides that are base-paired, i.e. twice the number of
+/// base-pairs divided by the length of the secondary structure, or 0.0 if it is empty.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, paired_fraction};
+/// assert_eq!(paired_fraction(&from_dotbracketstring("((....))").unwrap()), 0.5);
+/// ```
+pub fn paired_fraction(paired: &dyn PairedSites) -> f64 {
+    let len = paired.paired().len();
+    if len == 0 {
+        0.0
+    } else {
+        (2 * num_base_pairs(paired)) as f64 / len as f64
+    }
+}
+
+/// Returns the maximum nesting depth of the base-pairs of the given secondary structure, i.e. the
+/// maximum height of its [mountain vector](../distance_metrics/fn.get_mountain_vector.html), or 0
+/// if it has no base-pairs. For pseudoknotted structures this follows the mountain vector
+/// convention, in which every base-pair opened and not yet closed adds one to the height,
+/// whether or not it crosses the others.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, max_nesting_depth};
+/// assert_eq!(max_nesting_depth(&from_dotbracketstring("((..))(((...)))").unwrap()), 3);
+/// assert_eq!(max_nesting_depth(&from_dotbracketstring("(.[.).]").unwrap()), 2);
+/// ```
+pub fn max_nesting_depth(paired: &dyn PairedSites) -> i64 {
+    get_mountain_vector(paired).into_iter().fold(0.0, f64::max) as i64
+}
+
+/// Returns a SecondaryStructureRecord of length 'len' with the given 0-based base-pairs, and a
+/// default sequence of all N's, see [new](struct.SecondaryStructureRecord.html#method.new).
+///
+/// Returns `InvalidBasePair` if a base-pair pairs a position with itself, has a position beyond
+/// the end of the secondary structure, or has a position already paired by an earlier base-pair.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::from_base_pairs;
+/// let ss = from_base_pairs(6, &[(0, 5), (1, 4)]).unwrap();
+/// assert_eq!(ss.get_dot_bracket_string().unwrap(), "((..))");
+/// assert!(from_base_pairs(6, &[(0, 5), (5, 2)]).is_err());
+/// ```
+pub fn from_base_pairs(len: usize, pairs: &[(usize, usize)]) -> Result<SecondaryStructureRecord, StructureParseError> {
+    let mut ss = SecondaryStructureRecord::new(vec![0; len]);
+    for (i, j) in pairs.iter() {
+        ss.add_base_pair(*i, *j)?;
+    }
+    Ok(ss)
+}
+
+/// Returns a paired sites list of length 'new_len' in which every base-pair of the given secondary
+/// structure is moved by 'offset' positions, for embedding a structure in a larger context or
+/// extracting it from one. All other positions are unpaired. See also
+/// [concat](struct.SecondaryStructureRecord.html#method.concat) and
+/// [slice](struct.SecondaryStructureRecord.html#method.slice).
+///
+/// Returns `InvalidBasePair`, naming the original 0-based base-pair, if a shifted position falls
+/// outside `1..=new_len`.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, shift};
+/// let paired = from_dotbracketstring("..(.)").unwrap();
+/// assert_eq!(shift(&paired, -2, 3).unwrap(), from_dotbracketstring("(.)").unwrap());
+/// assert!(shift(&paired, -3, 3).is_err());
+/// ```
+pub fn shift(paired: &dyn PairedSites, offset: i64, new_len: usize) -> Result<Vec<i64>, StructureParseError> {
+    let mut shifted = vec![0; new_len];
+    for (i, j) in base_pairs(paired) {
+        let (k, l) = (i as i64 + offset, j as i64 + offset);
+        if k < 0 || l >= new_len as i64 {
+            return Err(StructureParseError::InvalidBasePair {
+                i,
+                j,
+                msg: format!("shifted by {} it lies outside a secondary structure of length {}", offset, new_len)
+            });
+        }
+        shifted[k as usize] = l + 1;
+        shifted[l as usize] = k + 1;
+    }
+    Ok(shifted)
+}
+
 /// Returns a vector of paired sites from a dot bracket string representation.
 /// For usage see [FromStr for SecondaryStructure](struct.SecondaryStructureRecord.html#impl-FromStr).
 pub fn from_dotbracketstring(dbs: &str) -> Result<Vec<i64>, StructureParseError> {
@@ -164,7 +815,7 @@ pub fn from_dotbracketstring(dbs: &str) -> Result<Vec<i64>, StructureParseError>
             stacks.get_mut(index).unwrap().push(i as i64);
         } else if is_right_bracket(c) {
             let index = RIGHT_BRACKETS.find(c).unwrap();
-            if !(stacks.get(index).unwrap().is_empty()) {
+            if stacks.get(index).is_some_and(|stack| !stack.is_empty()) {
                 let j = stacks.get_mut(index).unwrap().pop().unwrap();
                 _paired[i] = j + 1;
                 _paired[j as usize] = (i as i64) + 1;
@@ -218,6 +869,56 @@ impl str::FromStr for SecondaryStructureRecord {
 }
 
 
+/// Returns a vector of paired sites from a dot bracket string representation, as
+/// [from_dotbracketstring](fn.from_dotbracketstring.html) does, but rejecting any character that
+/// is neither a recognised bracket nor `.`, rather than treating it as unpaired.
+///
+/// Returns `BracketTypeNotRecognised` with the offending character and its (1-based) position.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, from_dotbracketstring_strict};
+/// assert_eq!(from_dotbracketstring_strict("((..))").unwrap(), from_dotbracketstring("((..))").unwrap());
+/// assert!(from_dotbracketstring_strict("((*.))").is_err());
+/// ```
+pub fn from_dotbracketstring_strict(dbs: &str) -> Result<Vec<i64>, StructureParseError> {
+    if let Some((i, c)) = dbs.chars().enumerate().find(|(_, c)| *c != '.' && !is_left_bracket(*c) && !is_right_bracket(*c)) {
+        return Err(StructureParseError::BracketTypeNotRecognised { c, pos: Some(i + 1) });
+    }
+    from_dotbracketstring(dbs)
+}
+
+/// The characters used to denote unpaired sites in WUSS notation.
+pub const WUSS_UNPAIRED: &str = ".,:_-~";
+
+/// Returns a vector of paired sites from a WUSS (Washington University Secondary Structure)
+/// notation string, as used for Rfam `SS_cons` lines. The unpaired symbols in
+/// [WUSS_UNPAIRED](constant.WUSS_UNPAIRED.html) are treated as unpaired sites, the `<>`, `()`,
+/// `[]` and `{}` bracket pairs as nested base-pairs, and matching upper and lower case letters
+/// (`Aa`, `Bb`, ...) as pseudoknotted base-pairs.
+///
+/// Returns `BracketTypeNotRecognised` for any other character, with its (1-based) position.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, from_wuss_string};
+/// let paired = from_wuss_string(":::<<<-AA__>>>,,aa::").unwrap();
+/// assert_eq!(paired, from_dotbracketstring("...(((.[[..)))..]]..").unwrap());
+/// ```
+pub fn from_wuss_string(wuss: &str) -> Result<Vec<i64>, StructureParseError> {
+    let mut dbs = String::with_capacity(wuss.len());
+    for (i, c) in wuss.chars().enumerate() {
+        if WUSS_UNPAIRED.contains(c) {
+            dbs.push('.');
+        } else if is_left_bracket(c) || is_right_bracket(c) {
+            dbs.push(c);
+        } else {
+            return Err(StructureParseError::BracketTypeNotRecognised { c, pos: Some(i + 1) });
+        }
+    }
+    from_dotbracketstring(&dbs)
+}
+
 /// Converts a paired sites list representing an arbitarily pseudoknotted secondary structure into
 /// a dot bracket string representation.
 ///
@@ -231,6 +932,29 @@ impl str::FromStr for SecondaryStructureRecord {
 /// assert_eq!(dbs_observed, dbs_expected);
 /// ```
 pub fn get_dot_bracket_string(paired: &dyn PairedSites) -> Result<String, StructureParseError> {
+    get_dot_bracket_string_with(paired, '.')
+}
+
+/// Converts a paired sites list into a dot bracket string representation as
+/// [get_dot_bracket_string](fn.get_dot_bracket_string.html) does, but writing `unpaired` rather
+/// than `.` at each unpaired position, e.g. `-` or a space for formats that expect them.
+///
+/// Returns `UnpairedCharacterIsBracket` if `unpaired` is one of the
+/// [LEFT_BRACKETS](constant.LEFT_BRACKETS.html) or
+/// [RIGHT_BRACKETS](constant.RIGHT_BRACKETS.html), as the string would then be ambiguous.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::get_dot_bracket_string_with;
+/// let paired = vec![6, 5, 0, 0, 2, 1, 0];
+/// assert_eq!(get_dot_bracket_string_with(&paired, '-').unwrap(), "((--))-");
+/// assert!(get_dot_bracket_string_with(&paired, '<').is_err());
+/// ```
+pub fn get_dot_bracket_string_with(paired: &dyn PairedSites, unpaired: char) -> Result<String, StructureParseError> {
+    if is_left_bracket(unpaired) || is_right_bracket(unpaired) {
+        return Err(StructureParseError::UnpairedCharacterIsBracket { c: unpaired });
+    }
     let paired = paired.paired();
 
     let mut stacks: Vec<Vec<i64>> = Vec::new();
@@ -241,7 +965,7 @@ pub fn get_dot_bracket_string(paired: &dyn PairedSites) -> Result<String, Struct
         let i = i as i64;
         let j = *j;
         if j == 0 {
-            dbn += ".";
+            dbn.push(unpaired);
         } else if i < j {
             let mut success = false;
             for (index, left) in LEFT_BRACKETS.chars().enumerate() {
@@ -271,7 +995,202 @@ pub fn get_dot_bracket_string(paired: &dyn PairedSites) -> Result<String, Struct
     Ok(dbn)
 }
 
-/// Returns true if the given secondary structure is pseudoknotted, false otherwise.
+/// Converts a paired sites list representing an arbitarily pseudoknotted secondary structure into
+/// a dot bracket string representation using the fewest bracket types possible.
+/// [get_dot_bracket_string](fn.get_dot_bracket_string.html) (and equally
+/// [pseudoknot_order](fn.pseudoknot_order.html)) assigns each base-pair in 5' order to the first
+/// bracket type it does not cross, which can use more bracket types than necessary, e.g. three
+/// for the 0-based base-pairs (0, 6), (1, 3), (2, 5) and (4, 7), where two suffice.
+///
+/// The base-pairs are instead divided into the fewest pages of mutually non-crossing base-pairs by
+/// backtracking over the [helices](fn.helices.html), whose base-pairs all cross the same
+/// base-pairs, bounded by the greedy assignment. The base-pairs on the k-th page are written using
+/// the k-th bracket type of [LEFT_BRACKETS](constant.LEFT_BRACKETS.html), with pages numbered in
+/// order of their 5'-most helix, so a non-pseudoknotted structure uses only `()`. The search takes
+/// time exponential in the number of helices in a set of mutually crossing helices in the worst
+/// case, but is fast for the pseudoknots found in natural RNAs.
+///
+/// # Examples
+///
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, get_dot_bracket_string_minimal};
+/// let paired = from_dotbracketstring("((..AA..))..aa").unwrap();
+/// assert_eq!(get_dot_bracket_string_minimal(&paired).unwrap(), "((..<<..))..>>");
+///
+/// let paired = vec![7, 4, 6, 2, 8, 3, 1, 5];
+/// assert_eq!(get_dot_bracket_string(&paired).unwrap(), "((<){>)}");
+/// assert_eq!(get_dot_bracket_string_minimal(&paired).unwrap(), "(<(><))>");
+/// ```
+pub fn get_dot_bracket_string_minimal(paired: &dyn PairedSites) -> Result<String, StructureParseError> {
+    let helices = helices(paired).into_iter().filter(|helix| helix[0].1 < paired.paired().len()).collect::<Vec<_>>();
+    let pages = minimum_pages(&helices, assign_pages(paired).len());
+    if pages.iter().any(|page| *page >= LEFT_BRACKETS.chars().count()) {
+        return Err(StructureParseError::InsufficientBracketTypes);
+    }
+
+    let left = LEFT_BRACKETS.chars().collect::<Vec<char>>();
+    let right = RIGHT_BRACKETS.chars().collect::<Vec<char>>();
+    let mut dbn = vec!['.'; paired.paired().len()];
+    for (helix, page) in helices.iter().zip(pages.iter()) {
+        for (i, j) in helix.iter() {
+            dbn[*i] = left[*page];
+            dbn[*j] = right[*page];
+        }
+    }
+    Ok(dbn.into_iter().collect())
+}
+
+/// Returns the page of each helix in a division of the helices into the fewest pages of mutually
+/// non-crossing helices, given an upper bound on the number of pages needed. Each set of helices
+/// connected by crossings is divided separately, trying each number of pages in turn, and a
+/// helix taking a new page is given the lowest page number not yet used.
+fn minimum_pages(helices: &[Vec<(usize, usize)>], upper_bound: usize) -> Vec<usize> {
+    let crosses = |a: usize, b: usize| {
+        let ((i, j), (k, l)) = (helices[a][0], helices[b][0]);
+        (k < i && i < l && l < j) || (i < k && k < j && j < l)
+    };
+    let neighbours = (0..helices.len())
+        .map(|a| (0..helices.len()).filter(|b| *b != a && crosses(a, *b)).collect::<Vec<usize>>())
+        .collect::<Vec<Vec<usize>>>();
+
+    // assigns pages to the helices of a component from `index` onwards, using pages below `k`
+    fn colour(component: &[usize], index: usize, used: usize, k: usize, neighbours: &[Vec<usize>], pages: &mut Vec<Option<usize>>) -> bool {
+        let helix = match component.get(index) {
+            Some(helix) => *helix,
+            None => return true
+        };
+        for page in 0..k.min(used + 1) {
+            if neighbours[helix].iter().all(|other| pages[*other] != Some(page)) {
+                pages[helix] = Some(page);
+                if colour(component, index + 1, used.max(page + 1), k, neighbours, pages) {
+                    return true;
+                }
+            }
+        }
+        pages[helix] = None;
+        false
+    }
+
+    let mut pages: Vec<Option<usize>> = vec![None; helices.len()];
+    let mut seen = vec![false; helices.len()];
+    for start in 0..helices.len() {
+        if seen[start] {
+            continue;
+        }
+        let mut component = vec![start];
+        seen[start] = true;
+        let mut index = 0;
+        while index < component.len() {
+            for other in neighbours[component[index]].iter() {
+                if !seen[*other] {
+                    seen[*other] = true;
+                    component.push(*other);
+                }
+            }
+            index += 1;
+        }
+        component.sort_unstable();
+
+        // the component can always be divided into the pages of the greedy assignment
+        let found = (1..=upper_bound.max(1)).any(|k| colour(&component, 0, 0, k, &neighbours, &mut pages));
+        debug_assert!(found);
+    }
+    pages.into_iter().map(|page| page.unwrap_or(0)).collect()
+}
+
+/// Returns the helices (stems) of a secondary structure, i.e. the maximal runs of stacked
+/// base-pairs `(i, j), (i + 1, j - 1), ...`, ordered by 5' position. Base-pairs are 0-based as in
+/// [base_pairs](fn.base_pairs.html). A bulge or internal loop ends a helix and a lone base-pair
+/// is a helix of length one.
+///
+/// Pseudoknotted structures are accepted: helices are returned regardless of whether they cross.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, helices};
+/// let paired = from_dotbracketstring("((.((...))))").unwrap();
+/// assert_eq!(helices(&paired), vec![vec![(0, 11), (1, 10)], vec![(3, 9), (4, 8)]]);
+/// ```
+pub fn helices(paired: &dyn PairedSites) -> Vec<Vec<(usize, usize)>> {
+    let mut helices: Vec<Vec<(usize, usize)>> = Vec::new();
+    for (i, j) in base_pairs(paired) {
+        match helices.last_mut() {
+            Some(helix) if i > 0 && helix.last() == Some(&(i - 1, j + 1)) => helix.push((i, j)),
+            _ => helices.push(vec![(i, j)])
+        }
+    }
+    helices
+}
+
+/// Returns the lonely (isolated) base-pairs `(i, j)` of a secondary structure, those stacked on
+/// neither side as neither `(i - 1, j + 1)` nor `(i + 1, j - 1)` is a base-pair, i.e. the
+/// [helices](fn.helices.html) of length one. Positions are 0-based and ordered by `i`.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, lonely_pairs};
+/// let paired = from_dotbracketstring("(.((...)).)").unwrap();
+/// assert_eq!(lonely_pairs(&paired), vec![(0, 10)]);
+/// ```
+pub fn lonely_pairs(paired: &dyn PairedSites) -> Vec<(usize, usize)> {
+    helices(paired).into_iter().filter(|helix| helix.len() == 1).map(|helix| helix[0]).collect()
+}
+
+/// Returns a copy of the given secondary structure with its [lonely_pairs](fn.lonely_pairs.html)
+/// set to unpaired. The result has no lonely base-pairs, as every remaining base-pair is stacked
+/// on another remaining base-pair.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, remove_lonely_pairs};
+/// let paired = from_dotbracketstring("(.((...)).)").unwrap();
+/// assert_eq!(get_dot_bracket_string(&remove_lonely_pairs(&paired)).unwrap(), "..((...))..");
+/// ```
+pub fn remove_lonely_pairs(paired: &dyn PairedSites) -> Vec<i64> {
+    let mut result = paired.paired().clone();
+    for (i, j) in lonely_pairs(paired) {
+        result[i] = 0;
+        if let Some(partner) = result.get_mut(j) {
+            *partner = 0;
+        }
+    }
+    result
+}
+
+/// Returns the maximal runs of unpaired positions of a secondary structure as 0-based half-open
+/// ranges, ordered by 5' position. Pseudoknotted structures are accepted.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, unpaired_regions};
+/// let paired = from_dotbracketstring("..((...))[[.]]..").unwrap();
+/// assert_eq!(unpaired_regions(&paired), vec![0..2, 4..7, 11..12, 14..16]);
+/// ```
+pub fn unpaired_regions(paired: &dyn PairedSites) -> Vec<Range<usize>> {
+    let paired = paired.paired();
+
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, j) in paired.iter().enumerate() {
+        match (*j == 0, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                regions.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        regions.push(s..paired.len());
+    }
+    regions
+}
+
+/// Returns true if the given secondary structure is pseudoknotted, i.e. if any two of its
+/// base-pairs cross, false otherwise, in O(n) time. Returns `InconsistentPairing` if the paired
+/// sites list is not a valid matching, see
+/// [validate](struct.SecondaryStructureRecord.html#method.validate).
 ///
 /// # Examples
 /// ```rust
@@ -285,29 +1204,681 @@ pub fn get_dot_bracket_string(paired: &dyn PairedSites) -> Result<String, Struct
 /// ```
 pub fn is_pseudoknotted(paired: &dyn PairedSites) -> Result<bool, StructureParseError> {
     let paired = paired.paired();
+    validate_paired(paired)?;
 
-    let mut stack: Vec<i64> = Vec::new();
+    // the base-pairs are nested if and only if each closes the most recently opened base-pair
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, j) in paired.iter().enumerate() {
+        let j = *j as usize;
+        if j > i + 1 {
+            stack.push(i);
+        } else if j > 0 && stack.pop() != Some(j - 1) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
 
+/// Checks that every paired site of a 1-based paired sites list has a partner within the list
+/// that is paired back to it, returning `InconsistentPairing` for the first site that does not.
+fn validate_paired(paired: &[i64]) -> Result<(), StructureParseError> {
+    let len = paired.len() as i64;
     for (i, j) in paired.iter().enumerate() {
-        let i = i as i64;
         let j = *j;
-        if j == 0 {} else if i < j {
-            if !stack.is_empty() && j >= *stack.last().unwrap() {
-                return Ok(true);
-            } else {
-                stack.push(j);
+        if j == 0 {
+            continue;
+        }
+        if j < 1 || j > len || j == (i as i64) + 1 || paired[(j - 1) as usize] != (i as i64) + 1 {
+            return Err(StructureParseError::InconsistentPairing { i: i + 1, j: j.max(0) as usize });
+        }
+    }
+    Ok(())
+}
+
+/// Returns every pair of crossing base-pairs `((a, b), (c, d))` of the given secondary structure,
+/// where `a < c < b < d` and positions are 0-based. The result is ordered by `a` and then by `c`,
+/// and is empty if the structure is not pseudoknotted.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{crossing_pairs, from_dotbracketstring};
+/// let paired = from_dotbracketstring("(.[.).]").unwrap();
+/// assert_eq!(crossing_pairs(&paired), vec![((0, 4), (2, 6))]);
+/// ```
+pub fn crossing_pairs(paired: &dyn PairedSites) -> Vec<((usize, usize), (usize, usize))> {
+    let pairs = base_pairs(paired);
+
+    let mut crossing = Vec::new();
+    for (index, (a, b)) in pairs.iter().enumerate() {
+        for (c, d) in pairs[index + 1..].iter() {
+            if c < b && b < d {
+                crossing.push(((*a, *b), (*c, *d)));
             }
-        } else if !stack.is_empty() {
+        }
+    }
+    crossing
+}
+
+/// Returns the pseudoknot order of the given secondary structure, i.e. the number of non-crossing
+/// pages needed to draw every base-pair. Base-pairs are taken in order of their 5' position and
+/// each is assigned to the first page on which it crosses no other base-pair. A structure without
+/// crossing base-pairs, including one without any base-pairs, has order 1.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, pseudoknot_order};
+/// assert_eq!(pseudoknot_order(&from_dotbracketstring("((..))..((...))").unwrap()), 1);
+/// assert_eq!(pseudoknot_order(&from_dotbracketstring("((..[[..))..]]").unwrap()), 2);
+/// assert_eq!(pseudoknot_order(&from_dotbracketstring("(.[.{.).].}").unwrap()), 3);
+/// ```
+pub fn pseudoknot_order(paired: &dyn PairedSites) -> usize {
+    assign_pages(paired).len().max(1)
+}
+
+/// Returns true if every base-pair of the given secondary structure can be written in dot bracket
+/// notation, i.e. if its [pseudoknot_order](fn.pseudoknot_order.html) does not exceed the number
+/// of bracket types in [LEFT_BRACKETS](constant.LEFT_BRACKETS.html). Otherwise
+/// [get_dot_bracket_string](fn.get_dot_bracket_string.html) returns `InsufficientBracketTypes`.
+/// Formats that store partner positions directly, such as CT and BPSEQ, can represent any
+/// secondary structure.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, is_pseudoknot_safe};
+/// assert!(is_pseudoknot_safe(&from_dotbracketstring("(.[.{.).].}").unwrap()));
+/// // 31 mutually crossing base-pairs need one more bracket type than is available
+/// let paired = (0..62).map(|i| if i < 31 { i + 32 } else { i - 30 }).collect::<Vec<i64>>();
+/// assert!(!is_pseudoknot_safe(&paired));
+/// ```
+pub fn is_pseudoknot_safe(paired: &dyn PairedSites) -> bool {
+    pseudoknot_order(paired) <= LEFT_BRACKETS.chars().count()
+}
+
+/// Assigns each base-pair, in order of its 5' position, to the first page on which it crosses no
+/// other base-pair, and returns the pages.
+pub(crate) fn assign_pages(paired: &dyn PairedSites) -> Vec<Vec<(usize, usize)>> {
+    let crosses = |(i, j): &(usize, usize), (k, l): &(usize, usize)| (k < i && i < l && l < j) || (i < k && k < j && j < l);
+
+    let mut pages: Vec<Vec<(usize, usize)>> = Vec::new();
+    for pair in base_pairs(paired).into_iter().filter(|(_, j)| *j < paired.paired().len()) {
+        match pages.iter_mut().find(|page| !page.iter().any(|other| crosses(&pair, other))) {
+            Some(page) => page.push(pair),
+            None => pages.push(vec![pair])
+        }
+    }
+    pages
+}
+
+/// Returns a pseudoknot-free version of the given secondary structure, retaining a maximum
+/// cardinality set of mutually non-crossing base-pairs and setting all other sites to unpaired.
+///
+/// The set is found by dynamic programming over intervals in O(n^2) time and memory. Ties are
+/// broken deterministically by keeping the base-pair with the 5'-most opening position.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, remove_pseudoknots};
+/// let paired = from_dotbracketstring("(((..[[..)))..]]").unwrap();
+/// assert_eq!(get_dot_bracket_string(&remove_pseudoknots(&paired)).unwrap(), "(((......)))....");
+/// ```
+pub fn remove_pseudoknots(paired: &dyn PairedSites) -> Vec<i64> {
+    let paired = paired.paired();
+    let len = paired.len();
+    let partner = |i: usize| -> Option<usize> {
+        let j = paired[i];
+        if j > (i as i64) + 1 && j as usize <= len { Some((j - 1) as usize) } else { None }
+    };
+
+    // best[i][j] is the maximum number of non-crossing base-pairs within the half-open interval i..j
+    let mut best = vec![vec![0usize; len + 1]; len + 1];
+    for i in (0..len).rev() {
+        for j in i + 1..=len {
+            best[i][j] = best[i + 1][j];
+            if let Some(k) = partner(i) {
+                if k < j {
+                    best[i][j] = best[i][j].max(1 + best[i + 1][k] + best[k + 1][j]);
+                }
+            }
+        }
+    }
+
+    let mut result = vec![0; len];
+    let mut intervals = vec![(0, len)];
+    while let Some((i, j)) = intervals.pop() {
+        if i >= j {
+            continue;
+        }
+        match partner(i) {
+            Some(k) if k < j && best[i][j] == 1 + best[i + 1][k] + best[k + 1][j] => {
+                result[i] = (k + 1) as i64;
+                result[k] = (i + 1) as i64;
+                intervals.push((i + 1, k));
+                intervals.push((k + 1, j));
+            }
+            _ => intervals.push((i + 1, j))
+        }
+    }
+    result
+}
+
+/// Returns the record with the lowest free energy, or `None` if no record has an energy.
+/// Records without an energy, or whose energy is NaN, are ignored and ties return the first record
+/// encountered.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{mfe_structure, SecondaryStructureRecord};
+/// let mut records: Vec<SecondaryStructureRecord> = vec!["((..))".parse().unwrap(), "(....)".parse().unwrap()];
+/// assert!(mfe_structure(&records).is_none());
+/// records[0].energy = Some(-1.2);
+/// records[1].energy = Some(-3.4);
+/// assert_eq!(mfe_structure(&records).unwrap().paired, records[1].paired);
+/// ```
+pub fn mfe_structure(records: &[SecondaryStructureRecord]) -> Option<&SecondaryStructureRecord> {
+    let mut mfe: Option<&SecondaryStructureRecord> = None;
+    for record in records {
+        if let Some(energy) = record.energy.filter(|energy| !energy.is_nan()) {
+            match mfe {
+                Some(best) if best.energy.unwrap() <= energy => {}
+                _ => mfe = Some(record),
+            }
+        }
+    }
+    mfe
+}
+
+/// Returns all records with a free energy within `delta` kcal/mol of the minimum free energy
+/// record (see [mfe_structure](fn.mfe_structure.html)), in their original order. Records without
+/// an energy, or whose energy is NaN, are never included.
+pub fn structures_within(records: &[SecondaryStructureRecord], delta: f64) -> Vec<&SecondaryStructureRecord> {
+    match mfe_structure(records).and_then(|mfe| mfe.energy) {
+        Some(mfe) => records.iter().filter(|record| match record.energy {
+            Some(energy) => energy <= mfe + delta,
+            None => false
+        }).collect(),
+        None => Vec::new()
+    }
+}
+
+/// Returns true if the base-pairs of the given secondary structure can be drawn as non-crossing
+/// arcs above the sequence.
+///
+/// `strand_breaks` lists the 0-based positions at which a new strand begins in a multi-strand
+/// complex (e.g. the position following each `&`). Base-pairs whose partners lie on different
+/// strands are not counted as crossing, so only intramolecular pairs are checked. For a single
+/// strand (`&[]`) this is equivalent to the structure not being pseudoknotted.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, is_planar};
+/// let nested = from_dotbracketstring("((..))..((...))").unwrap();
+/// assert!(is_planar(&nested, &[]));
+/// let pseudoknotted = from_dotbracketstring("((..[[..))..]]").unwrap();
+/// assert!(!is_planar(&pseudoknotted, &[]));
+/// // the crossing pairs (0, 8) and (4, 12) span the strand break at position 6
+/// assert!(is_planar(&from_dotbracketstring("(...[...)...]").unwrap(), &[6]));
+/// ```
+pub fn is_planar(paired: &dyn PairedSites, strand_breaks: &[usize]) -> bool {
+    let paired = paired.paired();
+    let strand = |i: usize| strand_breaks.iter().filter(|b| **b <= i).count();
+
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, j) in paired.iter().enumerate() {
+        if *j <= 0 || *j as usize > paired.len() || *j as usize == i + 1 {
+            continue;
+        }
+        let j = (*j - 1) as usize;
+        if strand(i) != strand(j) {
+            continue;
+        }
+        if i < j {
+            stack.push(i);
+        } else if stack.last() == Some(&j) {
             stack.pop();
         } else {
-            return Err(StructureParseError::InputConsumed);
+            return false;
         }
     }
+    true
+}
+
+/// A structural element (loop) of a non-pseudoknotted secondary structure. All positions are
+/// 0-based and base-pairs are given as `(i, j)` with `i < j`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Loop {
+    /// A loop closed by a single base-pair that encloses no other base-pairs.
+    Hairpin {
+        /// The base-pair closing the hairpin.
+        closing: (usize, usize),
+        /// The unpaired positions enclosed by the closing pair.
+        unpaired: Vec<usize>,
+    },
+    /// Two base-pairs stacked directly on top of each other.
+    Stack {
+        /// The outer base-pair.
+        outer: (usize, usize),
+        /// The inner base-pair, `(outer.0 + 1, outer.1 - 1)`.
+        inner: (usize, usize),
+    },
+    /// Two base-pairs separated by unpaired positions on one side only.
+    Bulge {
+        /// The outer base-pair.
+        outer: (usize, usize),
+        /// The inner base-pair.
+        inner: (usize, usize),
+        /// The unpaired positions between the two base-pairs.
+        unpaired: Vec<usize>,
+    },
+    /// Two base-pairs separated by unpaired positions on both sides.
+    Internal {
+        /// The outer base-pair.
+        outer: (usize, usize),
+        /// The inner base-pair.
+        inner: (usize, usize),
+        /// The unpaired positions between the two base-pairs.
+        unpaired: Vec<usize>,
+    },
+    /// A loop closed by a single base-pair that encloses two or more branching base-pairs.
+    Multiloop {
+        /// The base-pair closing the multiloop.
+        closing: (usize, usize),
+        /// The base-pairs of the helices branching from the multiloop, excluding the closing pair.
+        branches: Vec<(usize, usize)>,
+        /// The unpaired positions within the multiloop.
+        unpaired: Vec<usize>,
+    },
+    /// The loop that is not closed by any base-pair and contains the 5' and 3' ends.
+    External {
+        /// The outermost base-pairs.
+        branches: Vec<(usize, usize)>,
+        /// The unpaired positions not enclosed by any base-pair.
+        unpaired: Vec<usize>,
+    },
+}
 
-    if !stack.is_empty() {
-        return Err(StructureParseError::InputNotConsumed);
+/// Returns the base-pairs (as 0-based `(i, j)` with `i < j`) and the unpaired positions that lie
+/// directly within the interval `start..end` of a non-pseudoknotted structure, i.e. those not
+/// enclosed by another base-pair within the interval.
+pub(crate) fn enclosed_elements(paired: &[i64], start: usize, end: usize) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut branches = Vec::new();
+    let mut unpaired = Vec::new();
+    let mut k = start;
+    while k < end {
+        let j = paired[k];
+        if j > (k as i64) + 1 && (j as usize) <= end {
+            branches.push((k, (j - 1) as usize));
+            k = j as usize;
+        } else {
+            unpaired.push(k);
+            k += 1;
+        }
     }
+    (branches, unpaired)
+}
 
+/// Decomposes a non-pseudoknotted secondary structure into its loops: the external loop followed
+/// by the loop closed by each base-pair, in order of the 5' position of the closing pair.
+///
+/// Returns [StructureParseError::Pseudoknotted](enum.StructureParseError.html) if the structure
+/// contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{decompose_loops, from_dotbracketstring, Loop};
+/// let loops = decompose_loops(&from_dotbracketstring(".((.(...)))").unwrap()).unwrap();
+/// assert_eq!(loops, vec![
+///     Loop::External { branches: vec![(1, 10)], unpaired: vec![0] },
+///     Loop::Stack { outer: (1, 10), inner: (2, 9) },
+///     Loop::Bulge { outer: (2, 9), inner: (4, 8), unpaired: vec![3] },
+///     Loop::Hairpin { closing: (4, 8), unpaired: vec![5, 6, 7] },
+/// ]);
+/// ```
+pub fn decompose_loops(paired: &dyn PairedSites) -> Result<Vec<Loop>, StructureParseError> {
+    if !is_planar(paired, &[]) {
+        return Err(StructureParseError::Pseudoknotted);
+    }
+    let paired = paired.paired();
 
-    Ok(false)
-}
\ No newline at end of file
+    let (branches, unpaired) = enclosed_elements(paired, 0, paired.len());
+    let mut loops = vec![Loop::External { branches, unpaired }];
+    for (i, j) in paired.iter().enumerate() {
+        if *j <= (i as i64) + 1 || *j as usize > paired.len() {
+            continue;
+        }
+        let closing = (i, (*j - 1) as usize);
+        let (mut branches, unpaired) = enclosed_elements(paired, closing.0 + 1, closing.1);
+        loops.push(match branches.len() {
+            0 => Loop::Hairpin { closing, unpaired },
+            1 => {
+                let inner = branches.pop().unwrap();
+                let left = inner.0 - closing.0 - 1;
+                let right = closing.1 - inner.1 - 1;
+                if left == 0 && right == 0 {
+                    Loop::Stack { outer: closing, inner }
+                } else if left == 0 || right == 0 {
+                    Loop::Bulge { outer: closing, inner, unpaired }
+                } else {
+                    Loop::Internal { outer: closing, inner, unpaired }
+                }
+            }
+            _ => Loop::Multiloop { closing, branches, unpaired }
+        });
+    }
+    Ok(loops)
+}
+
+/// Converts a non-pseudoknotted secondary structure into a WUSS notation string, as used by
+/// Infernal and Rfam. Base-pairs are written as `<>` and each unpaired position according to the
+/// loop containing it, see [decompose_loops](fn.decompose_loops.html): `:` in the external loop,
+/// `_` in hairpin loops, `-` in bulges and internal loops, and `,` in multiloops.
+///
+/// Returns [StructureParseError::Pseudoknotted](enum.StructureParseError.html) if the structure
+/// contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, from_wuss_string, get_wuss_string};
+/// let paired = from_dotbracketstring("..((.((...))..((...)))).").unwrap();
+/// let wuss = get_wuss_string(&paired).unwrap();
+/// assert_eq!(wuss, "::<<,<<___>>,,<<___>>>>:");
+/// assert_eq!(from_wuss_string(&wuss).unwrap(), paired);
+/// ```
+pub fn get_wuss_string(paired: &dyn PairedSites) -> Result<String, StructureParseError> {
+    let mut wuss = paired.paired().iter().enumerate()
+        .map(|(i, j)| if *j == 0 { ':' } else if *j > (i as i64) + 1 { '<' } else { '>' })
+        .collect::<Vec<char>>();
+    for l in decompose_loops(paired)? {
+        let (unpaired, c) = match l {
+            Loop::Hairpin { unpaired, .. } => (unpaired, '_'),
+            Loop::Bulge { unpaired, .. } | Loop::Internal { unpaired, .. } => (unpaired, '-'),
+            Loop::Multiloop { unpaired, .. } => (unpaired, ','),
+            _ => continue
+        };
+        for k in unpaired {
+            wuss[k] = c;
+        }
+    }
+    Ok(wuss.into_iter().collect())
+}
+
+/// Returns the abstract shape (Giegerich et al., 2004) of a non-pseudoknotted secondary structure
+/// at the given level of abstraction, from 1 (the most detailed) to 5 (the most abstract). Each
+/// helix is written as `[` and `]` and unpaired regions as `_`:
+///
+/// * Level 1: every unpaired region.
+/// * Level 2: unpaired regions in the external loop and multiloops only.
+/// * Level 3: no unpaired regions.
+/// * Level 4: as level 2, but helices interrupted only by bulges and internal loops are merged.
+/// * Level 5: as level 3, but helices interrupted only by bulges and internal loops are merged,
+///   leaving only the nesting of the helices.
+///
+/// Returns [StructureParseError::Pseudoknotted](enum.StructureParseError.html) if the structure
+/// contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{abstract_shape, from_dotbracketstring};
+/// let paired = from_dotbracketstring("((((...))..))..((...))").unwrap();
+/// assert_eq!(abstract_shape(&paired, 1).unwrap(), "[[_]_]_[_]");
+/// assert_eq!(abstract_shape(&paired, 3).unwrap(), "[[]][]");
+/// assert_eq!(abstract_shape(&paired, 5).unwrap(), "[][]");
+/// ```
+pub fn abstract_shape(paired: &dyn PairedSites, level: u8) -> Result<String, StructureParseError> {
+    if !(1..=5).contains(&level) {
+        return Err(StructureParseError::ShapeLevelNotRecognised { level });
+    }
+    if !is_planar(paired, &[]) {
+        return Err(StructureParseError::Pseudoknotted);
+    }
+    let paired = paired.paired();
+
+    let mut shape = String::new();
+    shape_of_region(paired, 0, paired.len(), level, level != 3 && level != 5, &mut shape);
+    Ok(shape)
+}
+
+/// Appends the abstract shape of the elements directly within the interval `start..end` of a
+/// non-pseudoknotted structure.
+fn shape_of_region(paired: &[i64], start: usize, end: usize, level: u8, show_unpaired: bool, shape: &mut String) {
+    let mut k = start;
+    let mut in_unpaired_region = false;
+    while k < end {
+        let j = paired[k];
+        if j > (k as i64) + 1 && (j as usize) <= end {
+            shape_of_helix(paired, k, (j - 1) as usize, level, shape);
+            k = j as usize;
+            in_unpaired_region = false;
+        } else {
+            if show_unpaired && !in_unpaired_region {
+                shape.push('_');
+            }
+            in_unpaired_region = true;
+            k += 1;
+        }
+    }
+}
+
+/// Appends the abstract shape of the helix closed by the base-pair `(i, j)`.
+fn shape_of_helix(paired: &[i64], i: usize, j: usize, level: u8, shape: &mut String) {
+    let (mut i, mut j) = (i, j);
+    shape.push('[');
+    loop {
+        while i + 1 < j - 1 && paired[i + 1] == j as i64 {
+            i += 1;
+            j -= 1;
+        }
+        let (branches, _) = enclosed_elements(paired, i + 1, j);
+        if level >= 4 && branches.len() == 1 {
+            // merge the helices either side of a bulge or internal loop
+            i = branches[0].0;
+            j = branches[0].1;
+            continue;
+        }
+        let show_unpaired = level == 1 || (branches.len() >= 2 && level != 3 && level != 5);
+        shape_of_region(paired, i + 1, j, level, show_unpaired, shape);
+        break;
+    }
+    shape.push(']');
+}
+
+/// Returns the base-pairs (0-based `(i, j)` with `i < j`) closing the helices that branch from
+/// each multiloop, grouped by multiloop and ordered by the 5' position of the multiloop's closing
+/// pair. The closing pair of the multiloop itself is not included, only the branch helices.
+///
+/// Returns [StructureParseError::Pseudoknotted](enum.StructureParseError.html) if the structure
+/// contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, multiloop_branch_points};
+/// let paired = from_dotbracketstring("((..((...))..((...))..))").unwrap();
+/// assert_eq!(multiloop_branch_points(&paired).unwrap(), vec![vec![(4, 10), (13, 19)]]);
+/// ```
+pub fn multiloop_branch_points(paired: &dyn PairedSites) -> Result<Vec<Vec<(usize, usize)>>, StructureParseError> {
+    Ok(decompose_loops(paired)?.into_iter().filter_map(|l| match l {
+        Loop::Multiloop { branches, .. } => Some(branches),
+        _ => None
+    }).collect())
+}
+
+/// Returns true if the base-pair between the 0-based positions `i` and `j` can be added to the
+/// given secondary structure without introducing a crossing with any existing base-pair.
+///
+/// Returns false (rather than an error) for invalid candidates: if `i >= j`, if `j` is out of
+/// range, if fewer than `mingap` unpaired nucleotides would separate `i` and `j`, or if either
+/// position is already paired.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{can_add_pair_nested, from_dotbracketstring};
+/// let paired = from_dotbracketstring("..((...))..").unwrap();
+/// assert!(can_add_pair_nested(&paired, 1, 9, 3));
+/// assert!(!can_add_pair_nested(&paired, 0, 5, 3)); // would cross (2, 8) and (3, 7)
+/// assert!(!can_add_pair_nested(&paired, 9, 10, 3)); // too few unpaired nucleotides in between
+/// assert!(!can_add_pair_nested(&paired, 2, 10, 3)); // position 2 is already paired
+/// ```
+pub fn can_add_pair_nested(paired: &dyn PairedSites, i: usize, j: usize, mingap: usize) -> bool {
+    let paired = paired.paired();
+    if i >= j || j >= paired.len() || j - i - 1 < mingap || paired[i] != 0 || paired[j] != 0 {
+        return false;
+    }
+    paired.iter().enumerate().all(|(k, l)| {
+        let l = *l - 1;
+        // a pair (k, l) crosses (i, j) if exactly one of its partners lies between i and j
+        l < 0 || ((i < k && k < j) == (i < l as usize && (l as usize) < j))
+    })
+}
+
+/// Returns the signature of the stem closed by the base-pair `(i, j)`: its length in base-pairs
+/// (following the stem through any bulges and internal loops) and the signatures of the stems
+/// branching from it.
+fn stem_signature(paired: &[i64], closing: (usize, usize)) -> String {
+    let (mut i, mut j) = closing;
+    let mut len = 1;
+    loop {
+        let (branches, _) = enclosed_elements(paired, i + 1, j);
+        if branches.len() == 1 {
+            len += 1;
+            i = branches[0].0;
+            j = branches[0].1;
+        } else {
+            let children = branches.iter().map(|pair| stem_signature(paired, *pair)).collect::<String>();
+            return format!("[{}{}]", len, children);
+        }
+    }
+}
+
+/// Returns a canonical signature of a non-pseudoknotted secondary structure for grouping
+/// structures with the same gross topology and stem sizes.
+///
+/// The signature is the level 5 abstract shape (the nesting of stems, ignoring unpaired
+/// nucleotides, bulges, and internal loops) with the number of base-pairs of each stem written
+/// after its opening bracket. A structure with no base-pairs has the signature `_`.
+///
+/// Returns [StructureParseError::Pseudoknotted](enum.StructureParseError.html) if the structure
+/// contains crossing base-pairs.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, structure_signature};
+/// let paired = from_dotbracketstring("..((...))...(((....)))").unwrap();
+/// assert_eq!(structure_signature(&paired).unwrap(), "[2][3]");
+/// let paired = from_dotbracketstring("((.((..((...))..((...)).)).))").unwrap();
+/// assert_eq!(structure_signature(&paired).unwrap(), "[4[2][2]]");
+/// ```
+pub fn structure_signature(paired: &dyn PairedSites) -> Result<String, StructureParseError> {
+    if !is_planar(paired, &[]) {
+        return Err(StructureParseError::Pseudoknotted);
+    }
+    let paired = paired.paired();
+    let (branches, _) = enclosed_elements(paired, 0, paired.len());
+    if branches.is_empty() {
+        return Ok("_".to_string());
+    }
+    Ok(branches.iter().map(|pair| stem_signature(paired, *pair)).collect())
+}
+
+/// Returns true if the two nucleotides can form a canonical base-pair, i.e. a Watson-Crick
+/// (A-U, G-C) or G-U wobble pair. Nucleotides are case-insensitive and T is treated as U.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::is_canonical_pair;
+/// assert!(is_canonical_pair('G', 'C'));
+/// assert!(is_canonical_pair('t', 'a'));
+/// assert!(is_canonical_pair('U', 'G'));
+/// assert!(!is_canonical_pair('A', 'G'));
+/// assert!(!is_canonical_pair('-', 'C'));
+/// ```
+pub fn is_canonical_pair(a: char, b: char) -> bool {
+    matches!((normalise_nucleotide(a), normalise_nucleotide(b)), ('A', 'U') | ('U', 'A') | ('G', 'C') | ('C', 'G') | ('G', 'U') | ('U', 'G'))
+}
+
+/// Returns the nucleotide in uppercase, with T replaced by U, so that DNA and RNA nucleotides of
+/// either case can be compared.
+pub(crate) fn normalise_nucleotide(c: char) -> char {
+    match c.to_ascii_uppercase() {
+        'T' => 'U',
+        c => c
+    }
+}
+
+/// Returns the number of secondary structures that are one base-pair move away from the given
+/// structure, i.e. its degree in the move graph. A move either removes an existing base-pair or
+/// adds a base-pair permitted by [can_add_pair_nested](fn.can_add_pair_nested.html) with the
+/// given `mingap`. Only non-pseudoknotted moves are counted: additions that would introduce a
+/// crossing base-pair are not allowed.
+///
+/// Each candidate addition is checked in O(n) time, giving O(n^3) time overall.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, neighbor_count};
+/// // removing (1, 5), or adding (0, 6) enclosing it, every other addition would cross (1, 5)
+/// let paired = from_dotbracketstring(".(...).").unwrap();
+/// assert_eq!(neighbor_count(&paired, 3), 2);
+/// ```
+pub fn neighbor_count(paired: &dyn PairedSites, mingap: usize) -> usize {
+    let len = paired.paired().len();
+    let removals = base_pairs(paired).len();
+    let mut additions = 0;
+    for i in 0..len {
+        for j in i + 1..len {
+            if can_add_pair_nested(paired, i, j, mingap) {
+                additions += 1;
+            }
+        }
+    }
+    removals + additions
+}
+
+/// Strips the leading and trailing unpaired positions from a structure, returning the trimmed
+/// structure together with the number of positions removed from the 5' and 3' ends. Base-pairs
+/// are renumbered to the trimmed coordinates. Only unpaired positions are removed, so no
+/// base-pair is broken by trimming. A structure with no base-pairs trims to an empty structure,
+/// with all positions counted as 5' trim.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, get_dot_bracket_string, trim_terminal_unpaired};
+/// let paired = from_dotbracketstring("..((...))...").unwrap();
+/// let (trimmed, five_prime, three_prime) = trim_terminal_unpaired(&paired);
+/// assert_eq!(get_dot_bracket_string(&trimmed).unwrap(), "((...))");
+/// assert_eq!((five_prime, three_prime), (2, 3));
+/// ```
+pub fn trim_terminal_unpaired(paired: &dyn PairedSites) -> (Vec<i64>, usize, usize) {
+    let paired = paired.paired();
+    let five_prime = match paired.iter().position(|j| *j != 0) {
+        Some(first) => first,
+        None => return (Vec::new(), paired.len(), 0)
+    };
+    let three_prime = paired.iter().rev().position(|j| *j != 0).unwrap_or(0);
+    let offset = five_prime as i64;
+    let trimmed = paired[five_prime..paired.len() - three_prime].iter()
+        .map(|j| if *j == 0 { 0 } else { *j - offset })
+        .collect();
+    (trimmed, five_prime, three_prime)
+}
+
+/// Returns the base-pairs (0-based `(i, j)` with `i < j`) whose nucleotides form a G-U or U-G
+/// wobble pair. Nucleotides are case-insensitive and T is treated as U. If the sequence is shorter
+/// than the structure, base-pairs involving a position past the end of the sequence are skipped.
+///
+/// # Examples
+/// ```rust
+/// use rna_secondary_structure::secondary_structure::{from_dotbracketstring, SecondaryStructureRecord, wobble_pairs};
+/// let mut ss = SecondaryStructureRecord::new(from_dotbracketstring("((...))").unwrap());
+/// ss.sequence = "GGAAACT".to_string();
+/// assert_eq!(wobble_pairs(&ss), vec![(0, 6)]);
+/// ```
+pub fn wobble_pairs(ss: &SecondaryStructureRecord) -> Vec<(usize, usize)> {
+    let sequence = ss.sequence.chars().map(normalise_nucleotide).collect::<Vec<char>>();
+    ss.base_pairs().into_iter()
+        .filter(|(i, j)| *j < sequence.len() && matches!((sequence[*i], sequence[*j]), ('G', 'U') | ('U', 'G')))
+        .collect()
+}
+
+/// Returns the number of G-U wobble base-pairs, see [wobble_pairs](fn.wobble_pairs.html).
+pub fn num_wobble_pairs(ss: &SecondaryStructureRecord) -> usize {
+    wobble_pairs(ss).len()
+}
@@ -0,0 +1,115 @@
+extern crate rna_secondary_structure;
+
+use num_bigint::BigUint;
+
+use crate::rna_secondary_structure::combinatorics::*;
+
+#[test]
+fn test_count_all_pairings() {
+    // the telephone numbers: every matching of n nucleotides, enumerated by hand
+    let expected: Vec<u32> = vec![1, 1, 2, 4, 10, 26, 76];
+    for (n, count) in expected.iter().enumerate() {
+        assert_eq!(count_all_pairings(n as i64, 0), BigUint::from(*count));
+    }
+
+    // n=4, mingap=1: none, (1,3), (1,4), (2,4), and the pseudoknot (1,3) with (2,4)
+    assert_eq!(count_all_pairings(4, 1), BigUint::from(5u32));
+    assert_eq!(count_structures(4, 1), BigUint::from(4u32));
+
+    // n=6, mingap=3: none, (1,5), (1,6), (2,6), and the pseudoknot (1,5) with (2,6)
+    assert_eq!(count_all_pairings(6, 3), BigUint::from(5u32));
+    assert_eq!(count_structures(6, 3), BigUint::from(4u32));
+
+    assert_eq!(count_all_pairings(-1, 0), BigUint::from(1u32));
+
+    // a negative mingap is treated as 0
+    for n in 0..8 {
+        assert_eq!(count_all_pairings(n, -2), count_all_pairings(n, 0));
+    }
+}
+
+#[test]
+fn test_count_all_pairings_at_least_count_structures() {
+    for mingap in 0..4 {
+        for n in 0..12 {
+            assert!(count_all_pairings(n, mingap) >= count_structures(n, mingap));
+        }
+    }
+}
+
+#[test]
+fn test_random_structure_uniform() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+
+    let mut rng = StdRng::seed_from_u64(42);
+    // a negative mingap is treated as 0
+    for (n, mingap) in [(5, 0), (7, 2), (4, -1)].iter() {
+        let total: u32 = count_structures(*n, *mingap).to_string().parse().unwrap();
+        let draws = 1000 * total;
+        let mut counts: HashMap<Vec<i64>, u32> = HashMap::new();
+        for _ in 0..draws {
+            let paired = random_structure(*n, *mingap, &mut rng);
+            for (i, j) in paired.iter().enumerate() {
+                if *j > 0 {
+                    assert!((*j - 1 - i as i64).abs() > (*mingap).max(0));
+                    assert_eq!(paired[(*j - 1) as usize], i as i64 + 1);
+                }
+            }
+            *counts.entry(paired).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len() as u32, total);
+        for count in counts.values() {
+            assert!(*count > 850 && *count < 1150, "count {} is far from 1000", count);
+        }
+    }
+}
+
+#[test]
+fn test_enumerate_structures() {
+    use std::collections::HashSet;
+
+    for mingap in 0..4 {
+        for n in 0..10 {
+            let structures = enumerate_structures(n, mingap);
+            assert_eq!(BigUint::from(structures.len()), count_structures(n, mingap));
+            let distinct: HashSet<&Vec<i64>> = structures.iter().collect();
+            assert_eq!(distinct.len(), structures.len());
+        }
+    }
+    // a negative mingap is treated as 0
+    for mingap in -3..0 {
+        for n in 0..8 {
+            assert_eq!(enumerate_structures(n, mingap), enumerate_structures(n, 0));
+        }
+    }
+    assert_eq!(enumerate_structures(0, 0), vec![Vec::<i64>::new()]);
+    assert_eq!(enumerate_structures(3, 0), vec![vec![0, 0, 0], vec![2, 1, 0], vec![3, 0, 1], vec![0, 3, 2]]);
+}
+
+#[test]
+fn test_count_structures_with_cache() {
+    use std::collections::HashMap;
+
+    let mut cache1 = HashMap::new();
+    let mut cache2 = HashMap::new();
+    for mingap in 0..4 {
+        for n in (0..40).rev() {
+            let count = count_structures(n, mingap);
+            assert_eq!(count_structures_with_cache(n, mingap, &mut cache1), count);
+            assert_eq!(count_structures_with_cache(n, mingap, &mut cache2), count);
+        }
+    }
+    assert_eq!(cache1, cache2);
+    assert_eq!(count_structures_with_cache(-1, 0, &mut HashMap::new()), BigUint::from(1u32));
+
+    // a negative mingap is treated as 0 by both
+    for mingap in -3..0 {
+        for n in 0..10 {
+            let count = count_structures(n, mingap);
+            assert_eq!(count, count_structures(n, 0));
+            assert_eq!(count_structures_with_cache(n, mingap, &mut cache1), count);
+        }
+    }
+}
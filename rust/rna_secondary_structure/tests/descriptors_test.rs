@@ -0,0 +1,61 @@
+extern crate rna_secondary_structure;
+
+use crate::rna_secondary_structure::descriptors::{compute_descriptors, StructureDescriptors};
+use crate::rna_secondary_structure::distance_metrics::get_mountain_vector;
+use crate::rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+
+#[test]
+fn test_compute_descriptors() {
+    let mut ss1: SecondaryStructureRecord = "((..[[..))..]]".parse().unwrap();
+    ss1.name = "pseudoknot".to_string();
+    ss1.set_sequence("GCAAGGNNGCAACC".to_string());
+    let ss2: SecondaryStructureRecord = "(((...)))..((.....))".parse().unwrap();
+    let ss3: SecondaryStructureRecord = "....".parse().unwrap();
+
+    let descriptors = compute_descriptors(&[ss1, ss2, ss3]);
+    assert_eq!(descriptors.len(), 3);
+
+    assert_eq!(descriptors[0].name, "pseudoknot");
+    assert_eq!(descriptors[0].num_pairs, 4);
+    assert_eq!(descriptors[0].num_helices, 2);
+    assert!(descriptors[0].pseudoknotted);
+    assert_eq!(descriptors[0].page_number, 2);
+    assert_eq!(descriptors[0].gc_content, 8.0 / 12.0);
+
+    assert_eq!(descriptors[1].length, 20);
+    assert_eq!(descriptors[1].num_helices, 2);
+    assert_eq!(descriptors[1].max_nesting_depth, 3);
+    assert_eq!(descriptors[1].mountain_area, get_mountain_vector(&"(((...)))..((.....))".parse::<SecondaryStructureRecord>().unwrap()).iter().sum::<f64>());
+    assert_eq!(descriptors[1].gc_content, 0.0);
+
+    assert_eq!(descriptors[2], StructureDescriptors {
+        name: "".to_string(),
+        length: 4,
+        num_pairs: 0,
+        num_helices: 0,
+        max_nesting_depth: 0,
+        pseudoknotted: false,
+        page_number: 1,
+        gc_content: 0.0,
+        mountain_area: 0.0,
+    });
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_compute_descriptors_par() {
+    use crate::rna_secondary_structure::descriptors::compute_descriptors_par;
+
+    let records = ["((..[[..))..]]", "(((...)))..((.....))", "....", "((.((...)).))..[[..]]", ""].iter()
+        .map(|dbs| dbs.parse::<SecondaryStructureRecord>().unwrap())
+        .collect::<Vec<SecondaryStructureRecord>>();
+    assert_eq!(compute_descriptors_par(&records), compute_descriptors(&records));
+    assert!(compute_descriptors_par(&[]).is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_descriptors_are_serializable() {
+    fn assert_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+    assert_serde::<StructureDescriptors>();
+}
@@ -0,0 +1,342 @@
+extern crate rna_secondary_structure;
+
+use crate::rna_secondary_structure::distance_metrics::*;
+use crate::rna_secondary_structure::secondary_structure::{from_dotbracketstring, Loop, PairedSites, SecondaryStructureRecord, StructureParseError};
+
+#[test]
+fn test_distances_to_reference() {
+    let reference = from_dotbracketstring("((....))").unwrap();
+    let p1 = from_dotbracketstring("(......)").unwrap();
+    let p2 = from_dotbracketstring("........").unwrap();
+    let structures: Vec<&dyn PairedSites> = vec![&reference, &p1, &p2];
+
+    let distances = distances_to_reference(&reference, &structures, &get_weighted_mountain_distance).unwrap();
+    assert_eq!(distances.len(), 3);
+    assert_eq!(distances[0], 0.0);
+    assert!(distances[1] < distances[2]);
+
+    let metric = |a: &dyn PairedSites, b: &dyn PairedSites| get_mountain_distance(a, b, Some(2.0));
+    assert_eq!(distances_to_reference(&reference, &structures, &metric).unwrap(), vec![0.0, 5.0, 22.0]);
+
+    let short = from_dotbracketstring("(..)").unwrap();
+    match distances_to_reference(&reference, &[&p1, &short], &metric) {
+        Err(SecondaryStructureMetricError::UnequalLength) => {}
+        _ => panic!("expected an UnequalLength error")
+    }
+}
+
+#[test]
+fn test_dot_bracket_edit_script() {
+    assert_eq!(dot_bracket_edit_script("", "(.)"), vec![
+        EditOp::Insert { pos: 0, c: '(' },
+        EditOp::Insert { pos: 1, c: '.' },
+        EditOp::Insert { pos: 2, c: ')' },
+    ]);
+    assert_eq!(dot_bracket_edit_script("..((..))", "((..))"), vec![
+        EditOp::Delete { pos: 0, c: '.' },
+        EditOp::Delete { pos: 1, c: '.' },
+    ]);
+    assert_eq!(dot_bracket_edit_script("((..))", "(....)"), vec![
+        EditOp::Substitute { pos: 1, from: '(', to: '.' },
+        EditOp::Substitute { pos: 4, from: ')', to: '.' },
+    ]);
+    // the number of operations is the edit distance between the two strings
+    assert_eq!(dot_bracket_edit_script("(((...)))", "((.(...).))").len(), 2);
+}
+
+#[test]
+fn test_nearest_neighbor() {
+    let query = from_dotbracketstring("(((...)))").unwrap();
+    let p1 = from_dotbracketstring("((.....))").unwrap();
+    let p2 = from_dotbracketstring("(((...)))").unwrap();
+    let p3 = from_dotbracketstring(".((...)).").unwrap();
+    let metric = |a: &dyn PairedSites, b: &dyn PairedSites| get_mountain_distance(a, b, None);
+
+    assert_eq!(nearest_neighbor(&query, &[&p1, &p3], &metric).unwrap(), Some((0, 4.0)));
+    assert_eq!(nearest_neighbor(&query, &[&p1, &p2, &p3], &metric).unwrap(), Some((1, 0.0)));
+
+    let short = from_dotbracketstring("(...)").unwrap();
+    match nearest_neighbor(&query, &[&p1, &short], &metric) {
+        Err(SecondaryStructureMetricError::UnequalLength) => {}
+        _ => panic!("expected an UnequalLength error")
+    }
+}
+
+#[test]
+fn test_loop_difference() {
+    let a = from_dotbracketstring("((...))((...))").unwrap();
+    let b = from_dotbracketstring("((...))(.....)").unwrap();
+    let diff = loop_difference(&a, &b).unwrap();
+    assert_eq!(diff.only_in_a, vec![
+        Loop::Stack { outer: (7, 13), inner: (8, 12) },
+        Loop::Hairpin { closing: (8, 12), unpaired: vec![9, 10, 11] },
+    ]);
+    assert_eq!(diff.only_in_b, vec![Loop::Hairpin { closing: (7, 13), unpaired: (8..13).collect() }]);
+
+    let diff = loop_difference(&a, &a).unwrap();
+    assert!(diff.only_in_a.is_empty() && diff.only_in_b.is_empty());
+
+    assert!(matches!(loop_difference(&a, &from_dotbracketstring("((...))").unwrap()), Err(SecondaryStructureMetricError::UnequalLength)));
+    let pseudoknotted = from_dotbracketstring("((..[[))..]]..").unwrap();
+    assert!(matches!(loop_difference(&a, &pseudoknotted), Err(SecondaryStructureMetricError::Structure(StructureParseError::Pseudoknotted))));
+}
+
+#[test]
+fn test_invert_mountain_vector_round_trip() {
+    for dbs in &["", "....", "(((...)))", "((..))((...)).", "(((..((...))..((...)).)))", ".(.(.(.).).)."] {
+        let paired = from_dotbracketstring(dbs).unwrap();
+        assert_eq!(invert_mountain_vector(&get_mountain_vector(&paired)).unwrap(), paired);
+        assert_eq!(invert_weighted_mountain_vector(&get_weighted_mountain_vector(&paired)).unwrap(), paired);
+    }
+
+    assert!(matches!(invert_mountain_vector(&[1.0, 2.0, 1.0]), Err(SecondaryStructureMetricError::InvalidMountainVector { pos: 2, .. })));
+    assert!(matches!(invert_mountain_vector(&[1.0, 3.0, 0.0]), Err(SecondaryStructureMetricError::InvalidMountainVector { pos: 1, .. })));
+    assert!(matches!(invert_mountain_vector(&[0.0, -1.0]), Err(SecondaryStructureMetricError::InvalidMountainVector { pos: 1, .. })));
+    assert!(invert_weighted_mountain_vector(&[0.5, 0.75]).is_err());
+}
+
+#[test]
+fn test_base_pair_distance() {
+    let paired1 = from_dotbracketstring("((..))").unwrap();
+    let paired2 = from_dotbracketstring("(....)").unwrap();
+    // only (1, 4) differs: the outer pair (0, 5) is shared
+    assert_eq!(get_base_pair_distance(&paired1, &paired2).unwrap(), 1);
+    assert_eq!(get_base_pair_distance(&paired1, &from_dotbracketstring("......").unwrap()).unwrap(), 2);
+    assert_eq!(get_base_pair_distance(&paired1, &paired1).unwrap(), 0);
+    assert_eq!(get_base_pair_distance(&from_dotbracketstring("(..)..").unwrap(), &from_dotbracketstring(".(..)").unwrap()).unwrap(), 2);
+
+    // structures of different lengths are compared by their base-pairs alone
+    assert_eq!(get_base_pair_distance(&paired1, &from_dotbracketstring("((..))...").unwrap()).unwrap(), 0);
+}
+
+#[test]
+fn test_score_prediction() {
+    let reference = from_dotbracketstring("((((....))))").unwrap();
+    let predicted = from_dotbracketstring("((..(..)..))").unwrap();
+    // two of four reference pairs are predicted, plus one false positive (4, 7)
+    let scores = score_prediction(&predicted, &reference).unwrap();
+    assert_eq!(scores.sensitivity, 0.5);
+    assert_eq!(scores.ppv, 2.0 / 3.0);
+    assert!((scores.f1 - 4.0 / 7.0).abs() < 1e-12);
+    // 66 possible pairs: TP = 2, FP = 1, FN = 2, TN = 61
+    let mcc = (2.0 * 61.0 - 1.0 * 2.0) / (3.0f64 * 4.0 * 62.0 * 63.0).sqrt();
+    assert!((scores.mcc - mcc).abs() < 1e-12);
+
+    let scores = score_prediction(&reference, &reference).unwrap();
+    assert_eq!((scores.sensitivity, scores.ppv, scores.f1), (1.0, 1.0, 1.0));
+    assert!((scores.mcc - 1.0).abs() < 1e-12);
+
+    // empty prediction and empty reference report 0.0 rather than NaN
+    let empty = from_dotbracketstring("............").unwrap();
+    let scores = score_prediction(&empty, &reference).unwrap();
+    assert_eq!(scores, PredictionScores { sensitivity: 0.0, ppv: 0.0, mcc: 0.0, f1: 0.0 });
+    let scores = score_prediction(&reference, &empty).unwrap();
+    assert_eq!((scores.sensitivity, scores.ppv, scores.mcc, scores.f1), (0.0, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_score_prediction_with_slippage() {
+    let reference = from_dotbracketstring("((((....))))...").unwrap();
+    let shifted = from_dotbracketstring("..((((....)))).").unwrap();
+    let offset = from_dotbracketstring(".((((....))))..").unwrap();
+
+    for predicted in &[&reference, &shifted, &offset] {
+        assert_eq!(score_prediction_with_slippage(*predicted, &reference, 0).unwrap(), score_prediction(*predicted, &reference).unwrap());
+    }
+
+    // every pair offset by one position on both sides is recovered with slip = 1
+    let scores = score_prediction_with_slippage(&offset, &reference, 1).unwrap();
+    assert_eq!((scores.sensitivity, scores.ppv), (1.0, 1.0));
+    // an offset of two is not
+    assert_eq!(score_prediction_with_slippage(&shifted, &reference, 1).unwrap().sensitivity, 0.0);
+
+    // each reference pair is matched at most once: both predicted pairs are within slip of (0, 7)
+    let reference = from_dotbracketstring("(......)..").unwrap();
+    let predicted = from_dotbracketstring("((....))..").unwrap();
+    let scores = score_prediction_with_slippage(&predicted, &reference, 1).unwrap();
+    assert_eq!((scores.sensitivity, scores.ppv), (1.0, 0.5));
+}
+
+#[test]
+fn test_hamming_distance() {
+    let paired1 = from_dotbracketstring("(..)").unwrap();
+    let paired2 = from_dotbracketstring(".().").unwrap();
+    assert_eq!(get_hamming_distance(&paired1, &paired2).unwrap(), 4);
+    assert_eq!(get_hamming_distance(&paired1, &paired1).unwrap(), 0);
+
+    // partners are ignored: both structures open, open, close, close
+    assert_eq!(get_hamming_distance(&from_dotbracketstring("(())").unwrap(), &from_dotbracketstring("([)]").unwrap()).unwrap(), 0);
+    assert!(matches!(get_hamming_distance(&paired1, &from_dotbracketstring(".()").unwrap()), Err(SecondaryStructureMetricError::UnequalLength)));
+}
+
+#[test]
+fn test_normalised_lp_mountain_distance() {
+    let star = get_structure_star(100);
+    let zero = get_structure_zero(100);
+    assert_eq!(get_normalised_lp_mountain_distance(&star, &zero, Some(2.0)).unwrap(), 1.0);
+    assert_eq!(get_normalised_lp_mountain_distance(&star, &star, Some(2.0)).unwrap(), 0.0);
+
+    let mut intermediate = get_structure_zero(100);
+    for i in 0..20 {
+        intermediate[i] = (100 - i) as i64;
+        intermediate[99 - i] = (i + 1) as i64;
+    }
+    let d = get_normalised_lp_mountain_distance(&star, &intermediate, Some(2.0)).unwrap();
+    assert!(d > 0.0 && d < 1.0);
+    let d = get_normalised_lp_mountain_distance(&zero, &intermediate, Some(2.0)).unwrap();
+    assert!(d > 0.0 && d < 1.0);
+
+    // the root is taken, so the unnormalised distance is the Euclidean distance for p = 2
+    let lp = get_lp_mountain_distance(&star, &intermediate, Some(2.0)).unwrap();
+    assert!((lp - get_mountain_distance(&star, &intermediate, Some(2.0)).unwrap().sqrt()).abs() < 1e-9);
+    assert_eq!(get_lp_mountain_distance(&star, &intermediate, None).unwrap(), get_mountain_distance(&star, &intermediate, None).unwrap());
+}
+
+#[test]
+fn test_pairwise_mountain_distances() {
+    let p1 = from_dotbracketstring("((...))").unwrap();
+    let p2 = from_dotbracketstring(".(...).").unwrap();
+    let p3 = from_dotbracketstring(".......").unwrap();
+    let structures: Vec<&dyn PairedSites> = vec![&p1, &p2, &p3];
+    let matrix = pairwise_mountain_distances(&structures, Some(2.0)).unwrap();
+    for i in 0..3 {
+        assert_eq!(matrix[i][i], 0.0);
+        for j in 0..3 {
+            assert_eq!(matrix[i][j], matrix[j][i]);
+            assert_eq!(matrix[i][j], get_mountain_distance(structures[i], structures[j], Some(2.0)).unwrap());
+        }
+    }
+
+    // the cached mountain vectors give exactly the distances computed pair by pair
+    let paired = ["((((....))))", "(((......)))", ".((......)).", "((..))((..))", "............", "(.(.(..).).)"].iter()
+        .map(|dbs| from_dotbracketstring(dbs).unwrap())
+        .collect::<Vec<Vec<i64>>>();
+    let structures = paired.iter().map(|p| p as &dyn PairedSites).collect::<Vec<&dyn PairedSites>>();
+    for p in &[None, Some(0.5), Some(3.0)] {
+        let matrix = pairwise_mountain_distances(&structures, *p).unwrap();
+        for i in 0..structures.len() {
+            for j in 0..structures.len() {
+                let naive = if i == j { 0.0 } else { get_mountain_distance(structures[i], structures[j], *p).unwrap() };
+                assert_eq!(matrix[i][j], naive);
+            }
+        }
+    }
+
+    let p4 = from_dotbracketstring("(...)").unwrap();
+    assert!(matches!(pairwise_mountain_distances(&[&p1, &p2, &p4], None), Err(SecondaryStructureMetricError::UnequalLengthBetween { i: 0, j: 2 })));
+    assert!(pairwise_mountain_distances(&[], None).unwrap().is_empty());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_pairwise_mountain_distances_par() {
+    let paired = ["((((....))))", "(((......)))", ".((......)).", "..((....))..", "((..))((..))", "............", "(.(.(..).).)", "((.(....).))", "(((.(..).)))", ".(((....)))."].iter()
+        .map(|dbs| from_dotbracketstring(dbs).unwrap())
+        .collect::<Vec<Vec<i64>>>();
+    let structures = paired.iter().map(|p| p as &dyn PairedSites).collect::<Vec<&dyn PairedSites>>();
+    for p in &[None, Some(2.0)] {
+        assert_eq!(pairwise_mountain_distances_par(&structures, *p).unwrap(), pairwise_mountain_distances(&structures, *p).unwrap());
+    }
+
+    let short = from_dotbracketstring("(...)").unwrap();
+    assert!(matches!(pairwise_mountain_distances_par(&[structures[0], &short], None), Err(SecondaryStructureMetricError::UnequalLengthBetween { i: 0, j: 1 })));
+    assert!(pairwise_mountain_distances_par(&[], None).unwrap().is_empty());
+}
+
+#[test]
+fn test_consensus_structure() {
+    let p1 = from_dotbracketstring("((....))..").unwrap();
+    let p2 = from_dotbracketstring("(.(...))..").unwrap();
+    let p3 = from_dotbracketstring(".(....)(.)").unwrap();
+    let structures: Vec<&dyn PairedSites> = vec![&p1, &p2, &p3];
+
+    // (0, 7) and (1, 6) occur in two of three structures, (2, 6) and (7, 9) in one
+    let consensus = consensus_structure(&structures, 0.5).unwrap();
+    assert_eq!(consensus, from_dotbracketstring("((....))..").unwrap());
+
+    // at a lower threshold (2, 6) and (7, 9) conflict with the more frequent pairs and are dropped
+    let consensus = consensus_structure(&structures, 0.3).unwrap();
+    assert_eq!(consensus, from_dotbracketstring("((....))..").unwrap());
+
+    assert_eq!(consensus_structure(&structures, 0.7).unwrap(), vec![0; 10]);
+    let p4 = from_dotbracketstring("(...)").unwrap();
+    assert!(matches!(consensus_structure(&[&p1, &p4], 0.5), Err(SecondaryStructureMetricError::UnequalLength)));
+}
+
+#[test]
+fn test_morgan_higgs_distance() {
+    // each of (0, 7) and (1, 6) crosses both (2, 9) and (3, 8), and vice versa
+    let paired1 = from_dotbracketstring("((....))..").unwrap();
+    let paired2 = from_dotbracketstring("..((....))").unwrap();
+    assert_eq!(get_morgan_higgs_distance(&paired1, &paired2).unwrap(), 8.0);
+
+    // the shared pair (0, 9) costs nothing, and (1, 4) crosses only (3, 6)
+    let paired1 = from_dotbracketstring("((..)....)").unwrap();
+    let paired2 = from_dotbracketstring("(..(..)..)").unwrap();
+    assert_eq!(get_morgan_higgs_distance(&paired1, &paired2).unwrap(), 2.0);
+
+    assert_eq!(get_morgan_higgs_distance(&paired1, &paired1).unwrap(), 0.0);
+    assert!(matches!(get_morgan_higgs_distance(&paired1, &from_dotbracketstring("(..)").unwrap()), Err(SecondaryStructureMetricError::UnequalLength)));
+}
+
+#[test]
+fn test_mountain_rmsd() {
+    let paired1 = from_dotbracketstring("((..))").unwrap();
+    let paired2 = from_dotbracketstring("......").unwrap();
+    // mountain vector [1, 2, 2, 2, 1, 0]
+    assert!((get_mountain_rmsd(&paired1, &paired2).unwrap() - (14.0f64 / 6.0).sqrt()).abs() < 1e-12);
+    assert_eq!(get_mountain_rmsd(&paired1, &paired1).unwrap(), 0.0);
+    assert_eq!(get_mountain_rmsd(&Vec::<i64>::new(), &Vec::<i64>::new()).unwrap(), 0.0);
+    assert!(matches!(get_mountain_rmsd(&paired1, &from_dotbracketstring("(..)").unwrap()), Err(SecondaryStructureMetricError::UnequalLength)));
+}
+
+#[test]
+fn test_coarse_grained_string() {
+    let hairpin = from_dotbracketstring("..((((...))))..").unwrap();
+    let two_hairpins = from_dotbracketstring("((...))..((...))").unwrap();
+    assert_eq!(coarse_grained_string(&hairpin).unwrap(), "((H)R)");
+    assert_eq!(coarse_grained_string(&two_hairpins).unwrap(), "((H)(H)R)");
+    assert_eq!(coarse_grained_edit_distance(&hairpin, &two_hairpins).unwrap(), 3);
+    assert_eq!(coarse_grained_edit_distance(&hairpin, &hairpin).unwrap(), 0);
+
+    // bulges and internal loops
+    assert_eq!(coarse_grained_string(&from_dotbracketstring("((((...)).))").unwrap()).unwrap(), "(((H)B)R)");
+    assert_eq!(coarse_grained_string(&from_dotbracketstring("((.((...)).))").unwrap()).unwrap(), "(((H)I)R)");
+    assert_eq!(coarse_grained_string(&from_dotbracketstring("....").unwrap()).unwrap(), "(R)");
+
+    let pseudoknotted = from_dotbracketstring("((..[[..))..]]").unwrap();
+    assert!(matches!(coarse_grained_string(&pseudoknotted), Err(StructureParseError::Pseudoknotted)));
+    assert!(coarse_grained_edit_distance(&hairpin, &pseudoknotted).is_err());
+}
+
+#[test]
+fn test_tree_edit_distance() {
+    let a = from_dotbracketstring("..((.((...))..)).((...))").unwrap();
+    assert_eq!(tree_edit_distance(&a, &a).unwrap(), 0);
+
+    // removing a base-pair deletes its node, and its unpaired nucleotides are inserted
+    let one_fewer = from_dotbracketstring("..((.((...))..)).(.....)").unwrap();
+    assert_eq!(tree_edit_distance(&a, &one_fewer).unwrap(), 3);
+    assert_eq!(tree_edit_distance(&one_fewer, &a).unwrap(), 3);
+    assert_eq!(tree_edit_distance(&from_dotbracketstring("((..))").unwrap(), &from_dotbracketstring("(....)").unwrap()).unwrap(), 3);
+
+    // a base-pair relabelled as an unpaired nucleotide, or an extra unpaired nucleotide
+    assert_eq!(tree_edit_distance(&from_dotbracketstring("(())").unwrap(), &from_dotbracketstring("(.)").unwrap()).unwrap(), 1);
+    assert_eq!(tree_edit_distance(&from_dotbracketstring("(...)..").unwrap(), &from_dotbracketstring("(...)...").unwrap()).unwrap(), 1);
+    assert_eq!(tree_edit_distance(&vec![], &from_dotbracketstring("...").unwrap()).unwrap(), 3);
+
+    let pseudoknotted = from_dotbracketstring("((..[[..))..]]").unwrap();
+    assert!(matches!(tree_edit_distance(&a, &pseudoknotted), Err(StructureParseError::Pseudoknotted)));
+}
+
+/// distance_metrics::get_mountain_vector is the single mountain vector implementation, and accepts
+/// both paired sites vectors and records through PairedSites.
+#[test]
+fn test_get_mountain_vector_accepts_paired_sites() {
+    let paired: Vec<i64> = from_dotbracketstring("((.))").unwrap();
+    let ss = SecondaryStructureRecord::new(paired.clone());
+    let sites: &dyn PairedSites = &paired;
+    assert_eq!(get_mountain_vector(&paired), vec![1.0, 2.0, 2.0, 1.0, 0.0]);
+    assert_eq!(get_mountain_vector(&ss), get_mountain_vector(&paired));
+    assert_eq!(get_mountain_vector(sites), get_mountain_vector(&paired));
+}
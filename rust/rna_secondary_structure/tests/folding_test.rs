@@ -0,0 +1,173 @@
+extern crate rna_secondary_structure;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::rna_secondary_structure::combinatorics::enumerate_structures;
+use crate::rna_secondary_structure::folding::{boltzmann_sample, canonical_pairs, canonical_plus_wobble, mea_structure, nussinov, nussinov_with, partition_function};
+use crate::rna_secondary_structure::secondary_structure::{base_pairs, is_canonical_pair, is_pseudoknotted};
+
+#[test]
+fn test_nussinov() {
+    let ss = nussinov(&"A".repeat(20), 3);
+    assert_eq!(ss.paired, vec![0; 20]);
+    assert_eq!(ss.sequence, "A".repeat(20));
+    assert_eq!(ss.name, "");
+
+    let ss = nussinov("GGGAAACCC", 3);
+    assert_eq!(ss.base_pairs(), vec![(0, 8), (1, 7), (2, 6)]);
+
+    // the minimum loop size prevents the innermost pair
+    assert_eq!(nussinov("GGGAAACCC", 4).base_pairs().len(), 2);
+    assert_eq!(nussinov("GC", 0).base_pairs(), vec![(0, 1)]);
+    assert!(nussinov("", 3).paired.is_empty());
+
+    let sequence = "GGGAAAUCCCGCUUAGGCAAAGCCUAAGGAUCC";
+    let ss = nussinov(sequence, 3);
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    assert!(!is_pseudoknotted(&ss).unwrap());
+    assert!(base_pairs(&ss).iter().all(|(i, j)| j - i > 3 && is_canonical_pair(nucleotides[*i], nucleotides[*j])));
+}
+
+#[test]
+fn test_nussinov_with() {
+    // a single G-C pair is possible without wobbles, four G-U pairs with them
+    let canonical = nussinov_with("GGGGCUUUUUU", 3, canonical_pairs);
+    let wobble = nussinov_with("GGGGCUUUUUU", 3, canonical_plus_wobble);
+    assert_eq!(canonical.base_pairs().len(), 1);
+    assert_eq!(wobble.base_pairs().len(), 4);
+    assert_ne!(canonical.paired, wobble.paired);
+    assert_eq!(wobble.paired, nussinov("GGGGCUUUUUU", 3).paired);
+
+    assert!(canonical_pairs('a', 'T'));
+    assert!(!canonical_pairs('G', 'U'));
+    assert!(canonical_plus_wobble('G', 'U'));
+    assert!(nussinov_with("GGGAAACCC", 3, |_, _| false).base_pairs().is_empty());
+}
+
+#[test]
+fn test_mea_structure() {
+    // pairing 0 with 9 scores 2 * gamma * 0.4 against 2 * 0.6 for leaving both unpaired
+    let probabilities = vec![(9, 0, 0.4), (2, 6, 0.05)];
+    assert_eq!(mea_structure(10, &probabilities, 20.0).base_pairs(), vec![(0, 9), (2, 6)]);
+    assert_eq!(mea_structure(10, &probabilities, 2.0).base_pairs(), vec![(0, 9)]);
+    assert_eq!(mea_structure(10, &probabilities, 1.0).base_pairs(), vec![]);
+
+    // crossing base-pairs cannot both be chosen
+    let probabilities = vec![(0, 5, 0.5), (2, 8, 0.6), (3, 8, 0.0), (4, 20, 1.0), (3, 3, 1.0)];
+    let ss = mea_structure(9, &probabilities, 5.0);
+    assert_eq!(ss.base_pairs(), vec![(2, 8)]);
+    assert!(!is_pseudoknotted(&ss).unwrap());
+    assert_eq!(ss.sequence, "NNNNNNNNN");
+
+    assert!(mea_structure(0, &[], 1.0).paired.is_empty());
+}
+
+#[test]
+fn test_boltzmann_sample() {
+    let sequence = "GGGGGGAAAACCCCCC";
+    let mut rng = StdRng::seed_from_u64(2);
+    let samples = boltzmann_sample(sequence, 3, 200, &mut rng);
+    assert_eq!(samples.len(), 200);
+
+    let stem = samples.iter().filter(|ss| ss.get_dot_bracket_string().unwrap() == "((((((....))))))").count();
+    let open = samples.iter().filter(|ss| ss.base_pairs().is_empty()).count();
+    assert!(stem > 100, "{}", stem);
+    assert!(open < 5, "{}", open);
+
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    for ss in samples.iter() {
+        assert!(!is_pseudoknotted(ss).unwrap());
+        assert!(base_pairs(ss).iter().all(|(i, j)| j - i > 3 && is_canonical_pair(nucleotides[*i], nucleotides[*j])));
+    }
+
+    // nothing can pair, so every sample is the open structure
+    assert!(boltzmann_sample("AAAAAAAA", 3, 5, &mut rng).iter().all(|ss| ss.paired == vec![0; 8]));
+    assert!(boltzmann_sample("GGGAAACCC", 3, 0, &mut rng).is_empty());
+
+    // the samples of a long sequence, whose partition function exceeds the range of an f64, have
+    // about the expected number of base-pairs
+    let sequence = (0..600).map(|_| ['A', 'C', 'G', 'U'][rng.gen_range(0..4)]).collect::<String>();
+    let samples = boltzmann_sample(&sequence, 3, 20, &mut rng);
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    for ss in samples.iter() {
+        assert!(!is_pseudoknotted(ss).unwrap());
+        assert!(base_pairs(ss).iter().all(|(i, j)| j - i > 3 && is_canonical_pair(nucleotides[*i], nucleotides[*j])));
+    }
+    let mean = samples.iter().map(|ss| ss.base_pairs().len()).sum::<usize>() as f64 / 20.0;
+    let expected = partition_function(&sequence, 3).1.iter().map(|(_, _, p)| p).sum::<f64>();
+    assert!((mean - expected).abs() < 0.1 * expected, "{} {}", mean, expected);
+}
+
+#[test]
+fn test_partition_function() {
+    let sequence = "GGGGGGAAAACCCCCC";
+    let (_, probs) = partition_function(sequence, 3);
+    let mut paired_probability = vec![0.0; sequence.len()];
+    for (i, j, p) in probs.iter() {
+        assert!(i < j && j - i > 3);
+        assert!(*p > 0.0 && *p <= 1.0 + 1e-9);
+        paired_probability[*i] += p;
+        paired_probability[*j] += p;
+    }
+    assert!(paired_probability.iter().all(|p| *p <= 1.0 + 1e-9));
+
+    let outer = probs.iter().find(|(i, j, _)| (*i, *j) == (0, 15)).unwrap().2;
+    assert!(outer > 0.9, "{}", outer);
+    let ss = mea_structure(sequence.len(), &probs, 1.0);
+    assert_eq!(ss.get_dot_bracket_string().unwrap(), "((((((....))))))");
+
+    // agrees with summing the Boltzmann weights of every structure of a short sequence
+    let sequence = "GGUACAGCU";
+    let nucleotides = sequence.chars().collect::<Vec<char>>();
+    let energy = |a: char, b: char| match (a, b) {
+        ('G', 'C') | ('C', 'G') => Some(-3.0),
+        ('A', 'U') | ('U', 'A') => Some(-2.0),
+        ('G', 'U') | ('U', 'G') => Some(-1.0),
+        _ => None
+    };
+    let mut expected_z = 0.0;
+    let mut expected_pairs = vec![vec![0.0; sequence.len()]; sequence.len()];
+    for paired in enumerate_structures(sequence.len() as i64, 3) {
+        let pairs = base_pairs(&paired);
+        let energies = pairs.iter().map(|(i, j)| energy(nucleotides[*i], nucleotides[*j])).collect::<Option<Vec<f64>>>();
+        if let Some(energies) = energies {
+            let weight = (-energies.iter().sum::<f64>() / 0.61632).exp();
+            expected_z += weight;
+            for (i, j) in pairs.iter() {
+                expected_pairs[*i][*j] += weight;
+            }
+        }
+    }
+    let (z, probs) = partition_function(sequence, 3);
+    assert!((z - expected_z).abs() < 1e-9 * expected_z);
+    for (i, j, p) in probs.iter() {
+        assert!((p - expected_pairs[*i][*j] / expected_z).abs() < 1e-9);
+    }
+    let count = expected_pairs.iter().flatten().filter(|w| **w > 0.0).count();
+    assert_eq!(probs.len(), count);
+
+    // long sequences, whose partition function exceeds the range of an f64, still have valid
+    // base-pair probabilities
+    let mut rng = StdRng::seed_from_u64(5);
+    let sequence = (0..600).map(|_| ['A', 'C', 'G', 'U'][rng.gen_range(0..4)]).collect::<String>();
+    let (z, probs) = partition_function(&sequence, 3);
+    assert!(z.is_infinite());
+    assert!(!probs.is_empty());
+    let mut paired_probability = vec![0.0; sequence.len()];
+    for (i, j, p) in probs.iter() {
+        assert!(p.is_finite() && *p > 0.0 && *p <= 1.0 + 1e-9, "{}", p);
+        paired_probability[*i] += p;
+        paired_probability[*j] += p;
+    }
+    assert!(paired_probability.iter().all(|p| *p <= 1.0 + 1e-9));
+    let expected_pairs = probs.iter().map(|(_, _, p)| p).sum::<f64>();
+    assert!(expected_pairs > 100.0, "{}", expected_pairs);
+    let ss = mea_structure(sequence.len(), &probs, 1.0);
+    assert!(!is_pseudoknotted(&ss).unwrap());
+    assert!(ss.base_pairs().len() > 50);
+
+    // nothing can pair
+    assert_eq!(partition_function("AAAAAAAA", 3), (1.0, vec![]));
+    assert_eq!(partition_function("", 3), (1.0, vec![]));
+}
@@ -1,5 +1,7 @@
 extern crate rna_secondary_structure;
 
+use std::io::Read;
+
 use rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
 
 use crate::rna_secondary_structure::io;
@@ -14,6 +16,9 @@ fn test_ct_strings() {
         name: "example1".to_string(),
         paired: paired1,
         sequence: "ATAGCATCTCGGA".to_string(),
+        energy: None,
+        annotation: None,
+        numbering: None,
     };
 
     let dbs2 = "...............".to_string();
@@ -22,6 +27,9 @@ fn test_ct_strings() {
         name: "example2".to_string(),
         paired: paired2,
         sequence: "CCCCAAAAAAAAAAA".to_string(),
+        energy: None,
+        annotation: None,
+        numbering: None,
     };
 
     let dbs3 = "((....))".to_string();
@@ -30,6 +38,9 @@ fn test_ct_strings() {
         name: "example3".to_string(),
         paired: paired3,
         sequence: "CCAAAAGG".to_string(),
+        energy: None,
+        annotation: None,
+        numbering: None,
     };
 
     let mut ct_string = "".to_string();
@@ -48,4 +59,603 @@ fn test_ct_strings() {
     assert_eq!(ls[1].paired, ss2.paired);
     assert_eq!(ls[2].sequence, ss3.sequence);
     assert_eq!(ls[2].paired, ss3.paired);
-}
\ No newline at end of file
+}
+
+#[test]
+/// Tests that pseudoknotted structures survive CT and dot bracket round trips unchanged.
+fn test_pseudoknot_round_trips() {
+    // three mutually crossing helices need three bracket types
+    let three_pages = SecondaryStructureRecord::new(secondary_structure::from_dotbracketstring("((.[[.{{.)).]].}}").unwrap());
+    assert_eq!(secondary_structure::pseudoknot_order(&three_pages), 3);
+    // 31 mutually crossing base-pairs need more bracket types than dot bracket notation has
+    let clique = SecondaryStructureRecord::new((0..62).map(|i| if i < 31 { i + 32 } else { i - 30 }).collect());
+    assert!(!secondary_structure::is_pseudoknot_safe(&clique));
+
+    for ss in [&three_pages, &clique].iter() {
+        let reread = io::parse_ct_string(&io::get_ct_string(ss)).unwrap();
+        assert_eq!(reread[0].paired, ss.paired);
+    }
+
+    assert!(secondary_structure::is_pseudoknot_safe(&three_pages));
+    let mut buffer = Vec::new();
+    io::write_dbn(&mut buffer, &three_pages).unwrap();
+    let reread = io::parse_dbn_string(&String::from_utf8(buffer).unwrap()).unwrap();
+    assert_eq!(reread[0].paired, three_pages.paired);
+
+    let mut buffer = Vec::new();
+    assert!(io::write_dbn(&mut buffer, &clique).is_err());
+    assert!(buffer.is_empty());
+}
+
+#[test]
+/// Tests that CT and BPSEQ output has a row for every position when the sequence is too short.
+fn test_ct_short_sequence() {
+    let mut ss = SecondaryStructureRecord::new(secondary_structure::from_dotbracketstring("(..)").unwrap());
+    ss.set_sequence("AC".to_string());
+
+    let ct_string = io::get_ct_string(&ss);
+    assert_eq!(ct_string.lines().count(), 5);
+    assert!(ct_string.ends_with("3\tN\t2\t4\t0\t3\n4\tN\t3\t5\t1\t4\n"));
+    let reread = io::parse_ct_string(&ct_string).unwrap();
+    assert_eq!(reread[0].paired, ss.paired);
+    assert_eq!(reread[0].sequence, "ACNN");
+
+    let bpseq_string = io::get_bpseq_string(&ss);
+    assert_eq!(bpseq_string, "1 A 4\n2 C 0\n3 N 0\n4 N 1\n");
+}
+
+#[test]
+/// Tests that energies are written after the dot bracket string and read back.
+fn test_dbn_energy_round_trip() {
+    let mut ss: SecondaryStructureRecord = "((...))".parse().unwrap();
+    ss.name = "with_energy".to_string();
+    ss.set_sequence("GGAAACC".to_string());
+    ss.energy = Some(-7.1);
+    let without_energy: SecondaryStructureRecord = "(...)".parse().unwrap();
+
+    let mut buffer = Vec::new();
+    io::write_records_to_dbn(&mut buffer, &[&ss, &without_energy]).unwrap();
+    let dbn_string = String::from_utf8(buffer).unwrap();
+    assert_eq!(dbn_string, ">with_energy\nGGAAACC\n((...)) (-7.10)\n>\nNNNNN\n(...)\n");
+
+    let records = io::parse_dbn_string(&dbn_string).unwrap();
+    assert_eq!(records[0], ss);
+    assert_eq!(records[1].energy, None);
+    assert!(io::parse_dbn_string(">bad\nGGAAACC\n((...)) -7.1\n").is_err());
+}
+
+#[test]
+/// Tests parsing DBN records held in memory, separated by any number of blank lines.
+fn test_parse_dbn_string() {
+    let dbn = "\n>first\nGGGAAACCC\n(((...)))\n\n\n>second\nGACGAAAGUC\n((.(..).))\n";
+    let records = io::parse_dbn_string(dbn).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].name, "first");
+    assert_eq!(records[0].sequence, "GGGAAACCC");
+    assert_eq!(records[0].paired, secondary_structure::from_dotbracketstring("(((...)))").unwrap());
+    assert_eq!(records[1].name, "second");
+    assert_eq!(records[1].paired, secondary_structure::from_dotbracketstring("((.(..).))").unwrap());
+    // records need not be separated by blank lines
+    assert_eq!(io::parse_dbn_string(&dbn.replace("\n\n\n", "\n")).unwrap(), records);
+
+    assert!(io::parse_dbn_string(">first\nGGGAAACCC\n\n(((...)))\n").is_err());
+    assert!(io::parse_dbn_string(">first\n\n").is_err());
+}
+
+#[test]
+/// Tests parsing DBN records listing several alternative structures for one sequence.
+fn test_parse_dbn_multi() {
+    let dbn = ">suboptimals\nGGGGAAAACCCC\n((((....)))) (-12.00)\n.(((....))). (-9.00)\n((((....)).))\n";
+    assert!(io::parse_dbn_multi(dbn.as_bytes()).is_err());
+
+    let dbn = dbn.replace("((((....)).))", "(((......)))");
+    let records = io::parse_dbn_multi(dbn.as_bytes()).unwrap();
+    assert_eq!(records.len(), 1);
+    let (ss, alternatives) = &records[0];
+    assert_eq!(ss.name, "suboptimals");
+    assert_eq!(ss.sequence, "GGGGAAAACCCC");
+    assert_eq!(ss.paired, secondary_structure::from_dotbracketstring("((((....))))").unwrap());
+    assert_eq!(ss.energy, Some(-12.0));
+    assert_eq!(alternatives, &vec![
+        secondary_structure::from_dotbracketstring(".(((....))).").unwrap(),
+        secondary_structure::from_dotbracketstring("(((......)))").unwrap(),
+    ]);
+
+    // alternatives end at a blank line or the next record
+    let two = format!("{}\n>second\nGGAAACC\n((...))\n>third\nGGAAACC\n((...))\n.(...).\n", dbn);
+    let records = io::parse_dbn_multi(two.as_bytes()).unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].1.len(), 2);
+    assert_eq!((records[1].0.name.as_str(), records[1].1.len()), ("second", 0));
+    assert_eq!((records[2].0.name.as_str(), records[2].1.len()), ("third", 1));
+    assert_eq!(io::parse_dbn_multi(two.replace("\n>", "\n\n>").as_bytes()).unwrap(), records);
+
+    assert!(io::parse_dbn_multi(">first\nGGGAAACCC\n".as_bytes()).is_err());
+}
+
+#[test]
+/// Tests that malformed CT strings give an error rather than a partial list of records.
+fn test_parse_ct_string_malformed() {
+    let bad_partner = ">example\n1\tG\t0\t2\tx\t1\n2\tC\t1\t3\t1\t2\n".to_string();
+    let err = io::parse_ct_string(&bad_partner).unwrap_err();
+    assert!(matches!(err.downcast_ref::<io::CtParseError>(), Some(io::CtParseError::BadInteger { line: 2 })));
+
+    let garbled = ">example\n1\tG\t0\t2\t2\t1\nC 1 2\n2\tC\t1\t3\t1\t2\n".to_string();
+    let err = io::parse_ct_string(&garbled).unwrap_err();
+    assert!(matches!(err.downcast_ref::<io::CtParseError>(), Some(io::CtParseError::WrongColumnCount { line: 3, found: 3 })));
+
+    assert!(io::parse_ct_string(&"not a CT file\n".to_string()).is_err());
+    assert!(io::parse_ct_string(&"\n\n".to_string()).unwrap().is_empty());
+}
+
+#[test]
+/// Tests that suboptimal CT files with `ENERGY =` and `dG =` headers are split into records with energies.
+fn test_ct_energy_headers() {
+    let ct_string = "    4  ENERGY = -1.5    subopt1
+    1 G       0    2    4    1
+    2 A       1    3    0    2
+    3 A       2    4    0    3
+    4 C       3    0    1    4
+    4  dG = -0.3    subopt2
+    1 G       0    2    0    1
+    2 A       1    3    0    2
+    3 A       2    4    0    3
+    4 C       3    0    0    4
+".to_string();
+
+    let ls = io::parse_ct_string(&ct_string).unwrap();
+    assert_eq!(ls.len(), 2);
+    assert_eq!(ls[0].name, "subopt1");
+    assert_eq!(ls[0].energy, Some(-1.5));
+    assert_eq!(ls[0].paired, vec![4, 0, 0, 1]);
+    assert_eq!(ls[1].name, "subopt2");
+    assert_eq!(ls[1].energy, Some(-0.3));
+
+    let ls2 = io::parse_ct_string(&io::get_ct_string(&ls[0])).unwrap();
+    assert_eq!(ls2[0].name, "subopt1");
+    assert_eq!(ls2[0].energy, Some(-1.5));
+    assert_eq!(ls2[0].paired, ls[0].paired);
+}
+
+#[test]
+/// Tests that the forna JSON output escapes names and pads missing sequence positions with N.
+fn test_forna_json() {
+    let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    ss.name = "a \"quoted\" name".to_string();
+    ss.sequence = "GG".to_string();
+
+    let json = io::get_forna_json_string(&ss);
+    assert!(json.starts_with("{\"name\":\"a \\\"quoted\\\" name\","));
+    assert_eq!(json.matches("\"node_type\":\"nucleotide\"").count(), 6);
+    assert_eq!(json.matches("{\"name\":\"N\"").count(), 4);
+    assert_eq!(json.matches("\"link_type\":\"backbone\"").count(), 5);
+    assert_eq!(json.matches("\"link_type\":\"basepair\"").count(), 2);
+    assert!(json.contains("{\"source\":1,\"target\":4,\"link_type\":\"basepair\"}"));
+}
+
+#[test]
+/// Tests that bpRNA .st files without an annotation line parse and that length mismatches are rejected.
+fn test_parse_bprna_st() {
+    let st_string = "#Name: no_annotation\n#Length: 6\n\nGGAACC\n((..))\n";
+    let ss = io::parse_bprna_st(st_string.as_bytes()).unwrap();
+    assert_eq!(ss.name, "no_annotation");
+    assert_eq!(ss.paired, vec![6, 5, 0, 0, 2, 1]);
+    assert_eq!(ss.annotation, None);
+
+    let st_string = "#Name: pseudoknot\nGGAAGGCCAACC\n((..[[))..]]\nSSMMSSSSMMSS\n";
+    let ss = io::parse_bprna_st(st_string.as_bytes()).unwrap();
+    assert_eq!(ss.paired, vec![8, 7, 0, 0, 12, 11, 2, 1, 0, 0, 6, 5]);
+
+    assert!(io::parse_bprna_st("#Name: mismatch\nGGAACC\n((...))\n".as_bytes()).is_err());
+    assert!(io::parse_bprna_st("#Name: truncated\nGGAACC\n".as_bytes()).is_err());
+}
+
+#[test]
+/// Tests that CT files with gaps in the residue numbering are remapped to dense positions.
+fn test_ct_non_contiguous_numbering() {
+    let ct_string = ">gapped
+    3 G 2 4 12 3
+    4 G 3 5 11 4
+    5 A 4 6 0 5
+    8 A 7 9 0 8
+    9 A 8 10 0 9
+   11 C 10 12 4 11
+   12 C 11 13 3 12
+".to_string();
+
+    let ls = io::parse_ct_string(&ct_string).unwrap();
+    assert_eq!(ls[0].sequence, "GGAAACC");
+    assert_eq!(ls[0].paired, vec![7, 6, 0, 0, 0, 2, 1]);
+    assert_eq!(ls[0].numbering, Some(vec![3, 4, 5, 8, 9, 11, 12]));
+
+    // writing retains the original numbering
+    let ls2 = io::parse_ct_string(&io::get_ct_string(&ls[0])).unwrap();
+    assert_eq!(ls2[0].paired, ls[0].paired);
+    assert_eq!(ls2[0].numbering, ls[0].numbering);
+
+    // a partner beyond the end of the numbering is written as unpaired rather than panicking
+    let mut out_of_range = ls[0].clone();
+    out_of_range.paired[0] = 20;
+    let line = io::get_ct_string(&out_of_range).lines().nth(1).unwrap().to_string();
+    assert_eq!(line, "3\tG\t2\t4\t0\t3");
+
+    // contiguous numbering is not recorded
+    let ss: SecondaryStructureRecord = "((.))".parse().unwrap();
+    assert_eq!(io::parse_ct_string(&io::get_ct_string(&ss)).unwrap()[0].numbering, None);
+}
+
+#[test]
+/// Tests parsing the base pair table from a larger DSSR output containing other sections.
+fn test_parse_dssr_pairs() {
+    let dssr_output = "Date and time: Tue Oct 14 12:00:00 2026
+****************************************************************************
+List of 4 base pairs
+     nt1            nt2            bp  name        Saenger   LW   DSSR
+   1 A.G1           A.C10          G-C WC          19-XIX    cWW  cW-W
+   2 A.A2           A.U9           A-U WC          20-XX     cWW  cW-W
+   3 A.G3           A.A8           G-A Sheared     11-XI     tHS  tM-m
+   4 A.PSU4^A       A.G7           P-G ~Wobble     n/a       cWW  cW-W
+****************************************************************************
+List of 1 helix
+  Note: a helix is defined by base-stacking interactions
+   1 A.G1           A.C10          G-C WC          19-XIX    cWW  cW-W
+";
+    let pairs = io::parse_dssr_pairs(dssr_output.as_bytes()).unwrap();
+    assert_eq!(pairs.len(), 4);
+    assert_eq!(pairs[2], (2, 7, "tHS".to_string()));
+    assert_eq!(pairs[3], (3, 6, "cWW".to_string()));
+
+    // the pseudouridine-guanine pair is cWW but not canonical in the given sequence
+    let paired = io::get_canonical_paired_from_dssr("GAGPAAGAUC", &pairs);
+    assert_eq!(paired, vec![10, 9, 0, 0, 0, 0, 0, 0, 2, 1]);
+}
+
+#[test]
+fn test_parse_ct_string_validated() {
+    let consistent = ">consistent\n1\tG\t0\t2\t4\t1\n2\tA\t1\t3\t0\t2\n3\tA\t2\t4\t0\t3\n4\tC\t3\t5\t1\t4\n";
+    assert!(io::parse_ct_string_validated(&consistent.to_string()).is_ok());
+
+    let asymmetric = ">asymmetric\n1\tG\t0\t2\t4\t1\n2\tA\t1\t3\t0\t2\n3\tA\t2\t4\t0\t3\n4\tC\t3\t5\t2\t4\n";
+    // the lenient parser accepts the file, the validated parser rejects it
+    assert_eq!(io::parse_ct_string(&asymmetric.to_string()).unwrap()[0].paired, vec![4, 0, 0, 2]);
+    let err = io::parse_ct_string_validated(&asymmetric.to_string()).unwrap_err();
+    assert!(matches!(err.downcast_ref::<secondary_structure::StructureParseError>(), Some(secondary_structure::StructureParseError::InconsistentPairing { i: 1, j: 4 })));
+
+    let out_of_range = ">out_of_range\n1\tG\t0\t2\t9\t1\n2\tA\t1\t3\t0\t2\n";
+    assert!(io::parse_ct_string_validated(&out_of_range.to_string()).is_err());
+}
+
+#[test]
+fn test_bpseq_round_trip() {
+    let mut ss: SecondaryStructureRecord = "..(((..[[..)))..]]..".parse().unwrap();
+    ss.set_sequence("AAGCGAAGGAACGCAACCAA".to_string());
+    ss.name = "round_trip".to_string();
+
+    let bpseq_string = io::get_bpseq_string(&ss);
+    let ss2 = io::parse_bpseq_string(&bpseq_string).unwrap();
+    assert_eq!(ss2.name, ss.name);
+    assert_eq!(ss2.sequence, ss.sequence);
+    assert_eq!(ss2.paired, ss.paired);
+
+    let path = std::env::temp_dir().join("rna_secondary_structure_round_trip.bpseq");
+    io::write_bpseq_file(&path, &ss).unwrap();
+    let ss3 = io::read_bpseq_file(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(ss3.paired, ss.paired);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_bpseq_headers_and_errors() {
+    let bpseq_string = "Organism: Escherichia coli\nAccession Number: X00001\n\n1 G 3\n\n2 A 0\n3 C 1\n";
+    let ss = io::parse_bpseq_string(bpseq_string).unwrap();
+    assert_eq!(ss.name, "Escherichia coli");
+    assert_eq!(ss.paired, vec![3, 0, 1]);
+
+    // skipped index and out-of-range partner
+    assert!(io::parse_bpseq_string("1 G 3\n3 C 1\n").is_err());
+    assert!(io::parse_bpseq_string("1 G 4\n2 A 0\n3 C 1\n").is_err());
+}
+
+#[test]
+fn test_fasta_structure() {
+    let fasta = ">first
+GCGGAUUUAG
+CUCAGC
+((((....))))....
+>second
+AAAA
+
+....
+";
+    let records = io::parse_fasta_structure(fasta.as_bytes()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].name, "first");
+    assert_eq!(records[0].sequence, "GCGGAUUUAGCUCAGC");
+    assert_eq!(records[0].get_dot_bracket_string().unwrap(), "((((....))))....");
+    assert_eq!(records[1].sequence, "AAAA");
+
+    assert!(io::parse_fasta_structure(">truncated\nGGGAAACCC\n".as_bytes()).is_err());
+
+    // long sequences are wrapped, and read back unchanged
+    let mut ss: SecondaryStructureRecord = format!("({})", ".".repeat(198)).parse().unwrap();
+    ss.set_sequence("ACGU".repeat(50));
+    ss.name = "long".to_string();
+    let mut buffer = Vec::new();
+    io::write_fasta_structure(&mut buffer, &ss).unwrap();
+    let lines = String::from_utf8(buffer.clone()).unwrap().lines().map(|l| l.len()).collect::<Vec<usize>>();
+    assert_eq!(lines, vec![5, 80, 80, 40, 200]);
+    let records = io::parse_fasta_structure(&buffer[..]).unwrap();
+    assert_eq!(records[0].sequence, ss.sequence);
+    assert_eq!(records[0].paired, ss.paired);
+}
+
+#[test]
+fn test_parse_vienna_string() {
+    let rnafold_output = ">folded
+GGGCGAAAGCCC
+((((....)))) (-5.40)
+>unfolded
+AAAAAA
+......
+";
+    let records = io::parse_vienna_string(rnafold_output).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].0.name, "folded");
+    assert_eq!(records[0].0.get_dot_bracket_string().unwrap(), "((((....))))");
+    assert_eq!(records[0].1, Some(-5.40));
+    assert_eq!(records[0].0.energy, Some(-5.40));
+    assert_eq!(records[1].0.name, "unfolded");
+    assert_eq!(records[1].0.sequence, "AAAAAA");
+    assert_eq!(records[1].1, None);
+
+    // RNAfold pads energies to a fixed width and omits the name line for unnamed input
+    let records = io::parse_vienna_string("GGGAAACCC\n(((...))) ( -1.20)\n").unwrap();
+    assert_eq!(records[0].0.name, "");
+    assert_eq!(records[0].1, Some(-1.2));
+
+    assert!(io::parse_vienna_string("GGGAAACCC\n(((...))) -1.20\n").is_err());
+    assert!(io::parse_vienna_string(">truncated\nGGGAAACCC\n").is_err());
+}
+
+#[test]
+fn test_parse_st_string_pseudoknot() {
+    let st_string = "#Name: bpRNA_pseudoknot
+#Length: 20
+#PageNumber: 2
+GGCGAAGGAAACGCAACCAA
+(((..[[...)))..]]...
+SSSHHSSHHHSSSXXSSEEE
+NNNNNNNNNNNNNNNNNNNN
+S1 1..3 \"GGC\" 11..13 \"CGC\"
+PK1 6..7 16..17
+";
+    let ss = io::parse_st_string(st_string).unwrap();
+    assert_eq!(ss.name, "bpRNA_pseudoknot");
+    assert_eq!(ss.base_pairs(), vec![(0, 12), (1, 11), (2, 10), (5, 16), (6, 15)]);
+    assert_eq!(ss.annotation, Some("SSSHHSSHHHSSSXXSSEEE".to_string()));
+    assert!(secondary_structure::is_pseudoknotted(&ss).unwrap());
+
+    // letter pseudoknot brackets, as used for higher order pseudoknots
+    let ss = io::parse_st_string("#Name: letters\nGGAACCAUGG\n((Aa..))..\n").unwrap();
+    assert_eq!(ss.base_pairs(), vec![(0, 7), (1, 6), (2, 3)]);
+}
+
+#[test]
+fn test_parse_ct_string_strict() {
+    let ct_string = "4\tENERGY = -1.5\tfirst\n1\tG\t0\t2\t4\t1\n2\tA\t1\t3\t0\t2\n3\tA\t2\t4\t0\t3\n4\tC\t3\t5\t1\t4\n>second\n1\tA\t0\t2\t0\t1\n2\tA\t1\t3\t0\t2\n";
+    let records = io::parse_ct_string_strict(&ct_string.to_string()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].name, "first");
+    assert_eq!(records[0].energy, Some(-1.5));
+    assert_eq!(records[0].paired, vec![4, 0, 0, 1]);
+    assert_eq!(records[1].paired, vec![0, 0]);
+
+    let strict_error = |ct_string: &str| io::parse_ct_string_strict(&ct_string.to_string()).err();
+
+    // skipped index line
+    let skipped = ">skipped\n1\tG\t0\t2\t4\t1\n2\tA\t1\t3\t0\t2\n4\tC\t3\t5\t1\t4\n";
+    assert!(matches!(strict_error(skipped), Some(io::CtParseError::NonSequentialIndex { line: 4, expected: 3, found: 4 })));
+
+    // self-referential pairing
+    let self_paired = ">self\n1\tG\t0\t2\t0\t1\n2\tA\t1\t3\t2\t2\n3\tC\t2\t4\t0\t3\n";
+    assert!(matches!(strict_error(self_paired), Some(io::CtParseError::SelfPairing { line: 3 })));
+    // which the lenient parser silently accepts
+    assert_eq!(io::parse_ct_string(&self_paired.to_string()).unwrap()[0].paired, vec![0, 2, 0]);
+
+    let column_shifted = ">shifted\n1\tG\t0\t2\t3\t1\n2\tA\t1\t3\t0\n3\tC\t2\t4\t1\t3\n";
+    assert!(matches!(strict_error(column_shifted), Some(io::CtParseError::WrongColumnCount { line: 3, found: 5 })));
+
+    let out_of_range = ">range\n1\tG\t0\t2\t7\t1\n2\tC\t1\t3\t0\t2\n";
+    assert!(matches!(strict_error(out_of_range), Some(io::CtParseError::OutOfRangePartner { line: 2, partner: 7, len: 2 })));
+
+    let truncated = "3\ttruncated\n1\tG\t0\t2\t0\t1\n2\tC\t1\t3\t0\t2\n";
+    assert!(matches!(strict_error(truncated), Some(io::CtParseError::TruncatedRecord { line: 1, expected: 3, found: 2 })));
+
+    let bad_integer = ">bad\n1\tG\t0\t2\tx\t1\n2\tC\t1\t3\t1\t2\n";
+    assert!(matches!(strict_error(bad_integer), Some(io::CtParseError::BadInteger { line: 2 })));
+
+    let bad_header = "three\tbad_header\n1\tG\t0\t2\t0\t1\n";
+    assert!(matches!(strict_error(bad_header), Some(io::CtParseError::ExpectedHeader { line: 1 })));
+
+    // position 3 claims position 1 as its partner, but position 1 is paired with position 2
+    let asymmetric = ">asymmetric\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t1\t2\n3\tC\t2\t4\t1\t3\n";
+    assert!(matches!(strict_error(asymmetric), Some(io::CtParseError::InvalidStructure { source: secondary_structure::StructureParseError::InconsistentPairing { .. } })));
+
+    // errors remain usable where a Box<dyn Error> is expected
+    let boxed: Box<dyn std::error::Error> = io::parse_ct_string_strict(&truncated.to_string()).unwrap_err().into();
+    assert!(boxed.to_string().contains("header declares 3 positions"));
+}
+
+#[test]
+fn test_parse_ct_strict_io_error() {
+    struct FailingReader;
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("unreadable"))
+        }
+    }
+
+    let first = ">first\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t1\t2\n".as_bytes();
+    let result = io::parse_ct_strict(std::io::BufReader::new(first.chain(FailingReader)));
+    assert!(matches!(result, Err(io::CtParseError::Io { .. })));
+}
+
+#[test]
+fn test_append_ct_and_dbn_files() {
+    let mut ss1: SecondaryStructureRecord = "((..))".parse().unwrap();
+    ss1.set_sequence("GCAAGC".to_string());
+    ss1.name = "first".to_string();
+    let mut ss2: SecondaryStructureRecord = ".(...)".parse().unwrap();
+    ss2.set_sequence("AGAAAC".to_string());
+    ss2.name = "second".to_string();
+
+    let path = std::env::temp_dir().join("rna_secondary_structure_append.ct");
+    io::write_ct_file(&path, &ss2).unwrap();
+    // the first write truncates what was there before
+    io::write_ct_file_append(&path, &ss1, false).unwrap();
+    io::write_ct_file_append(&path, &ss2, true).unwrap();
+    let records = io::read_ct_file(std::fs::File::open(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].name.as_str(), &records[0].paired), ("first", &ss1.paired));
+    assert_eq!((records[1].name.as_str(), &records[1].paired), ("second", &ss2.paired));
+
+    let path = std::env::temp_dir().join("rna_secondary_structure_append.dbn");
+    io::write_dbn_file(&path, &ss1).unwrap();
+    io::write_dbn_file_append(&path, &ss2, true).unwrap();
+    let records = io::read_dbn_file(std::fs::File::open(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].sequence, "AGAAAC");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_round_trip() {
+    let mut ss: SecondaryStructureRecord = "((..[[.))..]]".parse().unwrap();
+    ss.set_sequence("GGAACCAUCAAGG".to_string());
+    ss.name = "json \"quoted\"".to_string();
+    ss.energy = Some(-1.5);
+    ss.numbering = Some((10..23).collect());
+
+    let ss2 = io::from_json(&io::to_json(&ss)).unwrap();
+    assert_eq!(ss2.name, ss.name);
+    assert_eq!(ss2.sequence, ss.sequence);
+    assert_eq!(ss2.paired, ss.paired);
+    assert_eq!(ss2.energy, ss.energy);
+    assert_eq!(ss2.annotation, None);
+    assert_eq!(ss2.numbering, ss.numbering);
+
+    assert!(io::from_json("{\"name\": \"missing fields\"}").is_err());
+}
+
+#[test]
+fn test_iter_ct_file() {
+    let ct_string = "3\tENERGY = -1.5\tfirst
+1\tG\t0\t2\t3\t1
+2\tA\t1\t3\t0\t2
+3\tC\t2\t4\t1\t3
+>second
+1\tG\t0\t2\t3\t1
+2\tA\t1\t3\tx\t2
+3\tC\t2\t4\t1\t3
+>third
+1\tG\t0\t2\t2\t1
+2\tC\t1\t3\t1\t2
+
+";
+    let records: Vec<Result<SecondaryStructureRecord, Box<dyn std::error::Error>>> = io::iter_ct_file(ct_string.as_bytes()).collect();
+    assert_eq!(records.len(), 3);
+    let first = records[0].as_ref().unwrap();
+    assert_eq!((first.name.as_str(), first.energy, first.paired.clone()), ("first", Some(-1.5), vec![3, 0, 1]));
+    assert!(records[1].as_ref().unwrap_err().to_string().contains("Line 7"));
+    assert_eq!(records[2].as_ref().unwrap().paired, vec![2, 1]);
+
+    // asymmetric pairings are reported, and do not end the iteration
+    let asymmetric = ">asymmetric\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t0\t2\n>valid\n1\tA\t0\t2\t0\t1\n";
+    let records: Vec<bool> = io::iter_ct_file(asymmetric.as_bytes()).map(|ss| ss.is_ok()).collect();
+    assert_eq!(records, vec![false, true]);
+}
+
+#[test]
+fn test_iter_ct_file_is_lazy() {
+    struct FailingReader;
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("read past the first record"))
+        }
+    }
+
+    let first = ">first\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t1\t2\n>second\n".as_bytes();
+    let mut records = io::iter_ct_file(std::io::BufReader::new(first.chain(FailingReader)));
+    assert_eq!(records.next().unwrap().unwrap().name, "first");
+    assert!(records.next().unwrap().is_err());
+}
+
+#[test]
+fn test_read_any() {
+    let ct = "\n3\tENERGY = -1.5\thairpin\n1\tG\t0\t2\t3\t1\n2\tA\t1\t3\t0\t2\n3\tC\t2\t4\t1\t3\n";
+    let records = io::read_any(ct.as_bytes()).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!((records[0].name.as_str(), records[0].energy, records[0].paired.clone()), ("hairpin", Some(-1.5), vec![3, 0, 1]));
+
+    let ct = ">first\n1\tG\t0\t2\t2\t1\n2\tC\t1\t3\t1\t2\n>second\n1\tA\t0\t2\t0\t1\n";
+    assert_eq!(io::read_any(ct.as_bytes()).unwrap().len(), 2);
+
+    let dbn = ">first\nGGGAAACCC\n(((...)))\n\n>second\nGACGAAAGUC\n((.(..).))\n";
+    let records = io::read_any(dbn.as_bytes()).unwrap();
+    assert_eq!(records.iter().map(|ss| ss.name.as_str()).collect::<Vec<&str>>(), vec!["first", "second"]);
+    assert_eq!(records[1].paired, vec![10, 9, 0, 7, 0, 0, 4, 0, 2, 1]);
+
+    let stockholm = "# STOCKHOLM 1.0\n#=GF AC   RF00001\nseq1  GGAAACC\n#=GC SS_cons <<___>>\n#=GC RF      GGAAACC\n//\n";
+    let records = io::read_any(stockholm.as_bytes()).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!((records[0].name.as_str(), records[0].sequence.as_str()), ("RF00001", "GGAAACC"));
+    assert_eq!(records[0].paired, vec![7, 6, 0, 0, 0, 2, 1]);
+
+    let st = "#Name: example\n#Length: 5\nGAAAC\n(...)\nSHHHS\nNNNNN\n";
+    let records = io::read_any(st.as_bytes()).unwrap();
+    assert_eq!((records[0].name.as_str(), records[0].paired.clone()), ("example", vec![5, 0, 0, 0, 1]));
+
+    assert!(io::read_any("\n\n".as_bytes()).unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_dotplot_ps() {
+    let dotplot = "%!PS-Adobe-3.0 EPSF-3.0
+%%Title: RNA Dot Plot
+/DPdict 100 dict def
+/sequence { (\\
+GGGGAAAACCCC\\
+UU\\
+) } def
+/len { sequence length } bind def
+/ubox {
+   logscale {
+      log dup add lpmin div 1 exch sub dup 0 lt { pop 0 } if
+   } if
+   3 1 roll
+   exch len exch sub 1 add box
+} bind def
+%start of base pair probability data
+1 12 0.9 ubox
+2 11 0.5 ubox
+3 10 0.1 ubox
+1 12 0.9486833 lbox
+showpage
+end
+%%EOF
+";
+    let (sequence, probabilities) = io::parse_dotplot_ps(dotplot.as_bytes()).unwrap();
+    assert_eq!(sequence, "GGGGAAAACCCCUU");
+    let expected = [(0, 11, 0.81), (1, 10, 0.25), (2, 9, 0.01)];
+    assert_eq!(probabilities.len(), expected.len());
+    for ((i, j, p), (ei, ej, ep)) in probabilities.iter().zip(expected.iter()) {
+        assert_eq!((i, j), (ei, ej));
+        assert!((p - ep).abs() < 1e-12);
+    }
+
+    assert!(io::parse_dotplot_ps("1 12 0.9 ubox\n".as_bytes()).is_err());
+    assert!(io::parse_dotplot_ps("/sequence { (GGCC) } def\n1 x 0.9 ubox\n".as_bytes()).is_err());
+    assert_eq!(io::parse_dotplot_ps("/sequence { (GGCC) } def\n".as_bytes()).unwrap().0, "GGCC");
+}
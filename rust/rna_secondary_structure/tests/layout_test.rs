@@ -0,0 +1,21 @@
+extern crate rna_secondary_structure;
+
+use crate::rna_secondary_structure::layout::arc_diagram;
+use crate::rna_secondary_structure::secondary_structure::{base_pairs, from_dotbracketstring};
+
+#[test]
+fn test_arc_diagram() {
+    let paired = from_dotbracketstring("((.((...)).))..[[..((..]].))").unwrap();
+    let arcs = arc_diagram(&paired);
+    assert_eq!(arcs.iter().map(|arc| (arc.i, arc.j)).collect::<Vec<(usize, usize)>>(), base_pairs(&paired));
+    for arc in arcs.iter() {
+        assert_eq!(arc.radius, (arc.j - arc.i) as f64 / 2.0);
+        assert_eq!(arc.center_x - arc.radius, arc.i as f64);
+        assert_eq!(arc.center_x + arc.radius, arc.j as f64);
+    }
+    // the last helix crosses the helix [[..]] that opens before it
+    let pages = arcs.iter().map(|arc| arc.page).collect::<Vec<usize>>();
+    assert_eq!(pages, vec![0, 0, 0, 0, 0, 0, 1, 1]);
+
+    assert!(arc_diagram(&vec![0, 0, 0]).is_empty());
+}
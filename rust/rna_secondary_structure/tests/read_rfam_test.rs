@@ -0,0 +1,187 @@
+extern crate rna_secondary_structure;
+
+use crate::rna_secondary_structure::read_rfam::*;
+use crate::rna_secondary_structure::secondary_structure::{base_pairs, from_dotbracketstring, SecondaryStructureRecord};
+
+const INTERLEAVED_STOCKHOLM: &str = "# STOCKHOLM 1.0
+#=GF AC   RF00001
+#=GF ID   example1
+
+seqA              GGGA-AACCC
+seqB              GG-AAAA-CC
+#=GC SS_cons      <<<-____>>
+
+seqA              AA
+seqB              AG
+#=GC SS_cons      >:
+//
+# STOCKHOLM 1.0
+#=GF AC   RF00002
+seqC              CCAAAGG
+#=GC SS_cons      ((...))
+//
+";
+
+#[test]
+fn test_write_stockholm_as_dbn() {
+    let mut buffer = Vec::new();
+    let count = write_stockholm_as_dbn(INTERLEAVED_STOCKHOLM.as_bytes(), &mut buffer).unwrap();
+    assert_eq!(count, 3);
+    assert_eq!(String::from_utf8(buffer).unwrap(), ">seqA
+GGGAAACCCAA
+(((....))).
+>seqB
+GGAAAACCAG
+((.....)).
+>seqC
+CCAAAGG
+((...))
+");
+}
+
+#[test]
+fn test_write_stockholm_as_dbn_reports_accession() {
+    let stockholm = format!("{}# STOCKHOLM 1.0
+#=GF AC   RF00003
+seqD              CCAAAGG
+#=GC SS_cons      ((...)
+//
+", INTERLEAVED_STOCKHOLM);
+
+    let mut buffer = Vec::new();
+    let err = write_stockholm_as_dbn(stockholm.as_bytes(), &mut buffer).unwrap_err();
+    assert!(err.to_string().contains("RF00003"));
+    // records from the preceding families were already written
+    assert_eq!(String::from_utf8(buffer).unwrap().matches('>').count(), 3);
+
+    let truncated = "# STOCKHOLM 1.0\n#=GF AC   RF00004\nseqE  CCAAAGGA\n#=GC SS_cons      ((...))\n//\n";
+    let err = write_stockholm_as_dbn(truncated.as_bytes(), &mut Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("RF00004"));
+}
+
+#[test]
+fn test_parse_rfam_stockholm_full() {
+    let alignments = parse_rfam_stockholm_full(INTERLEAVED_STOCKHOLM.as_bytes()).unwrap();
+    assert_eq!(alignments.len(), 2);
+    assert_eq!(alignments[0].accession, "RF00001");
+    assert_eq!(alignments[0].consensus_structure, vec![11, 10, 9, 0, 0, 0, 0, 0, 3, 2, 1, 0]);
+    assert_eq!(alignments[0].rows, vec![("seqA".to_string(), "GGGA-AACCCAA".to_string()), ("seqB".to_string(), "GG-AAAA-CCAG".to_string())]);
+    assert_eq!(alignments[1].accession, "RF00002");
+    assert_eq!(alignments[1].rows, vec![("seqC".to_string(), "CCAAAGG".to_string())]);
+
+    assert!(parse_rfam_stockholm_full("# STOCKHOLM 1.0\n#=GF AC   RF00003\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_project_consensus() {
+    // three gapped columns, dropping the base-pairs (2,14), (3,13) and (5,11)
+    let consensus = from_dotbracketstring("(((.((...)).)))").unwrap();
+    let ss = project_consensus(&consensus, "GG-AGCUAAG.CA-C", SEQUENCE_GAP_CHARS);
+    assert_eq!(ss.sequence, "GGAGCUAAGCAC");
+    assert_eq!(ss.paired, vec![12, 0, 0, 0, 9, 0, 0, 0, 5, 0, 0, 1]);
+
+    let ss = project_consensus(&consensus, "GG~AGCUAAG.CA-C", "~");
+    assert_eq!(ss.sequence, "GGAGCUAAG.CA-C");
+    assert_eq!(ss.paired, vec![14, 13, 0, 10, 9, 0, 0, 0, 5, 4, 0, 0, 2, 1]);
+
+    // a partner beyond the end of the consensus structure is treated as a gap
+    let ss = project_consensus(&[9, 0, 0, 0], "GAAC", SEQUENCE_GAP_CHARS);
+    assert_eq!(ss.paired, vec![0, 0, 0, 0]);
+}
+
+#[test]
+fn test_write_stockholm() {
+    let alignments = parse_rfam_stockholm_full(INTERLEAVED_STOCKHOLM.as_bytes()).unwrap();
+    let records = alignments[0].rows.iter().map(|(name, aligned)| {
+        let mut ss = SecondaryStructureRecord::new(vec![0; aligned.len()]);
+        ss.name = name.clone();
+        ss.set_sequence(aligned.clone());
+        ss
+    }).collect::<Vec<SecondaryStructureRecord>>();
+
+    let mut buffer = Vec::new();
+    write_stockholm(&mut buffer, "RF00001", &records.iter().collect::<Vec<&SecondaryStructureRecord>>(), &alignments[0].consensus_structure).unwrap();
+    let reread = parse_rfam_stockholm_full(buffer.as_slice()).unwrap();
+    assert_eq!(reread, vec![alignments[0].clone()]);
+
+    let err = write_stockholm(&mut Vec::new(), "RF00001", &[&records[0]], &[0, 0, 0]).unwrap_err();
+    assert!(err.to_string().contains("RF00001"));
+}
+
+#[test]
+fn test_consensus_compatibility() {
+    let mut records = iter_rfam_stockholm(INTERLEAVED_STOCKHOLM.as_bytes());
+    let alignment = records.next().unwrap().unwrap();
+    let members = alignment.rows.iter().map(|(_, aligned)| aligned.as_str()).collect::<Vec<&str>>();
+
+    // the outer consensus pair is G-A in both members; seqB also has a gap opposite C
+    let compatibility = consensus_compatibility(&alignment.consensus_structure, &members).unwrap();
+    assert_eq!(compatibility, vec![2.0 / 3.0, 1.0 / 3.0]);
+
+    assert!(consensus_compatibility(&alignment.consensus_structure, &["GGGAAACCC"]).is_err());
+    assert_eq!(consensus_compatibility(&vec![0, 0, 0], &["A-C"]).unwrap(), vec![1.0]);
+}
+
+#[test]
+fn test_structure_conservation_index() {
+    let consensus = from_dotbracketstring("((((...))))").unwrap();
+    let conserved = from_dotbracketstring("((((...))))").unwrap();
+    let shifted = from_dotbracketstring(".(((...))).").unwrap();
+    let unrelated = from_dotbracketstring("((.....))..").unwrap();
+    assert_eq!(structure_conservation_index(&[&conserved], &consensus).unwrap(), 1.0);
+    assert_eq!(structure_conservation_index(&[&conserved, &shifted, &unrelated], &consensus).unwrap(), (1.0 + 0.75 + 0.0) / 3.0);
+    assert_eq!(structure_conservation_index(&[], &consensus).unwrap(), 0.0);
+
+    let truncated = from_dotbracketstring("((...))").unwrap();
+    assert!(structure_conservation_index(&[&conserved, &truncated], &consensus).is_err());
+}
+
+#[test]
+fn test_parse_rfam_stockholm_wuss() {
+    let stockholm = "# STOCKHOLM 1.0
+#=GF AC   RF00005
+seq1              GCGGAUUUAGCUCAGUUGGGAGAGCGCCAGACUGAAGAUCUGGAGGUCCUGUGUUCGAUCCACAGAAUUCGCACCA
+#=GC SS_cons      (((((((,,<<<<_______.____>>>>,<<<<<_______>>>>>,,,,,<<<<<_______>>>>>))))))):::::
+#=GC RF           gCgGAuUuAGCUCAGuuGGGAGAGCgccagaCUgaaGAucUggAGgUCcUGUGuuCGAuCCACAGAAUUCGCAcca
+//
+";
+    let records = parse_rfam_stockholm(stockholm.as_bytes()).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name, "RF00005");
+    assert_eq!(records[0].base_pairs().len(), 21);
+    assert_eq!(records[0].base_pairs()[0], (0, 75));
+}
+
+#[test]
+fn test_alignment_base_pairs() {
+    assert_eq!(alignment_base_pairs(":<<<____>>>:").unwrap(), vec![(1, 10), (2, 9), (3, 8)]);
+    assert!(alignment_base_pairs("....").unwrap().is_empty());
+    assert!(alignment_base_pairs("<<__>").is_err());
+
+    // the column pairs of a parsed alignment's consensus structure
+    let alignments = parse_rfam_stockholm_full(INTERLEAVED_STOCKHOLM.as_bytes()).unwrap();
+    assert_eq!(alignment_base_pairs("<<<-____>>>:").unwrap(), base_pairs(&alignments[0].consensus_structure));
+}
+
+#[test]
+fn test_covariation_scores() {
+    // the outer pair shows perfect compensatory covariation across all four Watson-Crick pairs,
+    // while the inner pair is conserved as A-U
+    let rows = ["GAAAUC", "CAAAUG", "AAAAUU", "UAAAUA"].iter().enumerate()
+        .map(|(k, aligned)| (format!("seq{}", k), aligned.to_string()))
+        .collect::<Vec<(String, String)>>();
+    let pairs = alignment_base_pairs("(<..>)").unwrap();
+    assert_eq!(pairs, vec![(0, 5), (1, 4)]);
+    let scores = covariation_scores(&rows, &pairs);
+    assert!((scores[0] - 2.0).abs() < 1e-12);
+    assert_eq!(scores[1], 0.0);
+
+    // a row gapped in both columns is ignored; one gapped in a single column is not
+    let mut gapped = rows.clone();
+    gapped.push(("gap-gap".to_string(), "-AAAU.".to_string()));
+    assert_eq!(covariation_scores(&gapped, &pairs), scores);
+    gapped.push(("gap".to_string(), "-AAAUC".to_string()));
+    assert!(covariation_scores(&gapped, &pairs)[0] != scores[0]);
+
+    assert_eq!(covariation_scores(&[], &pairs), vec![0.0, 0.0]);
+}
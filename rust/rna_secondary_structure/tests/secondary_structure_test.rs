@@ -1,6 +1,10 @@
 extern crate rna_secondary_structure;
 
-use crate::rna_secondary_structure::secondary_structure::SecondaryStructureRecord;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::rna_secondary_structure::secondary_structure::{abstract_shape, base_pairs, can_add_pair_nested, crossing_pairs, decompose_loops, from_base_pairs, from_dotbracketstring, from_dotbracketstring_strict, from_wuss_string, get_dot_bracket_string, get_dot_bracket_string_minimal, get_dot_bracket_string_with, get_matching_bracket, get_wuss_string, helices, is_planar, is_pseudoknotted, LEFT_BRACKETS, lonely_pairs, Loop, max_nesting_depth, mfe_structure, multiloop_branch_points, neighbor_count, num_base_pairs, num_wobble_pairs, paired_fraction, PairedSites, pseudoknot_order, remove_lonely_pairs, remove_pseudoknots, SecondaryStructureRecord, shift, structure_signature, StructureParseError, structures_within, trim_terminal_unpaired, unpaired_regions, wobble_pairs};
 
 #[test]
 fn test_to_dotbracketstring() {
@@ -14,4 +18,692 @@ fn test_from_dotbracketstring() {
     let ss: SecondaryStructureRecord = "<(A..a)..>..".parse().unwrap();
     let paired = vec![10, 7, 6, 0, 0, 3, 2, 0, 0, 1, 0, 0];
     assert_eq!(ss.paired, paired);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_mfe_structure_and_structures_within() {
+    let mut records: Vec<SecondaryStructureRecord> = vec!["((....))".parse().unwrap(), "(......)".parse().unwrap(), "........".parse().unwrap(), "((...).)".parse().unwrap()];
+    records[0].energy = Some(-2.5);
+    records[1].energy = Some(-1.0);
+    records[3].energy = Some(-2.5);
+
+    let mfe = mfe_structure(&records).unwrap();
+    assert_eq!(mfe.paired, records[0].paired); // ties return the first record
+
+    let within = structures_within(&records, 1.5);
+    assert_eq!(within.len(), 3);
+    assert_eq!(within[1].paired, records[1].paired);
+    assert_eq!(structures_within(&records, 0.0).len(), 2);
+
+    assert!(structures_within(&records[2..3], 10.0).is_empty());
+
+    // a NaN energy is ignored, whether it comes after or before the minimum
+    records[2].energy = Some(f64::NAN);
+    assert_eq!(mfe_structure(&records).unwrap().paired, records[0].paired);
+    assert_eq!(structures_within(&records, 1.5).len(), 3);
+    records[0].energy = Some(f64::NAN);
+    assert_eq!(mfe_structure(&records).unwrap().paired, records[3].paired);
+    assert_eq!(structures_within(&records, 1.5).len(), 2);
+    assert!(mfe_structure(&records[2..3]).is_none());
+}
+
+#[test]
+fn test_is_planar_multi_strand() {
+    // (0, 3) is intramolecular on the first strand and crosses the intermolecular pair (1, 6)
+    let paired = from_dotbracketstring("([.)..]").unwrap();
+    assert!(!is_planar(&paired, &[]));
+    assert!(is_planar(&paired, &[5]));
+
+    // crossing pairs that are both within the second strand are still detected
+    let paired = from_dotbracketstring("(..)([..)..]").unwrap();
+    assert!(!is_planar(&paired, &[4]));
+    assert!(is_planar(&paired, &[4, 7]));
+}
+
+#[test]
+fn test_multiloop_branch_points() {
+    // tRNA-like cloverleaf with a single four-way junction closed by the acceptor stem
+    let cloverleaf = from_dotbracketstring("(((((((..((((........)))).(((((.......))))).....(((((.......))))))))))))....").unwrap();
+    let branches = multiloop_branch_points(&cloverleaf).unwrap();
+    assert_eq!(branches, vec![vec![(9, 24), (26, 42), (48, 64)]]);
+
+    // nested multiloops are reported separately, the outer one first
+    let nested = from_dotbracketstring("((((...))((..((...))((...))..))))").unwrap();
+    let branches = multiloop_branch_points(&nested).unwrap();
+    assert_eq!(branches, vec![vec![(2, 8), (9, 30)], vec![(13, 19), (20, 26)]]);
+
+    assert!(multiloop_branch_points(&from_dotbracketstring("((...))((...))").unwrap()).unwrap().is_empty());
+
+    match multiloop_branch_points(&from_dotbracketstring("((..[[..))..]]").unwrap()) {
+        Err(StructureParseError::Pseudoknotted) => {}
+        _ => panic!("expected a pseudoknot error")
+    }
+}
+
+#[test]
+fn test_can_add_pair_nested() {
+    let paired = from_dotbracketstring("((...))....((...))").unwrap();
+    assert!(can_add_pair_nested(&paired, 7, 10, 2));
+    assert!(!can_add_pair_nested(&paired, 7, 10, 3));
+    assert!(can_add_pair_nested(&paired, 7, 10, 0));
+    assert!(!can_add_pair_nested(&paired, 7, 12, 0));
+    assert!(!can_add_pair_nested(&paired, 4, 8, 0));
+    assert!(!can_add_pair_nested(&paired, 8, 7, 0));
+    assert!(!can_add_pair_nested(&paired, 0, 100, 0));
+
+    // adding the pair keeps the structure non-pseudoknotted
+    let mut paired = paired;
+    paired[7] = 11;
+    paired[10] = 8;
+    assert!(is_planar(&paired, &[]));
+}
+
+#[test]
+fn test_structure_signature() {
+    // structures differing only in loop sizes and bulges share a signature
+    let s1 = structure_signature(&from_dotbracketstring("(((...)))..((((....))))").unwrap()).unwrap();
+    let s2 = structure_signature(&from_dotbracketstring(".((.(.....)))((((..)).))..").unwrap()).unwrap();
+    assert_eq!(s1, "[3][4]");
+    assert_eq!(s1, s2);
+
+    let s3 = structure_signature(&from_dotbracketstring("((((...))))..((((....))))").unwrap()).unwrap();
+    assert_ne!(s1, s3);
+
+    assert_eq!(structure_signature(&from_dotbracketstring(".....").unwrap()).unwrap(), "_");
+    assert!(structure_signature(&from_dotbracketstring("((..[[..))..]]").unwrap()).is_err());
+}
+
+#[test]
+fn test_neighbor_count() {
+    // the open chain of length 6 with mingap 3 can only form (0, 4), (0, 5), and (1, 5)
+    assert_eq!(neighbor_count(&from_dotbracketstring("......").unwrap(), 3), 3);
+    assert_eq!(neighbor_count(&from_dotbracketstring("......").unwrap(), 0), 15);
+    // removing either pair, or adding (3, 4) inside the helix, or (0, 7), (0, 8), (7, 8) outside it
+    assert_eq!(neighbor_count(&from_dotbracketstring(".((..))..").unwrap(), 0), 2 + 4);
+}
+
+#[test]
+fn test_trim_terminal_unpaired() {
+    let (trimmed, five_prime, three_prime) = trim_terminal_unpaired(&from_dotbracketstring("...(.(..)).((...)).").unwrap());
+    assert_eq!(trimmed, from_dotbracketstring("(.(..)).((...))").unwrap());
+    assert_eq!((five_prime, three_prime), (3, 1));
+
+    let paired = from_dotbracketstring("((..))").unwrap();
+    assert_eq!(trim_terminal_unpaired(&paired), (paired.clone(), 0, 0));
+    assert_eq!(trim_terminal_unpaired(&from_dotbracketstring("....").unwrap()), (vec![], 4, 0));
+}
+
+#[test]
+fn test_wobble_pairs() {
+    let mut ss = SecondaryStructureRecord::new(from_dotbracketstring("(((..)))((..))").unwrap());
+    ss.sequence = "GCgAAuGUUGAAuG".to_string();
+    assert_eq!(wobble_pairs(&ss), vec![(0, 7), (2, 5), (8, 13), (9, 12)]);
+    assert_eq!(num_wobble_pairs(&ss), 4);
+
+    // pairs beyond the end of a truncated sequence are skipped
+    ss.sequence = "GCgAAuGUUG".to_string();
+    assert_eq!(wobble_pairs(&ss), vec![(0, 7), (2, 5)]);
+}
+
+#[test]
+fn test_base_pairs() {
+    let ss = SecondaryStructureRecord::new(from_dotbracketstring("((..))").unwrap());
+    assert_eq!(ss.base_pairs(), vec![(0, 5), (1, 4)]);
+
+    let paired = from_dotbracketstring(".(.[..).]").unwrap();
+    assert_eq!(base_pairs(&paired), vec![(1, 6), (3, 8)]);
+    assert!(base_pairs(&from_dotbracketstring("....").unwrap()).is_empty());
+}
+
+#[test]
+fn test_validate() {
+    assert!(SecondaryStructureRecord::new(from_dotbracketstring("((..)).[.]").unwrap()).validate().is_ok());
+
+    let asymmetric = SecondaryStructureRecord::new(vec![6, 5, 0, 0, 2, 2]);
+    assert!(matches!(asymmetric.validate(), Err(StructureParseError::InconsistentPairing { i: 1, j: 6 })));
+
+    let out_of_range = SecondaryStructureRecord::new(vec![0, 7, 0, 0]);
+    assert!(matches!(out_of_range.validate(), Err(StructureParseError::InconsistentPairing { i: 2, j: 7 })));
+
+    let self_paired = SecondaryStructureRecord::new(vec![0, 0, 3, 0]);
+    assert!(matches!(self_paired.validate(), Err(StructureParseError::InconsistentPairing { i: 3, j: 3 })));
+}
+
+#[test]
+fn test_remove_pseudoknots() {
+    // H-type pseudoknot: the two-pair helix is removed, the three-pair helix kept
+    let paired = from_dotbracketstring("..(((..[[..)))..]]..").unwrap();
+    assert_eq!(remove_pseudoknots(&paired), from_dotbracketstring("..(((......)))......").unwrap());
+
+    // ties keep the 5'-most helix
+    let paired = from_dotbracketstring("((..[[..))..]]").unwrap();
+    assert_eq!(remove_pseudoknots(&paired), from_dotbracketstring("((......))....").unwrap());
+
+    for dbs in &["..(((..[[..)))..]]..", "A..<<<..a...>>>....", "((.[[.{{.)).]].}}", "(((...)))", "((..[..))..]..((..))"] {
+        let paired = from_dotbracketstring(dbs).unwrap();
+        let nested = remove_pseudoknots(&paired);
+        assert!(!is_pseudoknotted(&nested).unwrap());
+        // every retained pair was in the original structure
+        assert!(base_pairs(&nested).iter().all(|pair| base_pairs(&paired).contains(pair)));
+    }
+
+    let nested = from_dotbracketstring("<<<..<<<.<..>>.>..>..>...<<...>..>>.>").unwrap();
+    assert_eq!(remove_pseudoknots(&nested), nested);
+}
+
+#[test]
+fn test_to_rna_to_dna() {
+    let mut ss = SecondaryStructureRecord::new(from_dotbracketstring("(..)").unwrap());
+    ss.sequence = "ATGC".to_string();
+    ss.to_rna();
+    assert_eq!(ss.sequence, "AUGC");
+    ss.to_dna();
+    assert_eq!(ss.sequence, "ATGC");
+    assert_eq!(ss.paired, from_dotbracketstring("(..)").unwrap());
+
+    ss.sequence = "acgtN-u".to_string();
+    ss.to_rna();
+    assert_eq!(ss.sequence, "acguN-u");
+    ss.to_dna();
+    assert_eq!(ss.sequence, "acgtN-t");
+}
+
+#[test]
+fn test_reverse_complement() {
+    let mut ss = SecondaryStructureRecord::new(from_dotbracketstring(".((.[..)).]").unwrap());
+    ss.sequence = "ACGuAGGCGcU".to_string();
+    let rc = ss.reverse_complement();
+    assert_eq!(rc.sequence, "AgCGCCUaCGU");
+    assert_eq!(rc.paired, from_dotbracketstring("[.((..].)).").unwrap());
+    assert!(rc.validate().is_ok());
+
+    let rc2 = rc.reverse_complement();
+    assert_eq!(rc2.sequence, ss.sequence);
+    assert_eq!(rc2.paired, ss.paired);
+
+    ss.sequence = "ACGTAGGCGCN".to_string();
+    assert_eq!(ss.reverse_complement().sequence, "NGCGCCTACGT");
+    assert_eq!(ss.reverse_complement().reverse_complement().sequence, ss.sequence);
+}
+
+#[test]
+fn test_decompose_loops_cloverleaf() {
+    // tRNA-like cloverleaf: acceptor stem closing a multiloop with three hairpin arms
+    let paired = from_dotbracketstring("(((((((..((((........)))).(((((.......))))).....(((((.......))))))))))))....").unwrap();
+    let loops = decompose_loops(&paired).unwrap();
+
+    let hairpins = loops.iter().filter(|l| matches!(l, Loop::Hairpin { .. })).count();
+    let multiloops = loops.iter().filter(|l| matches!(l, Loop::Multiloop { .. })).collect::<Vec<&Loop>>();
+    let stacks = loops.iter().filter(|l| matches!(l, Loop::Stack { .. })).count();
+    assert_eq!(hairpins, 3);
+    assert_eq!(multiloops, vec![&Loop::Multiloop { closing: (6, 65), branches: vec![(9, 24), (26, 42), (48, 64)], unpaired: vec![7, 8, 25, 43, 44, 45, 46, 47] }]);
+    assert_eq!(stacks, 6 + 3 + 4 + 4);
+    assert_eq!(loops[0], Loop::External { branches: vec![(0, 71)], unpaired: vec![72, 73, 74, 75] });
+    // one loop per base-pair plus the external loop
+    assert_eq!(loops.len(), base_pairs(&paired).len() + 1);
+
+    let loops = decompose_loops(&from_dotbracketstring("((..((...))...))").unwrap()).unwrap();
+    assert!(loops.contains(&Loop::Internal { outer: (1, 14), inner: (4, 10), unpaired: vec![2, 3, 11, 12, 13] }));
+
+    assert!(matches!(decompose_loops(&from_dotbracketstring("((..[[..))..]]").unwrap()), Err(StructureParseError::Pseudoknotted)));
+}
+
+#[test]
+fn test_helices() {
+    assert_eq!(helices(&from_dotbracketstring("(((...)))").unwrap()), vec![vec![(0, 8), (1, 7), (2, 6)]]);
+    assert_eq!(helices(&from_dotbracketstring("((.((...))))").unwrap()).len(), 2);
+    assert_eq!(helices(&from_dotbracketstring("((...))((...))").unwrap()), vec![vec![(0, 6), (1, 5)], vec![(7, 13), (8, 12)]]);
+    assert!(helices(&from_dotbracketstring(".....").unwrap()).is_empty());
+
+    // crossing helices of a pseudoknot are both returned
+    assert_eq!(helices(&from_dotbracketstring("((..[[..))..]]").unwrap()), vec![vec![(0, 9), (1, 8)], vec![(4, 13), (5, 12)]]);
+}
+
+#[test]
+fn test_from_wuss_string() {
+    // tRNA (RF00005) SS_cons
+    let trna = "(((((((,,<<<<_______.____>>>>,<<<<<_______>>>>>,,,,,<<<<<_______>>>>>))))))):";
+    let paired = from_wuss_string(trna).unwrap();
+    assert_eq!(paired.len(), trna.len());
+    assert_eq!(helices(&paired).len(), 4);
+    assert_eq!(base_pairs(&paired).len(), 21);
+
+    // letter-annotated pseudoknot
+    let paired = from_wuss_string("::AAA--<<<__aaa>>>~~").unwrap();
+    assert_eq!(paired, from_dotbracketstring("..[[[..(((..]]])))..").unwrap());
+    assert!(is_pseudoknotted(&paired).unwrap());
+
+    assert!(matches!(from_wuss_string("<<..*>>"), Err(StructureParseError::BracketTypeNotRecognised { c: '*', pos: Some(5) })));
+    // a pseudoknot letter closed before it is opened
+    assert!(matches!(from_wuss_string("<<..x>>"), Err(StructureParseError::MissingLeftParentheses { left: 'X', right: 'x', pos: 5 })));
+    assert!(matches!(from_wuss_string("<<..>"), Err(StructureParseError::MissingRightParentheses { .. })));
+}
+
+#[test]
+fn test_from_dotbracketstring_strict() {
+    // the lenient parser silently treats '*' as unpaired
+    assert_eq!(from_dotbracketstring("((*.))").unwrap(), from_dotbracketstring("((..))").unwrap());
+    let err = from_dotbracketstring_strict("((*.))").unwrap_err();
+    assert!(matches!(err, StructureParseError::BracketTypeNotRecognised { c: '*', pos: Some(3) }));
+    assert_eq!(err.to_string(), "Bracket type not recognised: '*' at position 3");
+    assert_eq!(get_matching_bracket('*').unwrap_err().to_string(), "Bracket type not recognised: '*'");
+
+    // lower case letters close pseudoknot brackets, so an unmatched 'x' is reported as such
+    assert!(matches!(from_dotbracketstring_strict("((x.))"), Err(StructureParseError::MissingLeftParentheses { left: 'X', right: 'x', pos: 3 })));
+
+    assert_eq!(from_dotbracketstring_strict("<<.[[.>>.]]Aa").unwrap(), from_dotbracketstring("<<.[[.>>.]]Aa").unwrap());
+    assert!(matches!(from_dotbracketstring_strict("((.)"), Err(StructureParseError::MissingRightParentheses { .. })));
+}
+
+#[test]
+fn test_pseudoknot_order() {
+    assert_eq!(pseudoknot_order(&from_dotbracketstring("..........").unwrap()), 1);
+    assert_eq!(pseudoknot_order(&from_dotbracketstring("((.((...)).))..((...))").unwrap()), 1);
+    // H-type pseudoknot
+    assert_eq!(pseudoknot_order(&from_dotbracketstring("((((..[[[..))))..]]]").unwrap()), 2);
+    // three mutually crossing helices, nested within an outer helix
+    let paired = from_dotbracketstring("((.((..[[..{{..)).]]..}}.))").unwrap();
+    assert_eq!(pseudoknot_order(&paired), 3);
+    assert!(is_pseudoknotted(&paired).unwrap());
+    // crossing base-pairs can share a page with base-pairs they do not cross
+    assert_eq!(pseudoknot_order(&from_dotbracketstring("(([[..))..]]..((..[[..))..]]").unwrap()), 2);
+}
+
+#[test]
+fn test_get_dot_bracket_string_minimal() {
+    let nested = from_dotbracketstring("((.<<...>>.[[..]]))..{...}").unwrap();
+    assert_eq!(get_dot_bracket_string_minimal(&nested).unwrap(), "((.((...)).((..))))..(...)");
+
+    let paired = from_dotbracketstring("((((..{{{..))))..}}}").unwrap();
+    let dbs = get_dot_bracket_string_minimal(&paired).unwrap();
+    assert_eq!(dbs, "((((..<<<..))))..>>>");
+    assert!(dbs.chars().all(|c| "()<>.".contains(c)));
+    assert_eq!(from_dotbracketstring(&dbs).unwrap(), paired);
+
+    let paired = from_dotbracketstring("((.((..[[..{{..)).]]..}}.))").unwrap();
+    assert_eq!(get_dot_bracket_string_minimal(&paired).unwrap(), "((.((..<<..{{..)).>>..}}.))");
+    assert_eq!(get_dot_bracket_string_minimal(&vec![0, 0]).unwrap(), "..");
+
+    // the greedy assignment needs three bracket types here, but two suffice
+    let paired = from_dotbracketstring("((..((..<<..))..{{..>>..))..}}").unwrap();
+    assert_eq!(get_dot_bracket_string(&paired).unwrap(), "((..((..<<..))..{{..>>..))..}}");
+    let dbs = get_dot_bracket_string_minimal(&paired).unwrap();
+    assert_eq!(dbs, "((..<<..((..>>..<<..))..))..>>");
+    assert_eq!(from_dotbracketstring(&dbs).unwrap(), paired);
+
+    // agrees with the fewest bracket types found by trying every assignment of small matchings
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..500 {
+        let mut positions = (0..12).collect::<Vec<usize>>();
+        positions.shuffle(&mut rng);
+        let mut paired = vec![0; 12];
+        for pair in positions[..rng.gen_range(0..=12)].chunks_exact(2) {
+            paired[pair[0]] = (pair[1] + 1) as i64;
+            paired[pair[1]] = (pair[0] + 1) as i64;
+        }
+        let dbs = get_dot_bracket_string_minimal(&paired).unwrap();
+        assert_eq!(from_dotbracketstring(&dbs).unwrap(), paired);
+
+        let pairs = base_pairs(&paired);
+        let crosses = |(i, j): (usize, usize), (k, l): (usize, usize)| (i < k && k < j && j < l) || (k < i && i < l && l < j);
+        let fewest = (1..=pairs.len().max(1)).find(|k| {
+            (0..k.pow(pairs.len() as u32)).any(|assignment| {
+                let page = |index: usize| (assignment / k.pow(index as u32)) % k;
+                (0..pairs.len()).all(|a| (a + 1..pairs.len()).all(|b| page(a) != page(b) || !crosses(pairs[a], pairs[b])))
+            })
+        }).unwrap();
+        let used = dbs.chars().filter(|c| LEFT_BRACKETS.contains(*c)).collect::<std::collections::HashSet<char>>().len();
+        assert_eq!(used.max(1), fewest, "{}", dbs);
+    }
+}
+
+#[test]
+fn test_get_dot_bracket_string_with() {
+    let paired = from_dotbracketstring("..((..<<..))..>>..").unwrap();
+    assert_eq!(get_dot_bracket_string_with(&paired, '-').unwrap(), "--((--<<--))-->>--");
+    assert_eq!(get_dot_bracket_string_with(&paired, ' ').unwrap(), "  ((  <<  ))  >>  ");
+    assert_eq!(get_dot_bracket_string_with(&paired, '.').unwrap(), get_dot_bracket_string(&paired).unwrap());
+    assert_eq!(get_dot_bracket_string_with(&vec![0, 0, 0], '-').unwrap(), "---");
+
+    for c in ['(', '>', 'A', 'z'].iter() {
+        match get_dot_bracket_string_with(&paired, *c) {
+            Err(StructureParseError::UnpairedCharacterIsBracket { c: bracket }) => assert_eq!(bracket, *c),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+}
+
+#[test]
+fn test_gc_content_and_base_counts() {
+    let mut ss = SecondaryStructureRecord::new(vec![0; 4]);
+    ss.set_sequence("GCGC".to_string());
+    assert_eq!(ss.gc_content(), 1.0);
+    ss.set_sequence("AUAU".to_string());
+    assert_eq!(ss.gc_content(), 0.0);
+    ss.set_sequence("--NN".to_string());
+    assert_eq!(ss.gc_content(), 0.0);
+    ss.set_sequence("gcAT".to_string());
+    assert_eq!(ss.gc_content(), 0.5);
+
+    ss.set_sequence("acGTua".to_string());
+    let counts = ss.base_counts(false);
+    assert_eq!(counts.len(), 5);
+    assert_eq!((counts[&'A'], counts[&'C'], counts[&'G'], counts[&'T'], counts[&'U']), (2, 1, 1, 1, 1));
+    let counts = ss.base_counts(true);
+    assert_eq!(counts.len(), 4);
+    assert_eq!(counts[&'U'], 2);
+    assert!(!counts.contains_key(&'T'));
+}
+
+#[test]
+fn test_unpaired_regions() {
+    // two hairpin loops and the gap between the helices
+    assert_eq!(unpaired_regions(&from_dotbracketstring("((..))..((.))").unwrap()), vec![2..4, 6..8, 10..11]);
+    assert_eq!(unpaired_regions(&from_dotbracketstring("....").unwrap()), vec![0..4]);
+    assert_eq!(unpaired_regions(&from_dotbracketstring("(())").unwrap()), vec![]);
+    assert_eq!(unpaired_regions(&from_dotbracketstring(".((..[[.))..]].").unwrap()), vec![0..1, 3..5, 7..8, 10..12, 14..15]);
+    assert_eq!(unpaired_regions(&vec![]), vec![]);
+}
+
+#[test]
+fn test_record_clone_and_eq() {
+    let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    ss.name = "hairpin".to_string();
+    ss.set_sequence("GCAAGC".to_string());
+    ss.energy = Some(-1.5);
+
+    let mut cloned = ss.clone();
+    assert_eq!(cloned, ss);
+    cloned.name = "other".to_string();
+    assert_ne!(cloned, ss);
+
+    let mut cloned = ss.clone();
+    cloned.energy = None;
+    assert_ne!(cloned, ss);
+    cloned.energy = Some(f64::NAN);
+    assert_eq!(cloned, cloned.clone());
+}
+
+#[test]
+fn test_base_pair_at() {
+    let ss: SecondaryStructureRecord = "((.)).[.]".parse().unwrap();
+    let partners: Vec<Option<usize>> = (0..ss.paired.len()).map(|i| ss.base_pair_at(i)).collect();
+    assert_eq!(partners, vec![Some(4), Some(3), None, Some(1), Some(0), None, Some(8), None, Some(6)]);
+    assert_eq!(ss.base_pair_at(9), None);
+    assert_eq!(ss.paired.base_pair_at(0), Some(4));
+}
+
+#[test]
+fn test_iter_positions() {
+    let mut ss: SecondaryStructureRecord = "(.)".parse().unwrap();
+    ss.set_sequence("ACG".to_string());
+    let positions: Vec<(usize, char, Option<usize>)> = ss.iter_positions().collect();
+    assert_eq!(positions, vec![(0, 'A', Some(2)), (1, 'C', None), (2, 'G', Some(0))]);
+
+    // a sequence longer than the secondary structure is truncated
+    ss.set_sequence("ACGU".to_string());
+    assert_eq!(ss.iter_positions().map(|(_, c, _)| c).collect::<String>(), "ACG");
+}
+
+#[test]
+fn test_abstract_shape() {
+    // two hairpins, the first interrupted by a bulge
+    let paired = from_dotbracketstring("..((((...))..))..((...))..").unwrap();
+    assert_eq!(abstract_shape(&paired, 1).unwrap(), "_[[_]_]_[_]_");
+    assert_eq!(abstract_shape(&paired, 2).unwrap(), "_[[]]_[]_");
+    assert_eq!(abstract_shape(&paired, 3).unwrap(), "[[]][]");
+    assert_eq!(abstract_shape(&paired, 4).unwrap(), "_[]_[]_");
+    assert_eq!(abstract_shape(&paired, 5).unwrap(), "[][]");
+
+    // a multiloop closed by an internal loop
+    let paired = from_dotbracketstring("((.((.((...))..((...)).)).))").unwrap();
+    assert_eq!(abstract_shape(&paired, 1).unwrap(), "[_[_[_]_[_]_]_]");
+    assert_eq!(abstract_shape(&paired, 2).unwrap(), "[[_[]_[]_]]");
+    assert_eq!(abstract_shape(&paired, 3).unwrap(), "[[[][]]]");
+    assert_eq!(abstract_shape(&paired, 4).unwrap(), "[_[]_[]_]");
+    assert_eq!(abstract_shape(&paired, 5).unwrap(), "[[][]]");
+
+    assert_eq!(abstract_shape(&from_dotbracketstring("....").unwrap(), 5).unwrap(), "");
+    assert!(matches!(abstract_shape(&from_dotbracketstring("((..[[..))..]]").unwrap(), 5), Err(StructureParseError::Pseudoknotted)));
+    assert!(matches!(abstract_shape(&paired, 6), Err(StructureParseError::ShapeLevelNotRecognised { level: 6 })));
+}
+
+#[test]
+fn test_add_and_remove_base_pair() {
+    let mut ss: SecondaryStructureRecord = "((....))".parse().unwrap();
+    ss.add_base_pair(5, 2).unwrap();
+    assert_eq!(ss.paired, vec![8, 7, 6, 0, 0, 3, 2, 1]);
+
+    // overlapping, self, and out of range base-pairs are rejected without changes
+    assert!(matches!(ss.add_base_pair(3, 5), Err(StructureParseError::InvalidBasePair { i: 3, j: 5, .. })));
+    assert!(ss.add_base_pair(3, 3).is_err());
+    assert!(ss.add_base_pair(3, 8).is_err());
+    assert_eq!(ss.get_dot_bracket_string().unwrap(), "(((..)))");
+
+    ss.remove_base_pair(6);
+    assert_eq!(ss.paired, vec![8, 0, 6, 0, 0, 3, 0, 1]);
+    ss.remove_base_pair(3);
+    ss.remove_base_pair(100);
+    assert_eq!(ss.get_dot_bracket_string().unwrap(), "(.(..).)");
+}
+
+#[test]
+fn test_concat() {
+    let mut ss: SecondaryStructureRecord = "(.)".parse().unwrap();
+    ss.name = "first".to_string();
+    ss.set_sequence("GAC".to_string());
+    let mut other: SecondaryStructureRecord = "(())".parse().unwrap();
+    other.set_sequence("GGCC".to_string());
+
+    let joined = ss.concat(&other);
+    assert_eq!(joined.name, "first");
+    assert_eq!(joined.sequence, "GACGGCC");
+    assert_eq!(joined.paired, vec![3, 0, 1, 7, 6, 5, 4]);
+    assert!(joined.validate().is_ok());
+    assert_eq!(other.concat(&ss).paired, vec![4, 3, 2, 1, 7, 0, 5]);
+}
+
+#[test]
+fn test_slice() {
+    let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    ss.set_sequence("GGAACC".to_string());
+    ss.annotation = Some("SSHHSS".to_string());
+    ss.energy = Some(-1.0);
+
+    // the outer base-pair has both positions outside the window
+    let sliced = ss.slice(1..5);
+    assert_eq!(sliced.sequence, "GAAC");
+    assert_eq!(sliced.paired, vec![4, 0, 0, 1]);
+    assert_eq!(sliced.annotation, Some("SHHS".to_string()));
+    assert_eq!(sliced.energy, None);
+
+    // base-pairs with a single position outside the window are removed
+    assert_eq!(ss.slice(0..5).paired, vec![0, 5, 0, 0, 2]);
+    assert_eq!(ss.slice(0..6).paired, ss.paired);
+    assert!(ss.slice(3..3).paired.is_empty());
+}
+
+#[test]
+fn test_record_hash() {
+    use std::collections::HashSet;
+
+    let mut ss: SecondaryStructureRecord = "((..))".parse().unwrap();
+    ss.set_sequence("GGAACC".to_string());
+    let mut same: SecondaryStructureRecord = "((..))".parse().unwrap();
+    same.set_sequence("GGAACC".to_string());
+    let mut set = HashSet::new();
+    set.insert(ss.clone());
+    set.insert(same);
+    assert_eq!(set.len(), 1);
+
+    let mut other = ss.clone();
+    other.paired = vec![0; 6];
+    set.insert(other);
+    assert_eq!(set.len(), 2);
+
+    // records differing only in energy are distinct, despite hashing identically
+    let mut other = ss.clone();
+    other.energy = Some(-2.0);
+    set.insert(other);
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn test_from_base_pairs() {
+    let ss = from_base_pairs(6, &[(0, 5), (1, 4)]).unwrap();
+    assert_eq!(ss.paired, vec![6, 5, 0, 0, 2, 1]);
+    assert_eq!(ss.get_dot_bracket_string().unwrap(), "((..))");
+    assert_eq!(ss.sequence, "NNNNNN");
+    assert_eq!(from_base_pairs(6, &[(4, 1), (5, 0)]).unwrap().paired, ss.paired);
+    assert_eq!(from_base_pairs(6, &[(0, 3), (2, 5)]).unwrap().get_dot_bracket_string().unwrap(), "(.<).>");
+
+    // positions claimed twice, paired with themselves, or out of range
+    assert!(matches!(from_base_pairs(6, &[(0, 5), (1, 5)]), Err(StructureParseError::InvalidBasePair { i: 1, j: 5, .. })));
+    assert!(from_base_pairs(6, &[(2, 2)]).is_err());
+    assert!(from_base_pairs(6, &[(0, 6)]).is_err());
+    assert!(from_base_pairs(0, &[]).unwrap().paired.is_empty());
+}
+
+#[test]
+fn test_num_base_pairs_and_paired_fraction() {
+    let paired = from_dotbracketstring("((..))").unwrap();
+    assert_eq!(num_base_pairs(&paired), 2);
+    assert_eq!(paired_fraction(&paired), 4.0 / 6.0);
+
+    let pseudoknotted = from_dotbracketstring("((..[[..))..]]").unwrap();
+    assert_eq!(num_base_pairs(&pseudoknotted), 4);
+    assert_eq!(paired_fraction(&SecondaryStructureRecord::new(vec![0; 4])), 0.0);
+    assert_eq!(num_base_pairs(&Vec::new()), 0);
+    assert_eq!(paired_fraction(&Vec::new()), 0.0);
+}
+
+#[test]
+fn test_crossing_pairs() {
+    // an H-type pseudoknot: every pair of the first helix crosses every pair of the second
+    let paired = from_dotbracketstring("((..[[..))..]]").unwrap();
+    assert_eq!(crossing_pairs(&paired), vec![((0, 9), (4, 13)), ((0, 9), (5, 12)), ((1, 8), (4, 13)), ((1, 8), (5, 12))]);
+
+    let three_pages = from_dotbracketstring("(.[.{.).].}").unwrap();
+    assert_eq!(crossing_pairs(&three_pages), vec![((0, 6), (2, 8)), ((0, 6), (4, 10)), ((2, 8), (4, 10))]);
+
+    assert!(crossing_pairs(&from_dotbracketstring("((..))..((...))").unwrap()).is_empty());
+    assert!(crossing_pairs(&from_dotbracketstring("......").unwrap()).is_empty());
+}
+
+#[test]
+fn test_is_pseudoknotted_multi_page() {
+    for dbs in &["(.[.{.).].}", "((.[[.{{.)).]].}}", "(<.)>", "A(B)a<b>", "((..[[..))..]]..{{..<<..}}..>>"] {
+        let paired = from_dotbracketstring(dbs).unwrap();
+        assert!(is_pseudoknotted(&paired).unwrap(), "{}", dbs);
+    }
+    for dbs in &["", "....", "((..))[[..]]", "(<{[..]}>)"] {
+        let paired = from_dotbracketstring(dbs).unwrap();
+        assert!(!is_pseudoknotted(&paired).unwrap(), "{}", dbs);
+    }
+
+    // position 2 is paired with position 1, but position 1 is unpaired
+    assert!(matches!(is_pseudoknotted(&vec![0, 1, 0]), Err(StructureParseError::InconsistentPairing { i: 2, j: 1 })));
+    assert!(is_pseudoknotted(&vec![5, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_get_wuss_string() {
+    assert_eq!(get_wuss_string(&from_dotbracketstring("(((...)))").unwrap()).unwrap(), "<<<___>>>");
+    // a bulge on the 5' side and an internal loop, with external single-stranded ends
+    let paired = from_dotbracketstring(".((.((..((...))))..)).").unwrap();
+    assert_eq!(get_wuss_string(&paired).unwrap(), ":<<-<<--<<___>>>>-->>:");
+    assert_eq!(from_wuss_string(&get_wuss_string(&paired).unwrap()).unwrap(), paired);
+    assert_eq!(get_wuss_string(&from_dotbracketstring("...").unwrap()).unwrap(), ":::");
+
+    assert!(matches!(get_wuss_string(&from_dotbracketstring("((..[[..))..]]").unwrap()), Err(StructureParseError::Pseudoknotted)));
+}
+
+#[test]
+fn test_record_len() {
+    let mut ss = SecondaryStructureRecord::new(from_dotbracketstring("(..)").unwrap());
+    assert_eq!(ss.sequence_len(), 4);
+    ss.set_sequence("AC".to_string());
+    assert_eq!(ss.len(), 4);
+    assert_eq!(ss.structure_len(), 4);
+    assert_eq!(ss.sequence_len(), 2);
+    assert!(!ss.is_empty());
+
+    let empty = SecondaryStructureRecord::new(Vec::new());
+    assert!(empty.is_empty());
+    assert_eq!(empty.len(), 0);
+}
+
+#[test]
+fn test_record_builder() {
+    let ss = SecondaryStructureRecord::builder().name("example").sequence("GGAAACC").dot_bracket("((...))").build().unwrap();
+    assert_eq!(ss.name, "example");
+    assert_eq!(ss.sequence, "GGAAACC");
+    assert_eq!(ss.paired, from_dotbracketstring("((...))").unwrap());
+    assert_eq!(ss.energy, None);
+
+    // the structure set last is used
+    let ss = SecondaryStructureRecord::builder().dot_bracket("((...))").paired(vec![0, 3, 2]).build().unwrap();
+    assert_eq!(ss.paired, vec![0, 3, 2]);
+    assert_eq!(ss.sequence, "NNN");
+    assert_eq!(SecondaryStructureRecord::builder().sequence("ACGU").build().unwrap().paired, vec![0; 4]);
+
+    let err = SecondaryStructureRecord::builder().sequence("GGAAAC").dot_bracket("((...))").build().unwrap_err();
+    assert!(matches!(err, StructureParseError::LengthMismatch { sequence_len: 6, structure_len: 7 }));
+    assert!(matches!(SecondaryStructureRecord::builder().dot_bracket("((...)").build(), Err(StructureParseError::MissingRightParentheses { .. })));
+    assert!(matches!(SecondaryStructureRecord::builder().paired(vec![2, 0]).build(), Err(StructureParseError::InconsistentPairing { .. })));
+}
+
+#[test]
+fn test_max_nesting_depth() {
+    assert_eq!(max_nesting_depth(&from_dotbracketstring("(((...)))").unwrap()), 3);
+    assert_eq!(max_nesting_depth(&from_dotbracketstring("....").unwrap()), 0);
+    assert_eq!(max_nesting_depth(&Vec::new()), 0);
+    assert_eq!(max_nesting_depth(&from_dotbracketstring("((.((...)).((...))))").unwrap()), 4);
+    // an H-type pseudoknot: both helices are open between positions 5 and 8
+    assert_eq!(max_nesting_depth(&from_dotbracketstring("((..[[..))..]]").unwrap()), 4);
+}
+
+#[test]
+fn test_lonely_pairs() {
+    let paired = from_dotbracketstring("(.(...).)").unwrap();
+    assert_eq!(lonely_pairs(&paired), vec![(0, 8), (2, 6)]);
+    assert_eq!(remove_lonely_pairs(&paired), vec![0; 9]);
+
+    // one lonely pair closing a stacked helix
+    let paired = from_dotbracketstring("(.((...))..)").unwrap();
+    assert_eq!(lonely_pairs(&paired), vec![(0, 11)]);
+    assert_eq!(remove_lonely_pairs(&paired), from_dotbracketstring("..((...))...").unwrap());
+
+    // lonely pseudoknotted pairs are found as well
+    let paired = from_dotbracketstring("((..[..))..]").unwrap();
+    assert_eq!(lonely_pairs(&paired), vec![(4, 11)]);
+    assert!(lonely_pairs(&from_dotbracketstring("((...))").unwrap()).is_empty());
+}
+
+#[test]
+fn test_pretty_print() {
+    let mut ss: SecondaryStructureRecord = "((((.....))))..".parse().unwrap();
+    ss.name = "example".to_string();
+    let lines = ss.pretty_print().lines().map(|line| line.to_string()).collect::<Vec<String>>();
+    assert_eq!(lines, vec![">example", "NNNNNNNNNNNNNNN", "((((.....))))..", "        10", ".........|....."]);
+    // the marker for position 10 is at 0-based column 9, beneath the end of its label
+    assert_eq!(lines[4].find('|'), Some(9));
+    assert_eq!(lines[3].rfind('0'), Some(9));
+
+    let longer = SecondaryStructureRecord::new(vec![0; 25]).pretty_print();
+    assert_eq!(longer.lines().nth(3).unwrap(), "        10        20");
+    assert_eq!(longer.lines().nth(4).unwrap(), ".........|.........|.....");
+    // Display is unchanged
+    assert_eq!(ss.to_string(), ">example\nNNNNNNNNNNNNNNN\n((((.....))))..");
+}
+
+#[test]
+fn test_shift() {
+    let paired = from_dotbracketstring("(.)").unwrap();
+    assert_eq!(shift(&paired, 5, 10).unwrap(), vec![0, 0, 0, 0, 0, 8, 0, 6, 0, 0]);
+    assert_eq!(shift(&paired, 7, 10).unwrap(), from_dotbracketstring(".......(.)").unwrap());
+    assert!(matches!(shift(&paired, 8, 10), Err(StructureParseError::InvalidBasePair { i: 0, j: 2, .. })));
+    assert!(shift(&paired, -1, 10).is_err());
+    // unpaired positions may be shifted out of range
+    assert_eq!(shift(&from_dotbracketstring("..((..))..").unwrap(), -2, 6).unwrap(), from_dotbracketstring("((..))").unwrap());
+}